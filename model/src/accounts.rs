@@ -0,0 +1,593 @@
+//! User accounts and sessions.
+//!
+//! Users are kept in supplementary tables, rather than as [`schema`](crate::schema) resources, so
+//! that private information like password hashes and email addresses is never exposed by the
+//! generic resource queries.
+//!
+//! Passwords are stored as salted Argon2 hashes. Signing in creates a session, identified by a
+//! random bearer token which the client sends in the `Authorization` header of each request. Only a
+//! SHA-256 hash of each token is stored, so the contents of the database are not enough to
+//! impersonate a user. The server validates the token before executing a request, and passes the
+//! resulting [`Viewer`] to the GraphQL API as request data.
+//!
+//! Signing in takes about as long whether or not the username exists, so that timing doesn't
+//! reveal which usernames are registered, and after too many failed attempts for a username from
+//! one client address, further attempts from that address are refused for a while, to slow down
+//! password guessing. Attempts from other addresses are counted separately, so that guessing a
+//! user's password from one place doesn't lock the user out everywhere else. Email addresses are
+//! compared case-insensitively.
+
+use crate::{
+    db::{self, SqlClient},
+    follows::{self, FollowKind, Followed},
+    notifications::{self, NotificationPreference},
+    schema,
+};
+use anyhow::Error;
+use argon2::{
+    password_hash::{
+        rand_core::{OsRng, RngCore},
+        PasswordHash, PasswordHasher, PasswordVerifier, SaltString,
+    },
+    Argon2,
+};
+use async_graphql::{ComplexObject, Context, Object, SimpleObject};
+use base64::prelude::*;
+use chrono::{DateTime, Utc};
+use relational_graphql::graphql::type_system::Id;
+use sha2::{Digest, Sha256};
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+/// How long a session lasts before the user has to sign in again.
+const SESSION_DAYS: i32 = 30;
+
+/// The minimum length of a password, in characters.
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// The maximum length of a username, in characters.
+const MAX_USERNAME_LEN: usize = 32;
+
+/// The number of failed sign-in attempts allowed for a username from one client address within
+/// [`LOGIN_WINDOW_MINUTES`].
+const MAX_FAILED_LOGINS: i32 = 5;
+
+/// How long failed sign-in attempts count against a username and client address.
+const LOGIN_WINDOW_MINUTES: i32 = 15;
+
+/// The user on whose behalf a request is being made.
+///
+/// This is attached to each GraphQL request by the server. If the request is not authenticated,
+/// `user` is `None`.
+#[derive(Clone, Debug, Default)]
+pub struct Viewer {
+    /// The ID of the signed-in user.
+    pub user: Option<i32>,
+    /// The hash of the token for the current session.
+    token_hash: Option<String>,
+    /// The address of the client making the request, if the server knows it.
+    pub client_addr: Option<IpAddr>,
+}
+
+impl Viewer {
+    /// Get the viewer of a GraphQL request.
+    pub fn of(ctx: &Context<'_>) -> Self {
+        ctx.data_opt::<Self>().cloned().unwrap_or_default()
+    }
+
+    /// The ID of the signed-in user, or an error if the request is not authenticated.
+    pub fn require(&self) -> async_graphql::Result<i32> {
+        self.user.ok_or_else(|| "you must be signed in".into())
+    }
+
+    /// The ID of the signed-in user, or an error if they are not an administrator.
+    pub async fn require_admin(&self, sql: &SqlClient) -> async_graphql::Result<i32> {
+        let user = self.require()?;
+        if is_admin(sql, user).await? {
+            Ok(user)
+        } else {
+            Err("you must be an administrator".into())
+        }
+    }
+}
+
+/// A registered user.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct User {
+    pub id: i32,
+    /// The name the user signs in with, which is also shown to other users.
+    pub username: String,
+    /// When the user registered.
+    pub created_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub email: String,
+    #[graphql(skip)]
+    pub history_enabled: bool,
+    #[graphql(skip)]
+    pub district: Option<Id>,
+}
+
+#[ComplexObject]
+impl User {
+    /// The user's email address.
+    ///
+    /// This is only visible to the user themself.
+    async fn email(&self, ctx: &Context<'_>) -> Option<&str> {
+        if Viewer::of(ctx).user == Some(self.id) {
+            Some(&self.email)
+        } else {
+            None
+        }
+    }
+
+    /// The bills, legislators, issues and states the user follows, most recently followed first.
+    ///
+    /// If `kind` is given, only entities of that kind are returned. This is only visible to the
+    /// user themself.
+    async fn following(
+        &self,
+        ctx: &Context<'_>,
+        kind: Option<FollowKind>,
+    ) -> async_graphql::Result<Option<Vec<Followed>>> {
+        if Viewer::of(ctx).user != Some(self.id) {
+            return Ok(None);
+        }
+        Ok(Some(follows::following(ctx.data()?, self.id, kind).await?))
+    }
+
+    /// The district the user lives in, which identifies them as a constituent of its legislators.
+    ///
+    /// This is only visible to the user themself.
+    async fn district(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::District>> {
+        match self.district {
+            Some(id) if Viewer::of(ctx).user == Some(self.id) => {
+                Ok(db::district_by_id(ctx.data()?, id).await?)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// How the user wants to be notified about changes to each kind of entity they follow.
+    ///
+    /// This is only visible to the user themself.
+    async fn notification_preferences(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<Vec<NotificationPreference>>> {
+        if Viewer::of(ctx).user != Some(self.id) {
+            return Ok(None);
+        }
+        Ok(Some(
+            notifications::preferences(ctx.data()?, self.id).await?,
+        ))
+    }
+
+    /// Whether the user's browsing history is being recorded.
+    ///
+    /// This is only visible to the user themself.
+    async fn history_enabled(&self, ctx: &Context<'_>) -> Option<bool> {
+        if Viewer::of(ctx).user == Some(self.id) {
+            Some(self.history_enabled)
+        } else {
+            None
+        }
+    }
+}
+
+/// A newly created session.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct Session {
+    /// The bearer token identifying the session.
+    ///
+    /// Send this in the `Authorization` header of subsequent requests, as `Bearer <token>`.
+    pub token: String,
+    /// When the session expires.
+    pub expires_at: DateTime<Utc>,
+    /// The signed-in user.
+    pub user: User,
+}
+
+/// Queries about the signed-in user.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AccountQuery;
+
+#[Object]
+impl AccountQuery {
+    /// The signed-in user, or `null` if the request is not authenticated.
+    async fn me(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        match Viewer::of(ctx).user {
+            Some(id) => Ok(user_by_id(ctx.data()?, id).await?),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Mutations for registering and signing in.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AccountMutation;
+
+#[Object]
+impl AccountMutation {
+    /// Create a new account and sign in to it.
+    async fn register(
+        &self,
+        ctx: &Context<'_>,
+        username: String,
+        email: String,
+        password: String,
+    ) -> async_graphql::Result<Session> {
+        let sql = ctx.data()?;
+        let user = register(sql, &username, &email, &password).await?;
+        Ok(start_session(sql, user).await?)
+    }
+
+    /// Sign in to an existing account.
+    async fn login(
+        &self,
+        ctx: &Context<'_>,
+        username: String,
+        password: String,
+    ) -> async_graphql::Result<Session> {
+        let sql = ctx.data()?;
+        match login(sql, &username, &password, Viewer::of(ctx).client_addr).await? {
+            Some(user) => Ok(start_session(sql, user).await?),
+            None => Err("invalid username or password".into()),
+        }
+    }
+
+    /// Set the district the signed-in user lives in, or clear it if `district` is `null`.
+    async fn set_district(
+        &self,
+        ctx: &Context<'_>,
+        district: Option<Id>,
+    ) -> async_graphql::Result<User> {
+        let user = Viewer::of(ctx).require()?;
+        let state = match district {
+            Some(id) => match db::district_by_id(ctx.data()?, id).await? {
+                Some(district) => Some(district.home_state().to_string()),
+                None => return Err(format!("no such district {id:?}").into()),
+            },
+            None => None,
+        };
+        let sql = ctx.data()?;
+        set_district(sql, user, district, state).await?;
+        user_by_id(sql, user)
+            .await?
+            .ok_or_else(|| format!("ICE: signed-in user {user} is missing").into())
+    }
+
+    /// End the current session.
+    ///
+    /// Returns whether there was a session to end.
+    async fn logout(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
+        match Viewer::of(ctx).token_hash {
+            Some(token_hash) => {
+                let sql: &SqlClient = ctx.data()?;
+                sql.execute("DELETE FROM sessions WHERE token_hash = $1", &[&token_hash])
+                    .await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+/// Create the tables for users and sessions.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS users (
+            id SERIAL PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            email TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );
+        CREATE TABLE IF NOT EXISTS sessions (
+            token_hash TEXT PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            expires_at TIMESTAMPTZ NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS sessions_user_id ON sessions (user_id);
+        -- Whether to record the user's browsing history.
+        ALTER TABLE users ADD COLUMN IF NOT EXISTS history_enabled BOOLEAN NOT NULL DEFAULT TRUE;
+        -- The district the user lives in, and the abbreviation of the state they live in.
+        ALTER TABLE users
+            ADD COLUMN IF NOT EXISTS district INTEGER,
+            ADD COLUMN IF NOT EXISTS state TEXT;
+        -- Whether the user can moderate other users' content.
+        ALTER TABLE users ADD COLUMN IF NOT EXISTS is_admin BOOLEAN NOT NULL DEFAULT FALSE;
+        -- Email addresses are unique regardless of case.
+        CREATE UNIQUE INDEX IF NOT EXISTS users_email_lower ON users (lower(email));
+        -- Failed sign-in attempts for each username from each client address (or '' if it is not
+        -- known), counted since the start of the current window.
+        CREATE TABLE IF NOT EXISTS login_failures (
+            username TEXT NOT NULL,
+            client TEXT NOT NULL,
+            count INTEGER NOT NULL,
+            window_start TIMESTAMPTZ NOT NULL DEFAULT now(),
+            PRIMARY KEY (username, client)
+        );
+        CREATE INDEX IF NOT EXISTS login_failures_window_start ON login_failures (window_start);",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Create a new user.
+pub async fn register(
+    sql: &SqlClient,
+    username: &str,
+    email: &str,
+    password: &str,
+) -> Result<User, Error> {
+    validate_username(username)?;
+    let email = email.trim().to_lowercase();
+    if !email.contains('@') {
+        return Err(Error::msg(format!("invalid email address {email}")));
+    }
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(Error::msg(format!(
+            "password must be at least {MIN_PASSWORD_LEN} characters"
+        )));
+    }
+
+    let row = sql
+        .query_opt(
+            "INSERT INTO users (username, email, password_hash) VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING
+                RETURNING id, username, email, created_at, history_enabled, district",
+            &[&username, &email, &hash_password(password)?],
+        )
+        .await?
+        .ok_or_else(|| Error::msg("that username or email address is already registered"))?;
+    user_from_row(&row)
+}
+
+/// Check a user's credentials.
+///
+/// `client` is the address of the client making the attempt, if it is known. Returns the user if
+/// the credentials are valid, or an error if there have been too many failed attempts to sign in
+/// as `username` from `client` recently.
+pub async fn login(
+    sql: &SqlClient,
+    username: &str,
+    password: &str,
+    client: Option<IpAddr>,
+) -> Result<Option<User>, Error> {
+    let client = client.map(|addr| addr.to_string()).unwrap_or_default();
+
+    // Forget failures whose window has passed, including those for usernames which don't exist.
+    sql.execute(
+        "DELETE FROM login_failures WHERE window_start < now() - make_interval(mins => $1)",
+        &[&LOGIN_WINDOW_MINUTES],
+    )
+    .await?;
+
+    // Count this attempt as a failure up front, so that concurrent attempts can't all slip in
+    // under the limit. The row lock taken by the upsert serializes them.
+    let failures: i32 = sql
+        .query_one(
+            "INSERT INTO login_failures AS f (username, client, count) VALUES ($1, $3, 1)
+                ON CONFLICT (username, client) DO UPDATE SET
+                    count = CASE
+                        WHEN f.window_start < now() - make_interval(mins => $2) THEN 1
+                        ELSE f.count + 1
+                    END,
+                    window_start = CASE
+                        WHEN f.window_start < now() - make_interval(mins => $2) THEN now()
+                        ELSE f.window_start
+                    END
+                RETURNING count",
+            &[&username, &LOGIN_WINDOW_MINUTES, &client],
+        )
+        .await?
+        .try_get("count")?;
+    if failures > MAX_FAILED_LOGINS {
+        return Err(Error::msg(
+            "too many failed attempts to sign in; try again later",
+        ));
+    }
+
+    let row = sql
+        .query_opt(
+            "SELECT id, username, email, created_at, history_enabled, district, password_hash
+                FROM users WHERE username = $1",
+            &[&username],
+        )
+        .await?;
+    // Verify against a dummy hash if there is no such user, so that this takes as long as it would
+    // for a real one.
+    let hash = match &row {
+        Some(row) => row.try_get::<_, String>("password_hash")?,
+        None => dummy_hash()?.to_string(),
+    };
+    let valid = verify_password(password, &hash)?;
+    match row {
+        Some(row) if valid => {
+            sql.execute(
+                "DELETE FROM login_failures WHERE username = $1 AND client = $2",
+                &[&username, &client],
+            )
+            .await?;
+            Ok(Some(user_from_row(&row)?))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Start a new session for `user`.
+pub async fn start_session(sql: &SqlClient, user: User) -> Result<Session, Error> {
+    let mut bytes = [0; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let token = BASE64_URL_SAFE_NO_PAD.encode(bytes);
+    let row = sql
+        .query_one(
+            "INSERT INTO sessions (token_hash, user_id, expires_at)
+                VALUES ($1, $2, now() + make_interval(days => $3))
+                RETURNING expires_at",
+            &[&hash_token(&token), &user.id, &SESSION_DAYS],
+        )
+        .await?;
+    Ok(Session {
+        token,
+        expires_at: row.try_get("expires_at")?,
+        user,
+    })
+}
+
+/// Find the viewer identified by a session token.
+///
+/// Returns `None` if the token does not identify a current session.
+pub async fn authenticate(sql: &SqlClient, token: &str) -> Result<Option<Viewer>, Error> {
+    let token_hash = hash_token(token);
+    let row = sql
+        .query_opt(
+            "SELECT user_id FROM sessions WHERE token_hash = $1 AND expires_at > now()",
+            &[&token_hash],
+        )
+        .await?;
+    match row {
+        Some(row) => Ok(Some(Viewer {
+            user: Some(row.try_get("user_id")?),
+            token_hash: Some(token_hash),
+            client_addr: None,
+        })),
+        None => Ok(None),
+    }
+}
+
+/// Whether `user` is an administrator.
+pub async fn is_admin(sql: &SqlClient, user: i32) -> Result<bool, Error> {
+    let row = sql
+        .query_opt("SELECT is_admin FROM users WHERE id = $1", &[&user])
+        .await?;
+    match row {
+        Some(row) => Ok(row.try_get("is_admin")?),
+        None => Ok(false),
+    }
+}
+
+/// Grant or revoke administrator privileges for the user named `username`.
+pub async fn set_admin(sql: &SqlClient, username: &str, admin: bool) -> Result<(), Error> {
+    let updated = sql
+        .execute(
+            "UPDATE users SET is_admin = $2 WHERE username = $1",
+            &[&username, &admin],
+        )
+        .await?;
+    if updated == 0 {
+        return Err(Error::msg(format!("no such user {username}")));
+    }
+    Ok(())
+}
+
+/// Load a user by ID.
+pub async fn user_by_id(sql: &SqlClient, id: i32) -> Result<Option<User>, Error> {
+    sql.query_opt(
+        "SELECT id, username, email, created_at, history_enabled, district FROM users
+            WHERE id = $1",
+        &[&id],
+    )
+    .await?
+    .map(|row| user_from_row(&row))
+    .transpose()
+}
+
+fn user_from_row(row: &tokio_postgres::Row) -> Result<User, Error> {
+    Ok(User {
+        id: row.try_get("id")?,
+        username: row.try_get("username")?,
+        email: row.try_get("email")?,
+        created_at: row.try_get("created_at")?,
+        history_enabled: row.try_get("history_enabled")?,
+        district: row.try_get("district")?,
+    })
+}
+
+/// Set the district `user` lives in, and the abbreviation of the state they live in.
+///
+/// For a congressional district, the state is its [home state](schema::District::home_state).
+pub async fn set_district(
+    sql: &SqlClient,
+    user: i32,
+    district: Option<Id>,
+    state: Option<String>,
+) -> Result<(), Error> {
+    sql.execute(
+        "UPDATE users SET district = $2, state = $3 WHERE id = $1",
+        &[&user, &district, &state],
+    )
+    .await?;
+    Ok(())
+}
+
+fn validate_username(username: &str) -> Result<(), Error> {
+    let len = username.chars().count();
+    if len == 0 || len > MAX_USERNAME_LEN {
+        return Err(Error::msg(format!(
+            "username must be between 1 and {MAX_USERNAME_LEN} characters"
+        )));
+    }
+    if !username
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(Error::msg(
+            "username may only contain letters, numbers, underscores and hyphens",
+        ));
+    }
+    Ok(())
+}
+
+fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|err| Error::msg(format!("unable to hash password: {err}")))?
+        .to_string())
+}
+
+/// A hash of a random password, to verify against when there is no real hash to check.
+fn dummy_hash() -> Result<&'static str, Error> {
+    static HASH: OnceLock<String> = OnceLock::new();
+    if let Some(hash) = HASH.get() {
+        return Ok(hash);
+    }
+    let mut password = [0; 32];
+    OsRng.fill_bytes(&mut password);
+    let hash = hash_password(&BASE64_STANDARD.encode(password))?;
+    Ok(HASH.get_or_init(|| hash))
+}
+
+fn verify_password(password: &str, hash: &str) -> Result<bool, Error> {
+    let hash = PasswordHash::new(hash)
+        .map_err(|err| Error::msg(format!("malformed password hash: {err}")))?;
+    Ok(Argon2::default()
+        .verify_password(password.as_bytes(), &hash)
+        .is_ok())
+}
+
+fn hash_token(token: &str) -> String {
+    BASE64_STANDARD.encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_password_hash() {
+        let hash = hash_password("correct horse").unwrap();
+        assert!(verify_password("correct horse", &hash).unwrap());
+        assert!(!verify_password("battery staple", &hash).unwrap());
+
+        // Hashes are salted, so the same password hashes differently each time.
+        assert_ne!(hash, hash_password("correct horse").unwrap());
+
+        // The dummy hash is a valid hash, which no password is likely to match.
+        assert!(!verify_password("correct horse", dummy_hash().unwrap()).unwrap());
+        assert_eq!(dummy_hash().unwrap(), dummy_hash().unwrap());
+    }
+}
@@ -0,0 +1,141 @@
+//! The legislative history of bills.
+//!
+//! Legiscan lists the actions taken on each bill, like being introduced, referred to a committee,
+//! or passed, in order. [`db::update`] records the actions on each new or changed bill in a
+//! supplementary table, `bill_actions`, numbered by their position in the bill's history. Legiscan
+//! only ever appends to a bill's history, so actions which have already been recorded are skipped.
+//!
+//! Unlike feed events, which are stamped with the time they were pulled from Legiscan, actions
+//! carry the date on which they actually happened, which is what [trending](crate::trending)
+//! scores are based on.
+
+use crate::{
+    db::{self, SqlClient},
+    legiscan::{self, State},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{ComplexObject, Context, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
+use relational_graphql::graphql::type_system::Id;
+
+/// An action taken on a bill.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct BillAction {
+    /// The position of this action in the bill's history, starting from 0.
+    pub sequence: i32,
+    /// The date of the action, in the form YYYY-MM-DD.
+    pub date: String,
+    /// What happened, like "Introduced In House - Assigned to Education".
+    pub description: String,
+    /// The name of the chamber in which the action was taken, or `null` for actions outside the
+    /// legislature.
+    pub chamber: Option<String>,
+    /// Whether this is a major step in the bill's progress, like passing a chamber.
+    pub major: bool,
+    /// When the action was first pulled from Legiscan.
+    pub recorded_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub bill: Id,
+}
+
+#[ComplexObject]
+impl BillAction {
+    /// The bill the action was taken on.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        Ok(db::bill_by_id(ctx.data()?, self.bill).await?)
+    }
+}
+
+/// Queries for the history of bills.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ActionQuery;
+
+#[Object]
+impl ActionQuery {
+    /// The actions taken on a bill, oldest first.
+    async fn bill_actions(
+        &self,
+        ctx: &Context<'_>,
+        bill: Id,
+    ) -> async_graphql::Result<Vec<BillAction>> {
+        Ok(actions(ctx.data()?, bill).await?)
+    }
+}
+
+/// Create the table of actions.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS bill_actions (
+            bill INTEGER NOT NULL,
+            sequence INTEGER NOT NULL,
+            date TEXT NOT NULL,
+            description TEXT NOT NULL,
+            chamber TEXT,
+            major BOOLEAN NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            PRIMARY KEY (bill, sequence)
+        );",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Record the actions taken on `bill`, given its full history.
+///
+/// Returns the actions which had not been recorded before.
+pub async fn record(
+    sql: &SqlClient,
+    bill: &schema::Bill,
+    history: &[legiscan::Action],
+) -> Result<Vec<BillAction>, Error> {
+    let state: State = bill.state.abbreviation.parse()?;
+    let recorded = try_join_all(history.iter().enumerate().map(|(i, action)| async move {
+        let chamber = action.chamber.map(|chamber| state.chamber_name(chamber));
+        sql.query_opt(
+            "INSERT INTO bill_actions (bill, sequence, date, description, chamber, major)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (bill, sequence) DO NOTHING
+                RETURNING *",
+            &[
+                &bill.id,
+                &(i as i32),
+                &action.date,
+                &action.description,
+                &chamber,
+                &action.major,
+            ],
+        )
+        .await?
+        .map(|row| action_from_row(&row))
+        .transpose()
+    }))
+    .await?;
+    Ok(recorded.into_iter().flatten().collect())
+}
+
+/// Load the actions taken on `bill`, oldest first.
+pub async fn actions(sql: &SqlClient, bill: Id) -> Result<Vec<BillAction>, Error> {
+    sql.query(
+        "SELECT * FROM bill_actions WHERE bill = $1 ORDER BY sequence",
+        &[&bill],
+    )
+    .await?
+    .iter()
+    .map(action_from_row)
+    .collect()
+}
+
+fn action_from_row(row: &tokio_postgres::Row) -> Result<BillAction, Error> {
+    Ok(BillAction {
+        bill: row.try_get("bill")?,
+        sequence: row.try_get("sequence")?,
+        date: row.try_get("date")?,
+        description: row.try_get("description")?,
+        chamber: row.try_get("chamber")?,
+        major: row.try_get("major")?,
+        recorded_at: row.try_get("created_at")?,
+    })
+}
@@ -1,11 +1,31 @@
 use clap::Parser;
 use model::{
-    db, init_logging,
-    legiscan::{Legiscan, LocalClient},
+    accounts,
+    classifier::Classifier,
+    comments, db,
+    follows::{self, FollowKind},
+    init_logging,
+    legiscan::{Legiscan, LocalClient, State},
+    stances::{self, Position},
+    taxonomy::Taxonomy,
+    trending,
 };
+use relational_graphql::graphql::type_system::Id;
 use std::path::PathBuf;
 use surf::Url;
 
+/// Constituents who take stances on the bill with ID 1, so that the test cases can count them.
+///
+/// Each entry is a username, the district the user lives in and the state containing it, and their
+/// position. Pierce lives in a congressional district, which belongs to the federal state.
+const CONSTITUENTS: [(&str, State, &str, Position); 5] = [
+    ("troy", State::CO, "HD-001", Position::Support),
+    ("britta", State::CO, "HD-002", Position::Oppose),
+    ("dean", State::CO, "HD-001", Position::Neutral),
+    ("shirley", State::DC, "SD-001", Position::Support),
+    ("pierce", State::US, "HD-CO-2", Position::Support),
+];
+
 /// Set up a test database and seed it with a small amount of data for easy testing.
 #[derive(Parser)]
 struct Options {
@@ -28,6 +48,16 @@ struct Options {
     )]
     dir: PathBuf,
 
+    /// Configuration file describing the hierarchy of issues.
+    #[clap(
+        short,
+        long,
+        env = "BILL_TRACKER_TAXONOMY",
+        name = "FILE",
+        default_value = "db/taxonomy.json"
+    )]
+    taxonomy: PathBuf,
+
     /// URL for connecting to the Postgres database.
     #[clap(
         long,
@@ -60,14 +90,110 @@ async fn main() -> Result<(), anyhow::Error> {
         db_password: opt.db_password,
     };
     let mut conn = db_opt.connect().await?;
+    let sql = db_opt.connect_sql().await?;
 
     // Set up the schema.
-    db::setup(&mut conn).await?;
+    let taxonomy = Taxonomy::load(&opt.taxonomy)?;
+    db::setup(&mut conn, &sql, &taxonomy).await?;
 
     // Insert test data.
     let client = LocalClient::open(opt.dir);
     let datasets = client.list_datasets(None, None).await?;
-    db::update::<_, PathBuf>(&mut conn, &client, datasets, None).await?;
+    db::update::<_, PathBuf>(&mut conn, &sql, &client, datasets, None).await?;
+
+    // Suggest issues for the test data.
+    db::retag(&mut conn, Classifier::new(&taxonomy)).await?;
+
+    // Seed activity by users. The legislative activity in the test data is too old to count
+    // towards trending scores, so a user following the DC bill is what makes it trending.
+    let user = accounts::register(&sql, "test", "test@example.com", "password").await?;
+    let bill: Id = sql
+        .query_one(
+            "SELECT id FROM catalog_bills WHERE state = 'DC' AND name = 'PR01-0001'",
+            &[],
+        )
+        .await?
+        .try_get("id")?;
+    follows::follow(&sql, user.id, FollowKind::Bill, bill).await?;
+    trending::refresh(&sql).await?;
+
+    // Seed stances on the first bill from constituents in different states and districts, and
+    // from the test user, who hasn't set their district.
+    let bill: Id = sql
+        .query_one("SELECT min(id) AS id FROM catalog_bills", &[])
+        .await?
+        .try_get("id")?;
+    let mut constituents = vec![];
+    for (username, state, district, position) in CONSTITUENTS {
+        let constituent = accounts::register(
+            &sql,
+            username,
+            &format!("{username}@example.com"),
+            "password",
+        )
+        .await?;
+        let district = db::find_district(&conn, state, district.into())
+            .await?
+            .ok_or_else(|| anyhow::Error::msg(format!("no district {state} {district}")))?;
+        accounts::set_district(
+            &sql,
+            constituent.id,
+            Some(district.id),
+            Some(district.home_state().to_string()),
+        )
+        .await?;
+        stances::set_stance(&sql, constituent.id, bill, position, None).await?;
+        constituents.push(constituent.id);
+    }
+    stances::set_stance(
+        &sql,
+        user.id,
+        bill,
+        Position::Oppose,
+        Some("Too expensive.".into()),
+    )
+    .await?;
+
+    // Seed a discussion of the same bill, with a reply to a reply, a deleted reply and a comment
+    // hidden by a moderator.
+    let (troy, britta, dean, shirley) = (
+        constituents[0],
+        constituents[1],
+        constituents[2],
+        constituents[3],
+    );
+    let first = comments::post_comment(
+        &sql,
+        troy,
+        bill,
+        None,
+        "This would help students in my district.",
+    )
+    .await?;
+    let reply = comments::post_comment(
+        &sql,
+        britta,
+        bill,
+        Some(first.id),
+        "It doesn't say how it would be paid for.",
+    )
+    .await?;
+    let answer = comments::post_comment(
+        &sql,
+        user.id,
+        bill,
+        Some(reply.id),
+        "The fiscal note covers it.",
+    )
+    .await?;
+    let deleted = comments::post_comment(&sql, shirley, bill, Some(first.id), "Agreed!").await?;
+    comments::delete_comment(&sql, shirley, deleted.id).await?;
+    let spam = comments::post_comment(&sql, dean, bill, None, "Streets ahead!").await?;
+    comments::report_comment(&sql, britta, spam.id, "Off topic.").await?;
+    comments::moderate(&sql, spam.id, Some(true)).await?;
+    comments::upvote_comment(&sql, britta, first.id, true).await?;
+    comments::upvote_comment(&sql, troy, answer.id, true).await?;
+    comments::upvote_comment(&sql, shirley, answer.id, true).await?;
 
     Ok(())
 }
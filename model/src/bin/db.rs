@@ -1,15 +1,37 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use model::{
-    db, init_logging,
+    accounts,
+    classifier::Classifier,
+    db, export, init_logging,
     legiscan::{Client, Legiscan, LocalClient, State},
+    migrations,
+    notifications::{self, NotificationMode, SmtpOptions},
+    search,
+    taxonomy::Taxonomy,
+    trending, webhooks,
 };
-use std::path::PathBuf;
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+use strum::IntoEnumIterator;
 
 /// Pull the latest data from Legiscan and update the local database.
 #[derive(Parser)]
 enum Command {
     /// Perform one-time setup of the database.
     Init {
+        /// Configuration file describing the hierarchy of issues.
+        #[clap(
+            short,
+            long,
+            env = "BILL_TRACKER_TAXONOMY",
+            name = "FILE",
+            default_value = "db/taxonomy.json"
+        )]
+        taxonomy: PathBuf,
+
         #[clap(flatten)]
         db: db::Options,
     },
@@ -61,6 +83,156 @@ enum Command {
         #[clap(short, long, env = "LEGISCAN_OUT", name = "DIR")]
         out: Option<PathBuf>,
 
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Suggest issues for bills based on their titles and summaries.
+    ///
+    /// This is useful for categorizing bills which Legiscan has not assigned any subjects.
+    Retag {
+        /// Configuration file describing the hierarchy of issues and their keywords.
+        #[clap(
+            short,
+            long,
+            env = "BILL_TRACKER_TAXONOMY",
+            name = "FILE",
+            default_value = "db/taxonomy.json"
+        )]
+        taxonomy: PathBuf,
+
+        /// Only suggest issues which score at least SCORE (between 0 and 1).
+        #[clap(long, env = "BILL_TRACKER_RETAG_THRESHOLD", name = "SCORE")]
+        threshold: Option<f64>,
+
+        /// Suggest at most N issues for each bill.
+        #[clap(long, env = "BILL_TRACKER_RETAG_MAX_SUGGESTIONS", name = "N")]
+        max_suggestions: Option<usize>,
+
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Grant a user administrator privileges, which allow them to moderate comments.
+    Admin {
+        /// The name of the user.
+        username: String,
+
+        /// Revoke the user's privileges instead.
+        #[clap(long)]
+        revoke: bool,
+
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Email users about changes to the entities they follow.
+    ///
+    /// By default, this sends pending instant notifications and exits.
+    Notify {
+        /// Send daily digests instead of instant notifications.
+        ///
+        /// Run this once a day.
+        #[clap(long)]
+        digest: bool,
+
+        /// Keep running, sending instant notifications as soon as changes are recorded.
+        #[clap(long, conflicts_with = "digest")]
+        watch: bool,
+
+        #[clap(flatten)]
+        smtp: SmtpOptions,
+
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Deliver changes to bills to webhooks.
+    ///
+    /// By default, this attempts the pending deliveries which are due and exits.
+    Webhooks {
+        /// Keep running, delivering changes as soon as they are recorded and retrying failed
+        /// deliveries when they are due.
+        #[clap(long)]
+        watch: bool,
+
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Export the database for analysis in other tools.
+    ///
+    /// Each table is written to its own file in DIR, like `bills.csv`.
+    Export {
+        /// The format to write: csv, jsonl or parquet.
+        #[clap(short, long, default_value = "csv")]
+        format: export::Format,
+
+        /// Write the exported tables to DIR.
+        #[clap(short, long, name = "DIR", default_value = ".")]
+        out: PathBuf,
+
+        #[clap(flatten)]
+        filter: export::ExportFilter,
+
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Load a snapshot written by `export` into an empty database.
+    ///
+    /// The database is set up as with `init` first. Importing into a database which already has
+    /// bills or legislators is refused, and a snapshot which refers to entities it doesn't include
+    /// is rejected before anything is written. If the import fails, it is rolled back, so it can be
+    /// retried.
+    Import {
+        /// The directory containing the snapshot, with one file for each table.
+        #[clap(name = "DIR")]
+        dir: PathBuf,
+
+        /// The format of the snapshot: csv, jsonl or parquet.
+        #[clap(short, long, default_value = "csv")]
+        format: export::Format,
+
+        /// Configuration file describing the hierarchy of issues.
+        #[clap(
+            short,
+            long,
+            env = "BILL_TRACKER_TAXONOMY",
+            name = "FILE",
+            default_value = "db/taxonomy.json"
+        )]
+        taxonomy: PathBuf,
+
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Rebuild the full-text search index from the bills, legislators and issues in the database.
+    Reindex {
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Recompute the trending scores of bills.
+    ///
+    /// Scores decay over time, so this should be run periodically, for example hourly.
+    Trending {
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Manage the version of the database schema.
+    Migrate {
+        #[clap(subcommand)]
+        command: MigrateCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum MigrateCommand {
+    /// Show the version of the database schema and the migrations which have not been applied.
+    Status {
+        #[clap(flatten)]
+        db: db::Options,
+    },
+    /// Apply pending migrations, bringing the database schema up to date.
+    Up {
+        /// Check that the pending migrations would succeed, without applying them.
+        #[clap(long)]
+        dry_run: bool,
+
         #[clap(flatten)]
         db: db::Options,
     },
@@ -71,9 +243,11 @@ async fn main() -> Result<(), anyhow::Error> {
     init_logging();
 
     match Command::parse() {
-        Command::Init { db } => {
+        Command::Init { taxonomy, db } => {
+            let taxonomy = Taxonomy::load(taxonomy)?;
             let mut conn = db.connect().await?;
-            db::setup(&mut conn).await?;
+            let sql = db.connect_sql().await?;
+            db::setup(&mut conn, &sql, &taxonomy).await?;
         }
         Command::Pull {
             api_key,
@@ -87,7 +261,8 @@ async fn main() -> Result<(), anyhow::Error> {
             tracing::info!("{} datasets available", datasets.len());
 
             let mut conn = db.connect().await?;
-            db::update(&mut conn, &client, datasets, out.as_ref()).await?;
+            let sql = db.connect_sql().await?;
+            db::update(&mut conn, &sql, &client, datasets, out.as_ref()).await?;
         }
         Command::Read {
             dir,
@@ -101,7 +276,148 @@ async fn main() -> Result<(), anyhow::Error> {
             tracing::info!("{} datasets available", datasets.len());
 
             let mut conn = db.connect().await?;
-            db::update(&mut conn, &client, datasets, out.as_ref()).await?;
+            let sql = db.connect_sql().await?;
+            db::update(&mut conn, &sql, &client, datasets, out.as_ref()).await?;
+        }
+        Command::Retag {
+            taxonomy,
+            threshold,
+            max_suggestions,
+            db,
+        } => {
+            let taxonomy = Taxonomy::load(taxonomy)?;
+            let mut classifier = Classifier::new(&taxonomy);
+            if let Some(threshold) = threshold {
+                classifier = classifier.with_threshold(threshold);
+            }
+            if let Some(max_suggestions) = max_suggestions {
+                classifier = classifier.with_max_suggestions(max_suggestions);
+            }
+
+            let mut conn = db.connect().await?;
+            db::retag(&mut conn, classifier).await?;
+        }
+        Command::Admin {
+            username,
+            revoke,
+            db,
+        } => {
+            let sql = db.connect_sql().await?;
+            accounts::set_admin(&sql, &username, !revoke).await?;
+        }
+        Command::Notify {
+            digest,
+            watch,
+            smtp,
+            db,
+        } => {
+            let sql = db.connect_sql().await?;
+            let mailer = smtp.connect()?;
+            if watch {
+                notifications::watch(&db, &sql, &mailer).await?;
+            } else {
+                let mode = if digest {
+                    NotificationMode::Daily
+                } else {
+                    NotificationMode::Instant
+                };
+                let sent = notifications::notify(&sql, &mailer, mode).await?;
+                tracing::info!("sent {sent} emails");
+            }
+        }
+        Command::Webhooks { watch, db } => {
+            let sql = db.connect_sql().await?;
+            if watch {
+                webhooks::watch(&db, &sql).await?;
+            } else {
+                let queued = webhooks::enqueue(&sql).await?;
+                let delivered = webhooks::deliver(&sql).await?;
+                tracing::info!("queued {queued} deliveries, delivered {delivered}");
+            }
+        }
+        Command::Export {
+            format,
+            out,
+            filter,
+            db,
+        } => {
+            let mut sql = db.connect_sql().await?;
+            fs::create_dir_all(&out)?;
+            for table in export::Table::iter() {
+                let path = out.join(format.file_name(table));
+                tracing::info!("exporting {table} to {}", path.display());
+                let mut file = BufWriter::new(File::create(&path)?);
+                let mut export = export::Export::start(&mut sql, table, &filter).await?;
+                while let Some(chunk) = export.next_chunk(format).await? {
+                    file.write_all(&chunk)?;
+                }
+                file.flush()?;
+            }
+        }
+        Command::Import {
+            dir,
+            format,
+            taxonomy,
+            db,
+        } => {
+            // Read the whole snapshot before touching the database, so that a bad snapshot doesn't
+            // leave the database half initialized.
+            let tables = export::Table::iter()
+                .map(|table| {
+                    let data = fs::read(dir.join(format.file_name(table)))?;
+                    export::Rows::read(table, format, data)
+                })
+                .collect::<Result<Vec<_>, anyhow::Error>>()?;
+
+            let taxonomy = Taxonomy::load(taxonomy)?;
+            let mut conn = db.connect().await?;
+            let sql = db.connect_sql().await?;
+            db::setup(&mut conn, &sql, &taxonomy).await?;
+            export::import(&db, &sql, &tables).await?;
+        }
+        Command::Reindex { db } => {
+            let conn = db.connect().await?;
+            let sql = db.connect_sql().await?;
+            search::reindex(&conn, &sql).await?;
+        }
+        Command::Trending { db } => {
+            let sql = db.connect_sql().await?;
+            trending::refresh(&sql).await?;
+        }
+        Command::Migrate {
+            command: MigrateCommand::Status { db },
+        } => {
+            let sql = db.connect_sql().await?;
+            let applied = migrations::status(&sql).await?;
+            if applied.is_empty() {
+                println!("schema is unversioned");
+            }
+            for migration in &applied {
+                println!(
+                    "{:>4} {} (applied {})",
+                    migration.version, migration.name, migration.applied_at
+                );
+            }
+            let version = applied.last().map_or(0, |migration| migration.version);
+            for migration in migrations::pending(version) {
+                println!("{:>4} {} (pending)", migration.version, migration.name);
+            }
+            println!("schema is at version {version} of {}", migrations::latest());
+        }
+        Command::Migrate {
+            command: MigrateCommand::Up { dry_run, db },
+        } => {
+            let sql = db.connect_sql().await?;
+            let applied = migrations::up(&db, &sql, dry_run).await?;
+            if dry_run {
+                tracing::info!("{} migrations would be applied", applied.len());
+            } else {
+                tracing::info!(
+                    "applied {} migrations, schema is at version {}",
+                    applied.len(),
+                    migrations::version(&sql).await?
+                );
+            }
         }
     }
 
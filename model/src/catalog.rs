@@ -0,0 +1,593 @@
+//! Supplementary tables mirroring the resources, for queries the backend can't answer.
+//!
+//! The relational GraphQL backend looks up entities and follows their relations, but it can't
+//! count, group or join them with data of our own, like follows and feed events. So the fields
+//! which features like [facets](crate::facets), [statistics](crate::stats), feeds and
+//! [trending bills](crate::trending) filter and group by are mirrored in supplementary tables,
+//! `catalog_*`, along with the relationships between bills, their sponsors and their issues, and
+//! the hierarchy of issues. [`db::update`] and [`import`](crate::export::import) keep them up to
+//! date as resources are inserted and changed. The filters in this module select IDs from these
+//! tables, and the entities themselves are loaded through the usual
+//! [`Connection`](db::Connection).
+
+use crate::{
+    db::{self, SqlClient},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{
+    connection::{Connection, ConnectionNameType, CursorType, Edge, EdgeNameType, EmptyFields},
+    ComplexObject, Context, InputObject, Object, OutputType, SimpleObject,
+};
+use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
+use relational_graphql::graphql::type_system::Id;
+use std::future::Future;
+use tokio_postgres::types::ToSql;
+
+/// The maximum number of entities returned in a single page.
+const MAX_PAGE_SIZE: usize = 100;
+
+/// Criteria for selecting bills.
+///
+/// Each criterion which is given narrows the selection to bills matching any of the given values.
+#[derive(Clone, Debug, Default, InputObject)]
+pub struct BillFilter {
+    /// Abbreviations of states in which the bills were introduced.
+    pub state: Option<Vec<String>>,
+    /// Abbreviations of parties of the bills' sponsors.
+    pub party: Option<Vec<String>>,
+    /// Names of issues the bills pertain to.
+    pub issue: Option<Vec<String>>,
+    /// Names of issues the bills pertain to, either directly or through one of their sub-issues.
+    pub within_issue: Option<Vec<String>>,
+    /// Statuses of the bills, like "Introduced" or "Passed".
+    pub status: Option<Vec<String>>,
+    /// Names of sessions in which the bills were introduced.
+    pub session: Option<Vec<String>>,
+    /// IDs of legislators sponsoring the bills.
+    pub sponsor: Option<Vec<Id>>,
+}
+
+impl BillFilter {
+    /// Build a condition on `catalog_bills b` selecting bills which match this filter.
+    pub(crate) fn sql(&self, params: &mut Params) -> String {
+        let mut conditions = vec![];
+        if let Some(states) = &self.state {
+            conditions.push(format!("b.state = ANY({})", params.bind(states.clone())));
+        }
+        if let Some(parties) = &self.party {
+            conditions.push(format!(
+                "EXISTS (
+                    SELECT 1 FROM catalog_sponsors s
+                        JOIN catalog_legislators l ON l.id = s.legislator
+                    WHERE s.bill = b.id AND l.party = ANY({})
+                )",
+                params.bind(parties.clone())
+            ));
+        }
+        if let Some(statuses) = &self.status {
+            conditions.push(format!("b.status = ANY({})", params.bind(statuses.clone())));
+        }
+        if let Some(sessions) = &self.session {
+            conditions.push(format!(
+                "b.session = ANY({})",
+                params.bind(sessions.clone())
+            ));
+        }
+        if let Some(issues) = &self.issue {
+            conditions.push(format!(
+                "EXISTS (
+                    SELECT 1 FROM catalog_bill_issues bi JOIN catalog_issues i ON i.id = bi.issue
+                    WHERE bi.bill = b.id AND i.name = ANY({})
+                )",
+                params.bind(issues.clone())
+            ));
+        }
+        if let Some(issues) = &self.within_issue {
+            conditions.push(format!(
+                "EXISTS (
+                    SELECT 1 FROM catalog_bill_issues bi
+                        JOIN catalog_issue_ancestors a ON a.issue = bi.issue
+                        JOIN catalog_issues i ON i.id = a.ancestor
+                    WHERE bi.bill = b.id AND i.name = ANY({})
+                )",
+                params.bind(issues.clone())
+            ));
+        }
+        if let Some(sponsors) = &self.sponsor {
+            conditions.push(format!(
+                "EXISTS (
+                    SELECT 1 FROM catalog_sponsors s WHERE s.bill = b.id AND s.legislator = ANY({})
+                )",
+                params.bind(sponsors.clone())
+            ));
+        }
+        conjunction(conditions)
+    }
+}
+
+/// Criteria for selecting legislators.
+///
+/// Each criterion which is given narrows the selection to legislators matching any of the given
+/// values.
+#[derive(Clone, Debug, Default, InputObject)]
+pub struct LegislatorFilter {
+    /// Abbreviations of states which the legislators represent.
+    pub state: Option<Vec<String>>,
+    /// Abbreviations of parties the legislators belong to.
+    pub party: Option<Vec<String>>,
+    /// Names of chambers the legislators serve in.
+    pub chamber: Option<Vec<String>>,
+    /// IDs of bills the legislators have sponsored.
+    pub sponsoring: Option<Vec<Id>>,
+}
+
+impl LegislatorFilter {
+    /// Build a condition on `catalog_legislators l` selecting legislators which match this filter.
+    pub(crate) fn sql(&self, params: &mut Params) -> String {
+        let mut conditions = vec![];
+        if let Some(states) = &self.state {
+            conditions.push(format!("l.state = ANY({})", params.bind(states.clone())));
+        }
+        if let Some(parties) = &self.party {
+            conditions.push(format!("l.party = ANY({})", params.bind(parties.clone())));
+        }
+        if let Some(chambers) = &self.chamber {
+            conditions.push(format!(
+                "l.chamber = ANY({})",
+                params.bind(chambers.clone())
+            ));
+        }
+        if let Some(bills) = &self.sponsoring {
+            conditions.push(format!(
+                "EXISTS (
+                    SELECT 1 FROM catalog_sponsors s WHERE s.legislator = l.id AND s.bill = ANY({})
+                )",
+                params.bind(bills.clone())
+            ));
+        }
+        conjunction(conditions)
+    }
+}
+
+/// Names the connection types for sorted listings, like `SortedBillConnection`.
+///
+/// This keeps them distinct from the connection types generated for resource queries.
+pub struct SortedConnectionName;
+
+impl ConnectionNameType for SortedConnectionName {
+    fn type_name<T: OutputType>() -> String {
+        format!("Sorted{}Connection", T::type_name())
+    }
+}
+
+/// Names the edge types for sorted listings, like `SortedBillEdge`.
+pub struct SortedEdgeName;
+
+impl EdgeNameType for SortedEdgeName {
+    fn type_name<T: OutputType>() -> String {
+        format!("Sorted{}Edge", T::type_name())
+    }
+}
+
+/// Information about a sorted listing as a whole.
+#[derive(Clone, Copy, Debug, SimpleObject)]
+pub struct ListingInfo {
+    /// The total number of entities in the listing, across all pages.
+    pub total_count: i64,
+}
+
+/// A page of a sorted listing.
+///
+/// Cursors are offsets into the listing.
+pub type SortedConnection<T> =
+    Connection<usize, T, ListingInfo, EmptyFields, SortedConnectionName, SortedEdgeName>;
+
+/// A bill which was ingested or changed recently.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct RecentBill {
+    /// Whether the bill is new, rather than changed, in the requested period.
+    pub is_new: bool,
+    #[graphql(skip)]
+    pub id: Id,
+}
+
+#[ComplexObject]
+impl RecentBill {
+    /// The bill itself.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        Ok(db::bill_by_id(ctx.data()?, self.id).await?)
+    }
+}
+
+/// Queries for listings of cataloged entities.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CatalogQuery;
+
+#[Object]
+impl CatalogQuery {
+    /// Bills which were ingested or changed since `since`, most recently changed first.
+    ///
+    /// If `state` is given, only bills introduced in one of the given states are listed.
+    async fn recent_bills(
+        &self,
+        ctx: &Context<'_>,
+        since: DateTime<Utc>,
+        state: Option<Vec<String>>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<SortedConnection<RecentBill>> {
+        let filter = BillFilter {
+            state,
+            ..Default::default()
+        };
+        let page = Page::new(first, after)?;
+        let mut params = Params::default();
+        let query = format!(
+            "SELECT b.id, row_number() OVER (ORDER BY b.updated_at DESC, b.id) AS position
+                FROM catalog_bills b
+                WHERE b.updated_at >= {} AND {}",
+            params.bind(since),
+            filter.sql(&mut params),
+        );
+        let sql = ctx.data()?;
+        Ok(page
+            .load(sql, query, params, |id| recent_bill(sql, id, since))
+            .await?)
+    }
+}
+
+/// Create the catalog tables.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS catalog_bills (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            title TEXT NOT NULL,
+            state TEXT NOT NULL,
+            status TEXT NOT NULL,
+            status_date DATE,
+            session TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS catalog_bills_state ON catalog_bills (state);
+        CREATE INDEX IF NOT EXISTS catalog_bills_status_date ON catalog_bills (status_date);
+        -- When each bill was first ingested, and when it last changed in Legiscan.
+        ALTER TABLE catalog_bills
+            ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+        CREATE INDEX IF NOT EXISTS catalog_bills_updated_at ON catalog_bills (updated_at);
+        CREATE TABLE IF NOT EXISTS catalog_legislators (
+            id INTEGER PRIMARY KEY,
+            first_name TEXT NOT NULL,
+            last_name TEXT NOT NULL,
+            state TEXT NOT NULL,
+            party TEXT NOT NULL,
+            chamber TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS catalog_legislators_state ON catalog_legislators (state);
+        ALTER TABLE catalog_legislators
+            ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT now();
+        CREATE TABLE IF NOT EXISTS catalog_issues (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL
+        );
+        -- Every issue is its own ancestor, so that filtering by ancestor includes the issue itself.
+        CREATE TABLE IF NOT EXISTS catalog_issue_ancestors (
+            issue INTEGER NOT NULL,
+            ancestor INTEGER NOT NULL,
+            PRIMARY KEY (issue, ancestor)
+        );
+        CREATE INDEX IF NOT EXISTS catalog_issue_ancestors_ancestor
+            ON catalog_issue_ancestors (ancestor);
+        CREATE TABLE IF NOT EXISTS catalog_sponsors (
+            bill INTEGER NOT NULL,
+            legislator INTEGER NOT NULL,
+            PRIMARY KEY (bill, legislator)
+        );
+        -- When the sponsorship was first seen, so that new cosponsors can count towards trending.
+        ALTER TABLE catalog_sponsors
+            ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT now();
+        CREATE INDEX IF NOT EXISTS catalog_sponsors_legislator ON catalog_sponsors (legislator);
+        CREATE TABLE IF NOT EXISTS catalog_bill_issues (
+            bill INTEGER NOT NULL,
+            issue INTEGER NOT NULL,
+            PRIMARY KEY (bill, issue)
+        );
+        CREATE INDEX IF NOT EXISTS catalog_bill_issues_issue ON catalog_bill_issues (issue);",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Add a bill to the catalog, or update it if it is already cataloged.
+pub async fn index_bill(sql: &SqlClient, bill: &schema::Bill) -> Result<(), Error> {
+    sql.execute(
+        "INSERT INTO catalog_bills (id, name, title, state, status, status_date, session)
+            VALUES ($1, $2, $3, $4, $5, NULLIF($6::TEXT, '')::DATE, $7)
+            ON CONFLICT (id) DO UPDATE SET
+                name = EXCLUDED.name,
+                title = EXCLUDED.title,
+                state = EXCLUDED.state,
+                status = EXCLUDED.status,
+                status_date = EXCLUDED.status_date,
+                session = EXCLUDED.session,
+                updated_at = now()",
+        &[
+            &bill.id,
+            &bill.name,
+            &bill.title,
+            &bill.state.abbreviation,
+            &bill.status,
+            &bill.status_date,
+            &bill.session,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Add a legislator to the catalog, or update them if they are already cataloged.
+pub async fn index_legislator(
+    sql: &SqlClient,
+    legislator: &schema::Legislator,
+) -> Result<(), Error> {
+    sql.execute(
+        "INSERT INTO catalog_legislators (id, first_name, last_name, state, party, chamber)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (id) DO UPDATE SET
+                first_name = EXCLUDED.first_name,
+                last_name = EXCLUDED.last_name,
+                state = EXCLUDED.state,
+                party = EXCLUDED.party,
+                chamber = EXCLUDED.chamber,
+                updated_at = now()",
+        &[
+            &legislator.id,
+            &legislator.first_name,
+            &legislator.last_name,
+            &legislator.district.state.abbreviation,
+            &legislator.party.abbreviation,
+            &legislator.chamber,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Add an issue to the catalog, or update it if it is already cataloged.
+pub async fn index_issue(sql: &SqlClient, id: Id, name: &str) -> Result<(), Error> {
+    sql.execute(
+        "INSERT INTO catalog_issues (id, name) VALUES ($1, $2)
+            ON CONFLICT (id) DO UPDATE SET name = EXCLUDED.name",
+        &[&id, &name],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Record the ancestors of issues, as `(issue, ancestor)` pairs.
+pub async fn index_issue_ancestors(sql: &SqlClient, ancestors: &[(Id, Id)]) -> Result<(), Error> {
+    let (issues, ancestors): (Vec<Id>, Vec<Id>) = ancestors.iter().copied().unzip();
+    sql.execute(
+        "INSERT INTO catalog_issue_ancestors (issue, ancestor)
+            SELECT * FROM UNNEST($1::INTEGER[], $2::INTEGER[])
+            ON CONFLICT DO NOTHING",
+        &[&issues, &ancestors],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Record the sponsors of bills, as `(bill, legislator)` pairs.
+///
+/// Returns the pairs which were not already recorded.
+pub async fn index_sponsors(
+    sql: &SqlClient,
+    sponsors: &[(Id, Id)],
+) -> Result<Vec<(Id, Id)>, Error> {
+    let (bills, legislators): (Vec<Id>, Vec<Id>) = sponsors.iter().copied().unzip();
+    sql.query(
+        "INSERT INTO catalog_sponsors (bill, legislator)
+            SELECT * FROM UNNEST($1::INTEGER[], $2::INTEGER[])
+            ON CONFLICT DO NOTHING
+            RETURNING bill, legislator",
+        &[&bills, &legislators],
+    )
+    .await?
+    .into_iter()
+    .map(|row| Ok((row.try_get("bill")?, row.try_get("legislator")?)))
+    .collect()
+}
+
+/// Record the issues of bills, as `(bill, issue)` pairs.
+///
+/// Returns the pairs which were not already recorded.
+pub async fn index_bill_issues(
+    sql: &SqlClient,
+    issues: &[(Id, Id)],
+) -> Result<Vec<(Id, Id)>, Error> {
+    let (bills, issues): (Vec<Id>, Vec<Id>) = issues.iter().copied().unzip();
+    sql.query(
+        "INSERT INTO catalog_bill_issues (bill, issue)
+            SELECT * FROM UNNEST($1::INTEGER[], $2::INTEGER[])
+            ON CONFLICT DO NOTHING
+            RETURNING bill, issue",
+        &[&bills, &issues],
+    )
+    .await?
+    .into_iter()
+    .map(|row| Ok((row.try_get("bill")?, row.try_get("issue")?)))
+    .collect()
+}
+
+/// Load a bill which was ingested or changed since `since`.
+async fn recent_bill(
+    sql: &SqlClient,
+    id: Id,
+    since: DateTime<Utc>,
+) -> Result<Option<RecentBill>, Error> {
+    sql.query_opt("SELECT created_at FROM catalog_bills WHERE id = $1", &[&id])
+        .await?
+        .map(|row| {
+            let created_at: DateTime<Utc> = row.try_get("created_at")?;
+            Ok(RecentBill {
+                is_new: created_at >= since,
+                id,
+            })
+        })
+        .transpose()
+}
+
+/// When a cataloged bill was first ingested, and when it last changed.
+pub(crate) async fn bill_timestamps(
+    sql: &SqlClient,
+    id: Id,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    timestamps(sql, "catalog_bills", id).await
+}
+
+/// When a cataloged legislator was first ingested, and when they last changed.
+pub(crate) async fn legislator_timestamps(
+    sql: &SqlClient,
+    id: Id,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    timestamps(sql, "catalog_legislators", id).await
+}
+
+/// When the entity `id` in the catalog table `table` was first ingested, and when it last changed.
+async fn timestamps(
+    sql: &SqlClient,
+    table: &str,
+    id: Id,
+) -> Result<(DateTime<Utc>, DateTime<Utc>), Error> {
+    let row = sql
+        .query_opt(
+            &format!("SELECT created_at, updated_at FROM {table} WHERE id = $1"),
+            &[&id],
+        )
+        .await?
+        .ok_or_else(|| Error::msg(format!("ICE: {table} has no entry for {id}")))?;
+    Ok((row.try_get("created_at")?, row.try_get("updated_at")?))
+}
+
+/// Parameters for a dynamically built SQL query.
+#[derive(Default)]
+pub(crate) struct Params(Vec<Box<dyn ToSql + Send + Sync>>);
+
+impl Params {
+    /// Add a parameter, returning the placeholder which refers to it.
+    pub(crate) fn bind(&mut self, param: impl ToSql + Send + Sync + 'static) -> String {
+        self.0.push(Box::new(param));
+        format!("${}", self.0.len())
+    }
+
+    pub(crate) fn as_refs(&self) -> Vec<&(dyn ToSql + Sync)> {
+        self.0
+            .iter()
+            .map(|param| param.as_ref() as &(dyn ToSql + Sync))
+            .collect()
+    }
+}
+
+/// Combine SQL conditions with `AND`.
+pub(crate) fn conjunction(conditions: Vec<String>) -> String {
+    if conditions.is_empty() {
+        "TRUE".into()
+    } else {
+        conditions.join(" AND ")
+    }
+}
+
+/// A requested page of a sorted listing.
+pub(crate) struct Page {
+    offset: usize,
+    limit: usize,
+}
+
+impl Page {
+    pub(crate) fn new(first: Option<i32>, after: Option<String>) -> async_graphql::Result<Self> {
+        let offset = match after {
+            Some(cursor) => usize::decode_cursor(&cursor)? + 1,
+            None => 0,
+        };
+        let limit = match first {
+            Some(first) if first < 0 => return Err("first must be non-negative".into()),
+            Some(first) => (first as usize).min(MAX_PAGE_SIZE),
+            None => MAX_PAGE_SIZE,
+        };
+        Ok(Self { offset, limit })
+    }
+
+    /// Load this page of the listing selected by `query`.
+    ///
+    /// `query` selects the `id` of each entity in the listing, and its `position` in the listing.
+    /// Each entity on the page is then loaded with `load`.
+    pub(crate) async fn load<T, F, Fut>(
+        &self,
+        sql: &SqlClient,
+        query: String,
+        mut params: Params,
+        load: F,
+    ) -> Result<SortedConnection<T>, Error>
+    where
+        T: OutputType,
+        F: Fn(Id) -> Fut,
+        Fut: Future<Output = Result<Option<T>, Error>>,
+    {
+        // Fetch one more row than requested, to find out if there is a next page. The total count
+        // is joined with the page, so that we get it in the same query, even if the page is empty.
+        let limit = params.bind((self.limit + 1) as i64);
+        let offset = params.bind(self.offset as i64);
+        let rows = sql
+            .query(
+                format!(
+                    "WITH listing AS ({query})
+                    SELECT totals.total, page.id
+                    FROM (SELECT count(*) AS total FROM listing) totals
+                    LEFT JOIN LATERAL (
+                        SELECT id, position FROM listing
+                        ORDER BY position
+                        LIMIT {limit} OFFSET {offset}
+                    ) page ON TRUE
+                    ORDER BY page.position"
+                )
+                .as_str(),
+                &params.as_refs(),
+            )
+            .await?;
+        let total_count = match rows.first() {
+            Some(row) => row.try_get("total")?,
+            None => 0,
+        };
+        let ids = rows
+            .into_iter()
+            .filter_map(|row| row.try_get::<_, Option<Id>>("id").transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+        let has_next = ids.len() > self.limit;
+
+        let nodes = try_join_all(ids.into_iter().take(self.limit).map(|id| {
+            let node = load(id);
+            async move {
+                node.await?
+                    .ok_or_else(|| Error::msg(format!("ICE: cataloged entity {id:?} is missing")))
+            }
+        }))
+        .await?;
+
+        let mut connection = SortedConnection::with_additional_fields(
+            self.offset > 0,
+            has_next,
+            ListingInfo { total_count },
+        );
+        connection.edges.extend(
+            nodes
+                .into_iter()
+                .enumerate()
+                .map(|(i, node)| Edge::new(self.offset + i, node)),
+        );
+        Ok(connection)
+    }
+}
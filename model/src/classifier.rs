@@ -0,0 +1,251 @@
+//! Automatic categorization of bills into issues.
+//!
+//! Many bills arrive from Legiscan without any subjects, so they would never show up under any
+//! [`Issue`](crate::schema::Issue). The [`Classifier`] in this module proposes issues for such bills
+//! based on the text of their titles and summaries. It runs entirely offline, using the keywords
+//! associated with each issue in the [`Taxonomy`], weighted by TF-IDF over a corpus of bills.
+//!
+//! Terms which appear in many bills (like "act" or "state") carry little information about what a
+//! particular bill is about, so each term in a bill is weighted by its frequency in that bill times
+//! its inverse document frequency across the corpus. An issue is scored by the ratio of the weight of
+//! that issue's keywords in the bill to the total weight of all the words in the bill.
+
+use crate::taxonomy::Taxonomy;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The longest keyword phrase, in words, which the classifier will match.
+const MAX_PHRASE_LEN: usize = 3;
+
+/// Words which are too common to be meaningful on their own.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "in", "into", "is", "it", "of",
+    "on", "or", "that", "the", "this", "to", "with",
+];
+
+/// An issue proposed for a bill by the [`Classifier`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Suggestion {
+    /// The name of the suggested issue.
+    pub issue: String,
+    /// How confident the classifier is in this suggestion, between 0 and 1.
+    pub score: f64,
+}
+
+/// A keyword-based TF-IDF classifier which suggests issues for bills.
+#[derive(Clone, Debug)]
+pub struct Classifier {
+    /// The normalized keyword terms associated with each issue.
+    keywords: BTreeMap<String, BTreeSet<String>>,
+    /// The number of documents in the corpus containing each term.
+    document_frequency: HashMap<String, usize>,
+    /// The total number of documents in the corpus.
+    documents: usize,
+    /// The minimum score for a suggestion.
+    threshold: f64,
+    /// The maximum number of suggestions for a single bill.
+    max_suggestions: usize,
+}
+
+impl Classifier {
+    /// Create a classifier for the issues in `taxonomy`.
+    ///
+    /// Each issue is recognized by its name as well as its keywords. The classifier starts with an
+    /// empty corpus, in which all terms are weighted equally; use [`train`](Self::train) to add
+    /// documents to the corpus.
+    pub fn new(taxonomy: &Taxonomy) -> Self {
+        let keywords = taxonomy
+            .keywords()
+            .into_iter()
+            .map(|(issue, keywords)| {
+                let terms = keywords
+                    .into_iter()
+                    .chain([issue])
+                    .filter_map(|keyword| {
+                        let words = words(keyword);
+                        if words.is_empty() || words.len() > MAX_PHRASE_LEN {
+                            None
+                        } else {
+                            Some(words.join(" "))
+                        }
+                    })
+                    .collect();
+                (issue.to_string(), terms)
+            })
+            .collect();
+        Self {
+            keywords,
+            document_frequency: Default::default(),
+            documents: 0,
+            threshold: 0.1,
+            max_suggestions: 3,
+        }
+    }
+
+    /// Set the minimum score for a suggestion.
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Set the maximum number of suggestions for a single bill.
+    pub fn with_max_suggestions(mut self, max_suggestions: usize) -> Self {
+        self.max_suggestions = max_suggestions;
+        self
+    }
+
+    /// Add a document to the corpus used to compute inverse document frequencies.
+    pub fn train(&mut self, text: &str) {
+        let terms = terms(text).into_keys().collect::<BTreeSet<_>>();
+        for term in terms {
+            *self.document_frequency.entry(term).or_default() += 1;
+        }
+        self.documents += 1;
+    }
+
+    /// Suggest issues for a document, most confident first.
+    pub fn classify(&self, text: &str) -> Vec<Suggestion> {
+        let weights = terms(text)
+            .into_iter()
+            .map(|(term, count)| {
+                let weight = count as f64 * self.idf(&term);
+                (term, weight)
+            })
+            .collect::<HashMap<_, _>>();
+        // Normalize by the weight of the individual words in the document. Phrases are counted
+        // towards the score of an issue, but not towards the total, so that a bill which matches a
+        // specific phrase scores higher than one which only matches its words.
+        let total = weights
+            .iter()
+            .filter(|(term, _)| !term.contains(' '))
+            .map(|(_, weight)| weight)
+            .sum::<f64>();
+        if total == 0.0 {
+            return vec![];
+        }
+
+        let mut suggestions = self
+            .keywords
+            .iter()
+            .filter_map(|(issue, keywords)| {
+                let score = keywords
+                    .iter()
+                    .filter_map(|keyword| weights.get(keyword))
+                    .sum::<f64>()
+                    / total;
+                // Phrases are counted towards the score, but not the total, so the ratio can exceed
+                // 1 in rare cases.
+                let score = score.min(1.0);
+                if score >= self.threshold {
+                    Some(Suggestion {
+                        issue: issue.clone(),
+                        score,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        suggestions.sort_by(|a, b| b.score.total_cmp(&a.score));
+        suggestions.truncate(self.max_suggestions);
+        suggestions
+    }
+
+    /// The inverse document frequency of a term.
+    ///
+    /// This is smoothed, so that terms which have never been seen get the highest weight, and terms
+    /// which appear in every document still get a small positive weight.
+    fn idf(&self, term: &str) -> f64 {
+        let df = self.document_frequency.get(term).copied().unwrap_or(0);
+        ((1 + self.documents) as f64 / (1 + df) as f64).ln() + 1.0
+    }
+}
+
+/// Count the terms in a document.
+///
+/// Terms are normalized words and phrases of up to [`MAX_PHRASE_LEN`] consecutive words.
+fn terms(text: &str) -> HashMap<String, usize> {
+    let words = words(text);
+    let mut terms = HashMap::new();
+    for len in 1..=MAX_PHRASE_LEN {
+        for phrase in words.windows(len) {
+            // Don't count phrases which start or end with a stop word, or stop words on their own.
+            if STOP_WORDS.contains(&phrase[0].as_str())
+                || STOP_WORDS.contains(&phrase[len - 1].as_str())
+            {
+                continue;
+            }
+            *terms.entry(phrase.join(" ")).or_default() += 1;
+        }
+    }
+    terms
+}
+
+/// Split text into normalized words.
+fn words(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| stem(&word.replace('\'', "").to_lowercase()))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// A crude stemmer which removes plural suffixes, so that "schools" matches "school".
+fn stem(word: &str) -> String {
+    if word.len() <= 3 || word.ends_with("ss") {
+        word.into()
+    } else if let Some(stem) = word.strip_suffix("ies") {
+        format!("{stem}y")
+    } else if ["xes", "ches", "shes", "sses"]
+        .iter()
+        .any(|suffix| word.ends_with(suffix))
+    {
+        word[..word.len() - 2].into()
+    } else if let Some(stem) = word.strip_suffix('s') {
+        stem.into()
+    } else {
+        word.into()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let taxonomy: Taxonomy = serde_json::from_value(serde_json::json!({
+            "issues": [
+                {
+                    "name": "Education",
+                    "keywords": ["school", "student"],
+                    "children": [
+                        { "name": "Higher Education", "keywords": ["college", "tuition"] }
+                    ]
+                },
+                { "name": "Taxation", "keywords": ["income tax"] }
+            ]
+        }))
+        .unwrap();
+
+        let mut classifier = Classifier::new(&taxonomy);
+        for text in [
+            "An act concerning public schools.",
+            "An act concerning the state income tax.",
+            "An act concerning college tuition for students.",
+        ] {
+            classifier.train(text);
+        }
+
+        let suggestions = classifier
+            .classify("An act concerning tuition assistance for college students.")
+            .into_iter()
+            .map(|suggestion| suggestion.issue)
+            .collect::<Vec<_>>();
+        assert_eq!(suggestions, ["Higher Education", "Education"]);
+
+        assert!(classifier
+            .classify("An act concerning the income taxes of farmers.")
+            .iter()
+            .any(|suggestion| suggestion.issue == "Taxation"));
+        assert_eq!(classifier.classify("An act concerning."), []);
+    }
+}
@@ -0,0 +1,618 @@
+//! Threaded discussion of bills.
+//!
+//! Each comment is on a bill, and may be a reply to another comment on the same bill. Comments are
+//! kept in supplementary tables, alongside the [accounts](crate::accounts) of their authors:
+//! `comments`, `comment_votes` for upvotes, `comment_reports` for reports of abuse and
+//! `comment_quotas` for rate limiting.
+//!
+//! Authors can edit and delete their comments. Deleting a comment erases its body and hides its
+//! author from readers, though the author is still recorded, and keeps its place in the thread, so
+//! that replies to it still make sense. Any user can report a comment, which puts it in the
+//! moderation queue for administrators, who can hide it or dismiss the reports.
+//!
+//! To limit abuse, comments have a maximum length, and each user can only post a limited number
+//! of comments per hour. The quota is counted in a row per user, which concurrent posts update
+//! one at a time, so that they can't all slip in under the limit together.
+
+use crate::{
+    accounts::{self, User, Viewer},
+    db::{self, SqlClient},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{
+    connection::{Connection, CursorType, Edge},
+    ComplexObject, Context, Object, SimpleObject,
+};
+use chrono::{DateTime, Utc};
+use relational_graphql::graphql::type_system::Id;
+
+/// The maximum number of comments returned in a single page.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// The maximum length of a comment, in characters.
+const MAX_BODY_LEN: usize = 5000;
+
+/// The maximum length of the reason for a report, in characters.
+const MAX_REASON_LEN: usize = 1000;
+
+/// The maximum number of comments a user can post in an hour.
+const MAX_COMMENTS_PER_HOUR: i32 = 20;
+
+/// The columns selected to build a [`Comment`] from a row of `comments c`.
+const COMMENT_COLUMNS: &str = "c.id, c.bill, c.parent, c.author, c.body, c.created_at,
+    c.edited_at, c.deleted, c.hidden,
+    (SELECT count(*) FROM comment_votes v WHERE v.comment = c.id) AS upvote_count";
+
+/// A comment on a bill.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Comment {
+    pub id: i32,
+    /// When the comment was posted.
+    pub created_at: DateTime<Utc>,
+    /// When the comment was last edited, if it has been.
+    pub edited_at: Option<DateTime<Utc>>,
+    /// Whether the comment was deleted by its author.
+    pub deleted: bool,
+    /// Whether the comment was hidden by a moderator.
+    pub hidden: bool,
+    /// The number of users who have upvoted the comment.
+    pub upvote_count: i64,
+    #[graphql(skip)]
+    pub bill: Id,
+    #[graphql(skip)]
+    pub parent: Option<i32>,
+    #[graphql(skip)]
+    pub author: Option<i32>,
+    #[graphql(skip)]
+    pub body: String,
+}
+
+#[ComplexObject]
+impl Comment {
+    /// The text of the comment.
+    ///
+    /// This is `null` if the comment was deleted or hidden.
+    async fn body(&self) -> Option<&str> {
+        if self.deleted || self.hidden {
+            None
+        } else {
+            Some(&self.body)
+        }
+    }
+
+    /// The user who posted the comment.
+    ///
+    /// This is `null` if the comment was deleted.
+    async fn author(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        match self.author {
+            Some(author) if !self.deleted => Ok(accounts::user_by_id(ctx.data()?, author).await?),
+            _ => Ok(None),
+        }
+    }
+
+    /// The bill this comment is on.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        Ok(db::bill_by_id(ctx.data()?, self.bill).await?)
+    }
+
+    /// The comment this is a reply to, if any.
+    async fn parent(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Comment>> {
+        match self.parent {
+            Some(parent) => Ok(comment(ctx.data()?, parent).await?),
+            None => Ok(None),
+        }
+    }
+
+    /// Replies to this comment, oldest first.
+    async fn replies(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<usize, Comment>> {
+        let (offset, limit) = page(first, after)?;
+        Ok(comments(ctx.data()?, self.bill, Some(self.id), offset, limit).await?)
+    }
+
+    /// Whether the signed-in user has upvoted this comment.
+    ///
+    /// This is always `false` if the request is not authenticated.
+    async fn upvoted(&self, ctx: &Context<'_>) -> async_graphql::Result<bool> {
+        match Viewer::of(ctx).user {
+            Some(user) => Ok(has_upvoted(ctx.data()?, user, self.id).await?),
+            None => Ok(false),
+        }
+    }
+}
+
+/// A reported comment awaiting moderation.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct ReportedComment {
+    /// The reported comment.
+    pub comment: Comment,
+    /// The number of users who have reported the comment.
+    pub report_count: i64,
+    /// The reasons given in the reports, oldest first.
+    pub reasons: Vec<String>,
+}
+
+/// Queries for comments.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CommentQuery;
+
+#[Object]
+impl CommentQuery {
+    /// A comment by ID.
+    async fn comment(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Option<Comment>> {
+        Ok(comment(ctx.data()?, id).await?)
+    }
+
+    /// The moderation queue: comments with reports which have not been dealt with, most reported
+    /// first.
+    ///
+    /// Only administrators can see the moderation queue.
+    async fn reported_comments(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<usize, ReportedComment>> {
+        let sql = ctx.data()?;
+        Viewer::of(ctx).require_admin(sql).await?;
+        let (offset, limit) = page(first, after)?;
+        Ok(reported_comments(sql, offset, limit).await?)
+    }
+}
+
+/// Mutations for posting and moderating comments.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CommentMutation;
+
+#[Object]
+impl CommentMutation {
+    /// Post a comment on a bill as the signed-in user.
+    ///
+    /// If `parent` is given, the comment is a reply to that comment, which must be on the same
+    /// bill.
+    async fn post_comment(
+        &self,
+        ctx: &Context<'_>,
+        bill: Id,
+        parent: Option<i32>,
+        body: String,
+    ) -> async_graphql::Result<Comment> {
+        let user = Viewer::of(ctx).require()?;
+        if db::bill_by_id(ctx.data()?, bill).await?.is_none() {
+            return Err(format!("no such bill {bill:?}").into());
+        }
+        Ok(post_comment(ctx.data()?, user, bill, parent, &body).await?)
+    }
+
+    /// Edit one of the signed-in user's comments.
+    async fn edit_comment(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        body: String,
+    ) -> async_graphql::Result<Comment> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(edit_comment(ctx.data()?, user, id, &body).await?)
+    }
+
+    /// Delete one of the signed-in user's comments.
+    ///
+    /// Replies to the comment are kept.
+    async fn delete_comment(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Comment> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(delete_comment(ctx.data()?, user, id).await?)
+    }
+
+    /// Upvote a comment as the signed-in user, or withdraw an upvote if `upvote` is `false`.
+    async fn upvote_comment(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        #[graphql(default = true)] upvote: bool,
+    ) -> async_graphql::Result<Comment> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(upvote_comment(ctx.data()?, user, id, upvote).await?)
+    }
+
+    /// Report a comment to the moderators as the signed-in user.
+    ///
+    /// Returns whether the user had not already reported the comment.
+    async fn report_comment(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        reason: String,
+    ) -> async_graphql::Result<bool> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(report_comment(ctx.data()?, user, id, &reason).await?)
+    }
+
+    /// Hide or unhide a comment, resolving any reports of it.
+    ///
+    /// Only administrators can hide comments.
+    async fn hide_comment(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        #[graphql(default = true)] hidden: bool,
+    ) -> async_graphql::Result<Comment> {
+        let sql = ctx.data()?;
+        Viewer::of(ctx).require_admin(sql).await?;
+        Ok(moderate(sql, id, Some(hidden)).await?)
+    }
+
+    /// Resolve the reports of a comment without hiding it.
+    ///
+    /// Only administrators can dismiss reports.
+    async fn dismiss_reports(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<Comment> {
+        let sql = ctx.data()?;
+        Viewer::of(ctx).require_admin(sql).await?;
+        Ok(moderate(sql, id, None).await?)
+    }
+}
+
+/// Create the tables for comments.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS comments (
+            id SERIAL PRIMARY KEY,
+            bill INTEGER NOT NULL,
+            parent INTEGER REFERENCES comments (id),
+            author INTEGER REFERENCES users (id) ON DELETE SET NULL,
+            body TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            edited_at TIMESTAMPTZ,
+            deleted BOOLEAN NOT NULL DEFAULT FALSE,
+            hidden BOOLEAN NOT NULL DEFAULT FALSE
+        );
+        CREATE INDEX IF NOT EXISTS comments_bill_parent ON comments (bill, parent, created_at);
+        CREATE INDEX IF NOT EXISTS comments_author ON comments (author, created_at);
+        CREATE TABLE IF NOT EXISTS comment_votes (
+            comment INTEGER NOT NULL REFERENCES comments (id) ON DELETE CASCADE,
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            PRIMARY KEY (comment, user_id)
+        );
+        CREATE TABLE IF NOT EXISTS comment_reports (
+            comment INTEGER NOT NULL REFERENCES comments (id) ON DELETE CASCADE,
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            reason TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            resolved BOOLEAN NOT NULL DEFAULT FALSE,
+            PRIMARY KEY (comment, user_id)
+        );
+        CREATE INDEX IF NOT EXISTS comment_reports_unresolved ON comment_reports (comment)
+            WHERE NOT resolved;
+        -- Comments posted by each user since the start of their current hour-long window.
+        CREATE TABLE IF NOT EXISTS comment_quotas (
+            user_id INTEGER PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+            count INTEGER NOT NULL,
+            window_start TIMESTAMPTZ NOT NULL DEFAULT now()
+        );",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Post a comment on `bill` as `user`.
+pub async fn post_comment(
+    sql: &SqlClient,
+    user: i32,
+    bill: Id,
+    parent: Option<i32>,
+    body: &str,
+) -> Result<Comment, Error> {
+    let body = validate_body(body)?;
+    if let Some(parent) = parent {
+        match comment(sql, parent).await? {
+            Some(parent) if parent.bill == bill => {}
+            Some(_) => {
+                return Err(Error::msg(
+                    "a reply must be on the same bill as the comment it replies to",
+                ))
+            }
+            None => return Err(Error::msg(format!("no such comment {parent}"))),
+        }
+    }
+
+    // Take a comment from the user's quota and post it in one statement, so that the comment only
+    // counts towards the quota if it is posted. Counting the user's recent comments instead would
+    // let concurrent posts all see the same count and all get in under the limit; the row lock
+    // taken by the upsert serializes them.
+    let row = sql
+        .query_one(
+            "WITH quota AS (
+                INSERT INTO comment_quotas AS q (user_id, count) VALUES ($1, 1)
+                    ON CONFLICT (user_id) DO UPDATE SET
+                        count = CASE
+                            WHEN q.window_start < now() - interval '1 hour' THEN 1
+                            ELSE q.count + 1
+                        END,
+                        window_start = CASE
+                            WHEN q.window_start < now() - interval '1 hour' THEN now()
+                            ELSE q.window_start
+                        END
+                    RETURNING count
+            ), posted AS (
+                INSERT INTO comments (bill, parent, author, body)
+                    SELECT $2, $3, $1, $4 FROM quota WHERE quota.count <= $5
+                    RETURNING id
+            )
+            SELECT posted.id FROM quota LEFT JOIN posted ON TRUE",
+            &[&user, &bill, &parent, &body, &MAX_COMMENTS_PER_HOUR],
+        )
+        .await?;
+    let Some(id) = row.try_get::<_, Option<i32>>("id")? else {
+        return Err(Error::msg(format!(
+            "you can post at most {MAX_COMMENTS_PER_HOUR} comments per hour"
+        )));
+    };
+    load_comment(sql, id).await
+}
+
+/// Edit `user`'s comment `id`.
+pub async fn edit_comment(
+    sql: &SqlClient,
+    user: i32,
+    id: i32,
+    body: &str,
+) -> Result<Comment, Error> {
+    let body = validate_body(body)?;
+    let updated = sql
+        .execute(
+            "UPDATE comments SET body = $3, edited_at = now()
+                WHERE id = $1 AND author = $2 AND NOT deleted",
+            &[&id, &user, &body],
+        )
+        .await?;
+    if updated == 0 {
+        return Err(Error::msg(format!("you have no comment {id}")));
+    }
+    load_comment(sql, id).await
+}
+
+/// Delete `user`'s comment `id`.
+pub async fn delete_comment(sql: &SqlClient, user: i32, id: i32) -> Result<Comment, Error> {
+    // Clear the body, so that the deleted text isn't kept around.
+    let updated = sql
+        .execute(
+            "UPDATE comments SET body = '', deleted = TRUE
+                WHERE id = $1 AND author = $2 AND NOT deleted",
+            &[&id, &user],
+        )
+        .await?;
+    if updated == 0 {
+        return Err(Error::msg(format!("you have no comment {id}")));
+    }
+    load_comment(sql, id).await
+}
+
+/// Add or withdraw `user`'s upvote of comment `id`.
+pub async fn upvote_comment(
+    sql: &SqlClient,
+    user: i32,
+    id: i32,
+    upvote: bool,
+) -> Result<Comment, Error> {
+    let comment = load_comment(sql, id).await?;
+    if upvote {
+        if comment.author == Some(user) {
+            return Err(Error::msg("you can't upvote your own comment"));
+        }
+        sql.execute(
+            "INSERT INTO comment_votes (comment, user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            &[&id, &user],
+        )
+        .await?;
+    } else {
+        sql.execute(
+            "DELETE FROM comment_votes WHERE comment = $1 AND user_id = $2",
+            &[&id, &user],
+        )
+        .await?;
+    }
+    load_comment(sql, id).await
+}
+
+/// Whether `user` has upvoted comment `id`.
+pub async fn has_upvoted(sql: &SqlClient, user: i32, id: i32) -> Result<bool, Error> {
+    let row = sql
+        .query_opt(
+            "SELECT 1 FROM comment_votes WHERE comment = $1 AND user_id = $2",
+            &[&id, &user],
+        )
+        .await?;
+    Ok(row.is_some())
+}
+
+/// Report comment `id` as `user`.
+///
+/// Returns whether the user had not already reported the comment.
+pub async fn report_comment(
+    sql: &SqlClient,
+    user: i32,
+    id: i32,
+    reason: &str,
+) -> Result<bool, Error> {
+    let reason = reason.trim();
+    if reason.is_empty() || reason.chars().count() > MAX_REASON_LEN {
+        return Err(Error::msg(format!(
+            "reason must be between 1 and {MAX_REASON_LEN} characters"
+        )));
+    }
+    load_comment(sql, id).await?;
+    let inserted = sql
+        .execute(
+            "INSERT INTO comment_reports (comment, user_id, reason) VALUES ($1, $2, $3)
+                ON CONFLICT DO NOTHING",
+            &[&id, &user, &reason],
+        )
+        .await?;
+    Ok(inserted > 0)
+}
+
+/// Resolve the reports of comment `id`, and hide or unhide it if `hidden` is given.
+pub async fn moderate(sql: &SqlClient, id: i32, hidden: Option<bool>) -> Result<Comment, Error> {
+    load_comment(sql, id).await?;
+    if let Some(hidden) = hidden {
+        sql.execute(
+            "UPDATE comments SET hidden = $2 WHERE id = $1",
+            &[&id, &hidden],
+        )
+        .await?;
+    }
+    sql.execute(
+        "UPDATE comment_reports SET resolved = TRUE WHERE comment = $1",
+        &[&id],
+    )
+    .await?;
+    load_comment(sql, id).await
+}
+
+/// Load a comment by ID.
+pub async fn comment(sql: &SqlClient, id: i32) -> Result<Option<Comment>, Error> {
+    sql.query_opt(
+        format!("SELECT {COMMENT_COLUMNS} FROM comments c WHERE c.id = $1").as_str(),
+        &[&id],
+    )
+    .await?
+    .map(|row| comment_from_row(&row))
+    .transpose()
+}
+
+/// Load a page of the comments on `bill` which reply to `parent`, oldest first.
+///
+/// If `parent` is `None`, the top-level comments are loaded.
+pub async fn comments(
+    sql: &SqlClient,
+    bill: Id,
+    parent: Option<i32>,
+    offset: usize,
+    limit: i64,
+) -> Result<Connection<usize, Comment>, Error> {
+    // Fetch one more comment than requested, to find out if there is a next page.
+    let rows = sql
+        .query(
+            format!(
+                "SELECT {COMMENT_COLUMNS} FROM comments c
+                    WHERE c.bill = $1 AND c.parent IS NOT DISTINCT FROM $2
+                    ORDER BY c.created_at, c.id
+                    LIMIT $3 OFFSET $4"
+            )
+            .as_str(),
+            &[&bill, &parent, &(limit + 1), &(offset as i64)],
+        )
+        .await?;
+    let has_next = rows.len() as i64 > limit;
+    let mut connection = Connection::new(offset > 0, has_next);
+    for (i, row) in rows.iter().take(limit as usize).enumerate() {
+        connection
+            .edges
+            .push(Edge::new(offset + i, comment_from_row(row)?));
+    }
+    Ok(connection)
+}
+
+/// Load a page of the moderation queue, most reported first.
+pub async fn reported_comments(
+    sql: &SqlClient,
+    offset: usize,
+    limit: i64,
+) -> Result<Connection<usize, ReportedComment>, Error> {
+    // Fetch one more comment than requested, to find out if there is a next page.
+    let rows = sql
+        .query(
+            format!(
+                "SELECT {COMMENT_COLUMNS}, r.report_count, r.reasons
+                FROM comments c
+                JOIN (
+                    SELECT comment,
+                        count(*) AS report_count,
+                        array_agg(reason ORDER BY created_at) AS reasons
+                    FROM comment_reports
+                    WHERE NOT resolved
+                    GROUP BY comment
+                ) r ON r.comment = c.id
+                ORDER BY r.report_count DESC, c.id
+                LIMIT $1 OFFSET $2"
+            )
+            .as_str(),
+            &[&(limit + 1), &(offset as i64)],
+        )
+        .await?;
+    let has_next = rows.len() as i64 > limit;
+    let mut connection = Connection::new(offset > 0, has_next);
+    for (i, row) in rows.iter().take(limit as usize).enumerate() {
+        let reported = ReportedComment {
+            comment: comment_from_row(row)?,
+            report_count: row.try_get("report_count")?,
+            reasons: row.try_get("reasons")?,
+        };
+        connection.edges.push(Edge::new(offset + i, reported));
+    }
+    Ok(connection)
+}
+
+/// Load a comment which is expected to exist.
+async fn load_comment(sql: &SqlClient, id: i32) -> Result<Comment, Error> {
+    comment(sql, id)
+        .await?
+        .ok_or_else(|| Error::msg(format!("no such comment {id}")))
+}
+
+fn comment_from_row(row: &tokio_postgres::Row) -> Result<Comment, Error> {
+    Ok(Comment {
+        id: row.try_get("id")?,
+        bill: row.try_get("bill")?,
+        parent: row.try_get("parent")?,
+        author: row.try_get("author")?,
+        body: row.try_get("body")?,
+        created_at: row.try_get("created_at")?,
+        edited_at: row.try_get("edited_at")?,
+        deleted: row.try_get("deleted")?,
+        hidden: row.try_get("hidden")?,
+        upvote_count: row.try_get("upvote_count")?,
+    })
+}
+
+/// Trim the body of a comment and check its length.
+fn validate_body(body: &str) -> Result<&str, Error> {
+    let body = body.trim();
+    if body.is_empty() || body.chars().count() > MAX_BODY_LEN {
+        return Err(Error::msg(format!(
+            "comment must be between 1 and {MAX_BODY_LEN} characters"
+        )));
+    }
+    Ok(body)
+}
+
+/// Interpret the arguments of a paginated query as an offset and a limit.
+pub(crate) fn page(
+    first: Option<i32>,
+    after: Option<String>,
+) -> async_graphql::Result<(usize, i64)> {
+    let offset = match after {
+        Some(cursor) => usize::decode_cursor(&cursor)? + 1,
+        None => 0,
+    };
+    let limit = first.map(i64::from).unwrap_or(MAX_PAGE_SIZE);
+    Ok((offset, limit.clamp(0, MAX_PAGE_SIZE)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_validate_body() {
+        assert_eq!(validate_body("  Great bill!\n").unwrap(), "Great bill!");
+        assert!(validate_body(" \n ").is_err());
+        assert!(validate_body(&"a".repeat(MAX_BODY_LEN)).is_ok());
+        assert!(validate_body(&"a".repeat(MAX_BODY_LEN + 1)).is_err());
+    }
+}
@@ -1,12 +1,22 @@
 //! The Postgres database used by the bill tracker application.
 
 use super::{
-    legiscan::{Bill, Dataset, DatasetMetadata, Legiscan, Party, Person, State},
-    schema,
+    accounts, actions, catalog,
+    classifier::Classifier,
+    comments, feed, follows, hearings, history,
+    legiscan::{self, Bill, Dataset, DatasetMetadata, Legiscan, Person, State},
+    live, migrations, notifications, schema, search, stances,
+    taxonomy::Taxonomy,
+    trending, votes, webhooks,
 };
 use anyhow::Error;
+use async_std::task::spawn;
 use clap::Args;
-use futures::future::{try_join, try_join_all};
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver},
+    future::{try_join, try_join_all},
+    stream::{self, StreamExt},
+};
 use relational_graphql::{
     graphql::{
         backend::{DataSource, PageRequest},
@@ -16,15 +26,19 @@ use relational_graphql::{
     },
     sql::{db::postgres, PostgresDataSource},
 };
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::path::Path;
 use strum::IntoEnumIterator;
 use surf::Url;
+use tokio_postgres::{types::ToSql, AsyncMessage, Notification};
 
 /// Database connection options.
 #[derive(Clone, Debug, Args)]
 pub struct Options {
     /// URL for connecting to the Postgres database.
+    ///
+    /// The path of the URL, if there is one, names the database to use, as in
+    /// `http://localhost:5432/bill_tracker`. Otherwise, the default database is used.
     #[clap(
         long,
         env = "BILL_TRACKER_DB_URL",
@@ -44,6 +58,59 @@ pub struct Options {
 impl Options {
     /// Connect to the database.
     pub async fn connect(&self) -> Result<Connection, Error> {
+        Ok(postgres::Connection::new(self.config()?).await?.into())
+    }
+
+    /// Connect to the database, creating and looking up resource tables in the Postgres schema
+    /// `schema` instead of the default one.
+    pub(crate) async fn connect_in_schema(&self, schema: &str) -> Result<Connection, Error> {
+        let mut config = self.config()?;
+        config.options(&format!("-c search_path={schema}"));
+        Ok(postgres::Connection::new(config).await?.into())
+    }
+
+    /// Connect to the database with a raw SQL client.
+    pub async fn connect_sql(&self) -> Result<SqlClient, Error> {
+        let (client, conn) = async_postgres::connect(self.config()?).await?;
+        spawn(async move {
+            if let Err(err) = conn.await {
+                tracing::error!("SQL connection failed: {err}");
+            }
+        });
+        Ok(client)
+    }
+
+    /// Connect to the database with a raw SQL client, and receive the notifications sent to it.
+    ///
+    /// The client only receives notifications on channels it has subscribed to with `LISTEN`. The
+    /// connection is closed when the client and the notification stream are both dropped.
+    pub async fn connect_sql_notifications(
+        &self,
+    ) -> Result<(SqlClient, UnboundedReceiver<Notification>), Error> {
+        let (client, mut conn) = async_postgres::connect(self.config()?).await?;
+        let (sender, receiver) = unbounded();
+        spawn(async move {
+            let mut messages = stream::poll_fn(move |cx| conn.poll_message(cx));
+            while let Some(message) = messages.next().await {
+                match message {
+                    Ok(AsyncMessage::Notification(notification)) => {
+                        if sender.unbounded_send(notification).is_err() {
+                            // Nobody is listening anymore.
+                            break;
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        tracing::error!("SQL connection failed: {err}");
+                        break;
+                    }
+                }
+            }
+        });
+        Ok((client, receiver))
+    }
+
+    fn config(&self) -> Result<postgres::Config, Error> {
         let mut config = postgres::Config::default();
         let host = self
             .db_url
@@ -57,7 +124,11 @@ impl Options {
         if let Some(port) = self.db_url.port() {
             config.port(port);
         }
-        Ok(postgres::Connection::new(config).await?.into())
+        let dbname = self.db_url.path().trim_start_matches('/');
+        if !dbname.is_empty() {
+            config.dbname(dbname);
+        }
+        Ok(config)
     }
 
     /// Connect to the test database with the conventional parameters (port 5433).
@@ -69,41 +140,163 @@ impl Options {
             db_password: "password".into(),
         }
     }
+
+    /// Create an empty database called `name` on the test server, replacing any existing database
+    /// of that name, and return options for connecting to it.
+    #[cfg(any(test, feature = "testing"))]
+    pub async fn test_scratch(name: &str) -> Result<Self, Error> {
+        let sql = Self::test().connect_sql().await?;
+        sql.batch_execute(&format!("DROP DATABASE IF EXISTS {name}"))
+            .await?;
+        sql.batch_execute(&format!("CREATE DATABASE {name}"))
+            .await?;
+        let mut opt = Self::test();
+        opt.db_url.set_path(name);
+        Ok(opt)
+    }
 }
 
 /// A connection to the database.
 pub type Connection = PostgresDataSource;
 
+/// A raw SQL client for the database.
+///
+/// Most data is accessed through a [`Connection`], in terms of the resources defined in [`schema`].
+/// This client is used for supplementary tables and queries which can't be expressed in those
+/// terms, like the full-text search index.
+pub type SqlClient = tokio_postgres::Client;
+
+/// Political parties which are populated when the database is first set up.
+///
+/// Each entry is a Legiscan party ID, an abbreviation, and a full name.
+pub(crate) const PARTIES: [(&str, &str, &str); 7] = [
+    ("0", "?", "Unknown"),
+    ("1", "D", "Democrat"),
+    ("2", "R", "Republican"),
+    ("3", "I", "Independent"),
+    ("4", "G", "Green"),
+    ("5", "L", "Libertarian"),
+    ("6", "n/a", "Nonpartisan"),
+];
+
 /// Perform one-time setup of the database.
 ///
 /// This will create the necessary tables and relations, and populate static data like state and
-/// party information.
-pub async fn setup(conn: &mut Connection) -> Result<(), Error> {
+/// party information and the issue hierarchy described by `taxonomy`. The new schema is recorded as
+/// being at the [latest version](migrations::latest), so it does not need to be migrated.
+pub async fn setup(
+    conn: &mut Connection,
+    sql: &SqlClient,
+    taxonomy: &Taxonomy,
+) -> Result<(), Error> {
     schema::Query::register(conn).await?;
+    setup_tables(sql).await?;
+    migrations::setup(sql).await?;
 
     // Populate states.
-    let states = State::iter().map(|state| schema::state::StateInput {
-        abbreviation: state.to_string(),
-        name: state.name().into(),
+    let states = State::iter().map(|state| {
+        let metadata = state.metadata();
+        schema::state::StateInput {
+            abbreviation: state.to_string(),
+            name: metadata.name.into(),
+            fips_code: metadata.fips_code.into(),
+            capital: metadata.capital.into(),
+            legislature: metadata.legislature.into(),
+            upper_chamber: metadata.upper_chamber.into(),
+            lower_chamber: metadata.lower_chamber.unwrap_or_default().into(),
+            session_calendar: metadata.session_calendar.to_string(),
+            website: metadata.website.into(),
+        }
     });
     conn.insert::<schema::State, _>(states).await?;
 
-    // Populate political parties.
-    let parties = Party::iter().map(|party| schema::party::PartyInput {
-        abbreviation: party.abbreviation().into(),
-        name: party.to_string(),
-    });
+    // Populate well-known political parties. Other parties will be created as needed when we
+    // encounter them in Legiscan data.
+    let parties =
+        PARTIES.iter().map(
+            |(legiscan_id, abbreviation, name)| schema::party::PartyInput {
+                legiscan_id: legiscan_id.to_string(),
+                abbreviation: abbreviation.to_string(),
+                name: name.to_string(),
+            },
+        );
     conn.insert::<schema::Party, _>(parties).await?;
 
+    // Populate the issue hierarchy.
+    let issues = taxonomy.issues();
+    conn.insert::<schema::Issue, _>(issues.iter().map(|name| schema::issue::IssueInput {
+        name: name.to_string(),
+    }))
+    .await?;
+    let issue_ids = find_issue_ids(conn, issues).await?;
+    try_join_all(
+        issue_ids
+            .iter()
+            .map(|(name, id)| index_issue(sql, *id, name)),
+    )
+    .await?;
+    conn.populate_relation::<schema::issue::fields::Children, _>(
+        taxonomy
+            .edges()
+            .into_iter()
+            .map(|(parent, child)| (issue_ids[parent], issue_ids[child]))
+            .collect::<Vec<_>>(),
+    )
+    .await?;
+    let ancestors = taxonomy
+        .ancestors()
+        .into_iter()
+        .flat_map(|(issue, ancestors)| {
+            let issue_ids = &issue_ids;
+            ancestors
+                .into_iter()
+                .map(move |ancestor| (issue_ids[issue], issue_ids[ancestor]))
+        })
+        .collect::<Vec<_>>();
+    catalog::index_issue_ancestors(sql, &ancestors).await?;
+    conn.populate_relation::<schema::issue::fields::Ancestors, _>(ancestors)
+        .await?;
+
+    // Populate the mapping from Legiscan subjects to issues.
+    conn.insert::<schema::Subject, _>(taxonomy.subjects().map(|(subject, issue)| {
+        schema::subject::SubjectInput {
+            name: subject.into(),
+            issue: issue_ids[issue],
+        }
+    }))
+    .await?;
+
+    Ok(())
+}
+
+/// Create the supplementary tables which are accessed through the raw [`SqlClient`].
+///
+/// This is idempotent, so it can also bring the tables of an older database up to date.
+pub(crate) async fn setup_tables(sql: &SqlClient) -> Result<(), Error> {
+    search::setup(sql).await?;
+    catalog::setup(sql).await?;
+    accounts::setup(sql).await?;
+    follows::setup(sql).await?;
+    history::setup(sql).await?;
+    stances::setup(sql).await?;
+    comments::setup(sql).await?;
+    actions::setup(sql).await?;
+    votes::setup(sql).await?;
+    hearings::setup(sql).await?;
+    feed::setup(sql).await?;
+    notifications::setup(sql).await?;
+    webhooks::setup(sql).await?;
+    trending::setup(sql).await?;
     Ok(())
 }
 
 /// Update information in the database based on the latest bulk download from Legiscan.
 ///
 /// If `out` is provided, the data pulled to legiscan will be saved to disk as well as persisted in
-/// the database.
+/// the database. Afterwards, trending scores are [refreshed](trending::refresh).
 pub async fn update<L: Legiscan, P: AsRef<Path>>(
     conn: &mut Connection,
+    sql: &SqlClient,
     legiscan: &L,
     datasets: Vec<L::DatasetMetadata>,
     out: Option<P>,
@@ -123,55 +316,77 @@ pub async fn update<L: Legiscan, P: AsRef<Path>>(
             tracing::info!("bill {} {} - {}", bill.state(), bill.name(), bill.title());
 
             // Check if this bill already exists and, if it does, whether it needs to be updated.
-            let actions = match find_bill(read_conn, bill.id()).await? {
+            let existing = find_bill(read_conn, bill.id()).await?;
+            if let Some(existing) = &existing {
+                if existing.legiscan_hash == bill.hash() {
+                    // This bill is unchanged, nothing to do.
+                    tracing::info!("bill {} is up-to-date", bill.id());
+                    return Ok(vec![]);
+                }
+            }
+
+            // The people that this bill depends on will all be inserted or updated from the people
+            // section of this dataset. The issues, on the other hand, need to be created now if
+            // they don't exist already. Legiscan gives us subjects, which we map to canonical
+            // issues. A subject we haven't seen before becomes a new issue of the same name, unless
+            // there is already an issue with that name.
+            let (issues, actions): (BTreeSet<String>, Vec<Vec<Action>>) =
+                try_join_all(bill.issues().into_iter().map(|subject| async move {
+                    if let Some(found) = find_subject(read_conn, subject.clone()).await? {
+                        return Ok((found.issue.name, vec![]));
+                    }
+                    let mut actions = vec![Action::InsertSubject(subject.clone())];
+                    if find_issue(read_conn, subject.clone()).await?.is_none() {
+                        actions.push(Action::InsertIssue(subject.clone()));
+                    }
+                    Ok::<_, Error>((subject, actions))
+                }))
+                .await?
+                .into_iter()
+                .unzip();
+            let mut actions = actions.into_iter().flatten().collect::<Vec<_>>();
+            let input = schema::bill::BillInput {
+                legiscan_id: bill.id(),
+                legiscan_hash: bill.hash(),
+                name: bill.name(),
+                title: bill.title(),
+                summary: bill.summary(),
+                status: bill.status().to_string(),
+                status_date: bill.status_date(),
+                session: bill.session(),
+                state: bill.state().id().into(),
+            };
+            match existing {
                 Some(existing) => {
-                    if existing.legiscan_hash == bill.hash() {
-                        // This bill is unchanged, nothing to do.
-                        tracing::info!("bill {} is up-to-date", bill.id());
-                        vec![]
-                    } else {
-                        // TODO implement updates for existing data.
-                        tracing::warn!(
-                            "not yet implemented: updates; bill {} will be out of date",
-                            bill.id()
-                        );
-                        vec![]
+                    // Record any change in status in users' feeds, then overwrite the stored bill,
+                    // including its hash, so that it is up-to-date on the next pull.
+                    let id = existing.id;
+                    if existing.status != input.status {
+                        actions.push(Action::ChangeStatus {
+                            bill: Box::new(existing),
+                            status: input.status.clone(),
+                            date: input.status_date.clone(),
+                        });
                     }
+                    actions.push(Action::UpdateBill(id, input));
                 }
-                None => {
-                    // The people that this bill depends on will all be inserted from the people
-                    // section of this dataset. The issues, on the other hand, need to be created
-                    // now if they don't exist already.
-                    let mut actions =
-                        try_join_all(bill.issues().into_iter().map(|issue| async move {
-                            if find_issue(read_conn, issue.clone()).await?.is_none() {
-                                Ok(Some(Action::InsertIssue(issue)))
-                            } else {
-                                Ok::<_, Error>(None)
-                            }
-                        }))
-                        .await?
-                        .into_iter()
-                        .flatten()
-                        .collect::<Vec<_>>();
-                    actions.extend([
-                        Action::InsertBill(schema::bill::BillInput {
-                            legiscan_id: bill.id(),
-                            legiscan_hash: bill.hash(),
-                            name: bill.name(),
-                            title: bill.title(),
-                            summary: bill.summary(),
-                            state: bill.state().id().into(),
-                        }),
-                        Action::LinkBill {
-                            bill: bill.id(),
-                            sponsors: bill.sponsors(),
-                            issues: bill.issues(),
-                        },
-                    ]);
-                    actions
-                }
-            };
+                None => actions.push(Action::InsertBill(input)),
+            }
+            // Sponsors and issues which are already related to the bill are skipped, so for an
+            // existing bill this only adds new cosponsors and issues.
+            actions.push(Action::LinkBill {
+                bill: bill.id(),
+                sponsors: bill.sponsors(),
+                issues: issues.into_iter().collect(),
+            });
+            // Actions, votes and hearings which have already been recorded are skipped, so this
+            // only records new ones.
+            actions.push(Action::RecordHistory {
+                bill: bill.id(),
+                actions: bill.history(),
+                votes: bill.votes(),
+                hearings: bill.calendar(),
+            });
 
             Ok::<_, Error>(actions)
         });
@@ -179,55 +394,81 @@ pub async fn update<L: Legiscan, P: AsRef<Path>>(
             tracing::info!(
                 "person {} ({} - {})",
                 person.name(),
-                person.party().abbreviation(),
+                person.party().abbreviation,
                 person.district()
             );
 
             // Check if this person already exists and, if it does, whether it needs to be updated.
-            let actions = match find_person(read_conn, person.id()).await? {
-                Some(existing) => {
-                    if existing.legiscan_hash == person.hash() {
-                        // This person is unchanged, nothing to do.
-                        tracing::info!("person {} is up-to-date", person.id());
-                        vec![]
-                    } else {
-                        // TODO implement updates for existing data.
-                        tracing::warn!(
-                            "not yet implemented: updates; person {} will be out of date",
-                            person.id()
-                        );
-                        vec![]
-                    }
+            let existing = find_person(read_conn, person.id()).await?;
+            if let Some(existing) = &existing {
+                if existing.legiscan_hash == person.hash() {
+                    // This person is unchanged, nothing to do.
+                    tracing::info!("person {} is up-to-date", person.id());
+                    return Ok(vec![]);
                 }
-                None => {
-                    let name = person.name();
-                    let state = person.state();
-
-                    // Look up the district; if it doesn't exist, we need to insert it.
-                    let district_name = person.district();
-                    let district = find_district(read_conn, state, district_name.clone()).await?;
-                    let build_person = move |district| schema::legislator::LegislatorInput {
-                        legiscan_id: person.id(),
-                        legiscan_hash: person.hash(),
-                        first_name: name.first,
-                        middle_name: name.middle,
-                        last_name: name.last,
-                        district,
-                        party: person.party().id().into(),
-                    };
-                    match district {
-                        Some(district) => vec![Action::InsertPerson(build_person(district.id))],
-                        None => vec![
-                            Action::InsertDistrict(InsertDistrict {
+            }
+            let existing = existing.map(|existing| existing.id);
+
+            let name = person.name();
+            let state = person.state();
+            let home_state = person.home_state();
+
+            // Look up the district and party; if either doesn't exist, we need to insert it, and
+            // then build the person once we know its ID.
+            let district_name = person.district();
+            let party = person.party();
+            let chamber = state.chamber_name(person.chamber());
+            let (district, existing_party) = try_join(
+                find_district(read_conn, state, district_name.clone()),
+                find_party(read_conn, party.id.clone()),
+            )
+            .await?;
+            let build_person = move |district, party| schema::legislator::LegislatorInput {
+                legiscan_id: person.id(),
+                legiscan_hash: person.hash(),
+                first_name: name.first,
+                middle_name: name.middle,
+                last_name: name.last,
+                district,
+                home_state: home_state.id().into(),
+                chamber: chamber.into(),
+                party,
+            };
+            let actions = match (district, existing_party) {
+                (Some(district), Some(party)) => {
+                    let person = build_person(district.id, party.id);
+                    vec![match existing {
+                        Some(id) => Action::UpdatePerson(id, person),
+                        None => Action::InsertPerson(person),
+                    }]
+                }
+                (district, existing_party) => {
+                    let mut actions = vec![];
+                    let district = match district {
+                        Some(district) => Dependency::Existing(district.id),
+                        None => {
+                            actions.push(Action::InsertDistrict(InsertDistrict {
                                 name: district_name.clone(),
                                 state,
-                            }),
-                            Action::BuildPerson {
-                                district: district_name,
-                                build: Box::new(build_person),
-                            },
-                        ],
-                    }
+                            }));
+                            Dependency::New(district_name)
+                        }
+                    };
+                    let party = match existing_party {
+                        Some(existing) => Dependency::Existing(existing.id),
+                        None => {
+                            let id = party.id.clone();
+                            actions.push(Action::InsertParty(party));
+                            Dependency::New(id)
+                        }
+                    };
+                    actions.push(Action::BuildPerson {
+                        existing,
+                        district,
+                        party,
+                        build: Box::new(build_person),
+                    });
+                    actions
                 }
             };
 
@@ -241,35 +482,62 @@ pub async fn update<L: Legiscan, P: AsRef<Path>>(
         // 1. Insert all districts, and create a map from district names to IDs. This will be
         //    necessary for building legislator objects to insert later.
         let mut insert_districts: HashSet<InsertDistrict> = Default::default();
-        // 2. Insert all people, lazily building them from the newly inserted district IDs if
-        //    necessary.
+        //    Similarly, insert all new parties and create a map from Legiscan party IDs to IDs.
+        let mut insert_parties: HashSet<legiscan::Party> = Default::default();
+        // 2. Insert or update all people, lazily building them from the newly inserted district and
+        //    party IDs if necessary.
         let mut insert_people: Vec<schema::legislator::LegislatorInput> = Default::default();
-        let mut build_people: Vec<(String, PersonBuilder)> = Default::default();
-        // 3. Insert all bills.
+        let mut update_people: Vec<(Id, schema::legislator::LegislatorInput)> = Default::default();
+        let mut build_people: Vec<(Option<Id>, Dependency, Dependency, PersonBuilder)> =
+            Default::default();
+        // 3. Insert or update all bills.
         let mut insert_bills: Vec<schema::bill::BillInput> = Default::default();
-        // 4. Insert all issues.
+        let mut update_bills: Vec<(Id, schema::bill::BillInput)> = Default::default();
+        // 4. Insert all issues, and then all subjects, each mapped to the issue of the same name.
         let mut insert_issues: HashSet<String> = Default::default();
+        let mut insert_subjects: HashSet<String> = Default::default();
         // 5. Add relations between bills and their sponsors.
         let mut bill_sponsors: Vec<(String, String)> = Default::default();
         // 6. Add relations between bills and their issues.
         let mut bill_issues: Vec<(String, String)> = Default::default();
+        // 7. Record changes to existing bills in feeds.
+        let mut status_changes: Vec<(schema::Bill, String, String)> = Default::default();
+        // 8. Record actions, votes and hearings on bills, and the new votes and hearings in feeds.
+        let mut bill_history: Vec<BillHistory> = Default::default();
         for action in actions {
             match action {
                 Action::InsertDistrict(district) => {
                     insert_districts.insert(district);
                 }
+                Action::InsertParty(party) => {
+                    insert_parties.insert(party);
+                }
                 Action::InsertPerson(person) => {
                     insert_people.push(person);
                 }
-                Action::BuildPerson { district, build } => {
-                    build_people.push((district, build));
+                Action::UpdatePerson(id, person) => {
+                    update_people.push((id, person));
+                }
+                Action::BuildPerson {
+                    existing,
+                    district,
+                    party,
+                    build,
+                } => {
+                    build_people.push((existing, district, party, build));
                 }
                 Action::InsertBill(bill) => {
                     insert_bills.push(bill);
                 }
+                Action::UpdateBill(id, bill) => {
+                    update_bills.push((id, bill));
+                }
                 Action::InsertIssue(name) => {
                     insert_issues.insert(name);
                 }
+                Action::InsertSubject(name) => {
+                    insert_subjects.insert(name);
+                }
                 Action::LinkBill {
                     bill,
                     sponsors,
@@ -282,6 +550,17 @@ pub async fn update<L: Legiscan, P: AsRef<Path>>(
                         bill_issues.push((bill.clone(), issue));
                     }
                 }
+                Action::ChangeStatus { bill, status, date } => {
+                    status_changes.push((*bill, status, date));
+                }
+                Action::RecordHistory {
+                    bill,
+                    actions,
+                    votes,
+                    hearings,
+                } => {
+                    bill_history.push((bill, actions, votes, hearings));
+                }
             }
         }
 
@@ -293,6 +572,16 @@ pub async fn update<L: Legiscan, P: AsRef<Path>>(
                 .map(schema::district::DistrictInput::from),
         )
         .await?;
+        conn.insert::<schema::Party, _>(insert_parties.iter().map(|party| {
+            schema::party::PartyInput {
+                legiscan_id: party.id.clone(),
+                abbreviation: party.abbreviation.clone(),
+                // Legiscan doesn't give us the full name of the party, so the best we can do is
+                // use the abbreviation.
+                name: party.abbreviation.clone(),
+            }
+        }))
+        .await?;
         let read_conn = &conn;
 
         // Get the district IDs we just inserted, indexing them by name.
@@ -309,34 +598,85 @@ pub async fn update<L: Legiscan, P: AsRef<Path>>(
         .into_iter()
         .collect::<HashMap<_, _>>();
 
-        // Build and insert people based on the district IDs.
-        conn.insert::<schema::Legislator, _>(
-            insert_people.into_iter().chain(
-                build_people
-                    .into_iter()
-                    .map(|(district, build)| {
-                        let district = district_ids.get(&district).ok_or_else(|| {
-                            Error::msg(format!(
-                                "ICE: expected to find district {district} after inserting it"
-                            ))
-                        })?;
-                        Ok(build(*district))
-                    })
-                    .collect::<Result<Vec<_>, Error>>()?,
-            ),
+        // Get the party IDs we just inserted, indexing them by Legiscan ID.
+        let party_ids = try_join_all(insert_parties.into_iter().map(|party| async move {
+            match find_party(read_conn, party.id.clone()).await? {
+                Some(found) => Ok((party.id, found.id)),
+                None => Err(Error::msg(format!(
+                    "ICE: expected to find party {} after inserting it",
+                    party.id
+                ))),
+            }
+        }))
+        .await?
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+
+        // Build and insert or update people based on the district and party IDs.
+        for (existing, district, party, build) in build_people {
+            let district = district.resolve(&district_ids, "district")?;
+            let party = party.resolve(&party_ids, "party")?;
+            match existing {
+                Some(id) => update_people.push((id, build(district, party))),
+                None => insert_people.push(build(district, party)),
+            }
+        }
+        let new_people = insert_people
+            .iter()
+            .map(|person| person.legiscan_id.clone())
+            .collect::<Vec<_>>();
+        conn.insert::<schema::Legislator, _>(insert_people).await?;
+        try_join_all(
+            update_people
+                .iter()
+                .map(|(id, person)| update_legislator(sql, *id, person)),
         )
         .await?;
 
-        // Insert issues.
+        // Insert issues. Issues created from unknown subjects are at the top of the hierarchy, so
+        // each one is its only ancestor.
         conn.insert::<schema::Issue, _>(
             insert_issues
+                .iter()
+                .map(|name| schema::issue::IssueInput { name: name.clone() }),
+        )
+        .await?;
+        let issue_ids = find_issue_ids(conn, insert_issues).await?;
+        try_join_all(
+            issue_ids
+                .iter()
+                .map(|(name, id)| index_issue(sql, *id, name)),
+        )
+        .await?;
+        let ancestors = issue_ids
+            .into_values()
+            .map(|id| (id, id))
+            .collect::<Vec<_>>();
+        catalog::index_issue_ancestors(sql, &ancestors).await?;
+        conn.populate_relation::<schema::issue::fields::Ancestors, _>(ancestors)
+            .await?;
+
+        // Insert subjects, mapping each one to the issue of the same name.
+        let subject_issues = find_issue_ids(conn, insert_subjects).await?;
+        conn.insert::<schema::Subject, _>(
+            subject_issues
                 .into_iter()
-                .map(|name| schema::issue::IssueInput { name }),
+                .map(|(name, issue)| schema::subject::SubjectInput { name, issue }),
         )
         .await?;
 
         // Insert bills.
+        let new_bills = insert_bills
+            .iter()
+            .map(|bill| bill.legiscan_id.clone())
+            .collect::<Vec<_>>();
         conn.insert::<schema::Bill, _>(insert_bills).await?;
+        try_join_all(
+            update_bills
+                .iter()
+                .map(|(id, bill)| update_bill(sql, *id, bill)),
+        )
+        .await?;
 
         // Finally, add relations between the newly inserted data (bills to sponsors and issues).
         let read_conn = &conn;
@@ -389,39 +729,460 @@ pub async fn update<L: Legiscan, P: AsRef<Path>>(
             ),
         )
         .await?;
+        // Changed bills are linked to all of their sponsors and issues again, so only populate the
+        // relations which the catalog hasn't seen before.
+        let (bill_sponsors, bill_issues) = try_join(
+            catalog::index_sponsors(sql, &bill_sponsors),
+            catalog::index_bill_issues(sql, &bill_issues),
+        )
+        .await?;
+        // New sponsors of existing bills are news in their own right, whereas the sponsors of new
+        // bills are part of the news of the bill being introduced.
+        let new_sponsors = bill_sponsors
+            .iter()
+            .filter(|(bill, _)| update_bills.iter().any(|(id, _)| id == bill))
+            .copied()
+            .collect::<Vec<_>>();
         conn.populate_relation::<schema::bill::fields::Sponsors, _>(bill_sponsors)
             .await?;
         conn.populate_relation::<schema::bill::fields::Issues, _>(bill_issues)
             .await?;
+
+        // Add the newly inserted bills and people to the search index and the catalog, and record
+        // the new bills in feeds. Update the index and the catalog for the changed ones.
+        let read_conn = &conn;
+        try_join(
+            try_join_all(new_bills.into_iter().map(|bill_id| async move {
+                match find_bill(read_conn, bill_id.clone()).await? {
+                    Some(bill) => {
+                        try_join(
+                            search::index_bill(sql, &bill),
+                            catalog::index_bill(sql, &bill),
+                        )
+                        .await?;
+                        feed::record_new_bill(sql, &bill).await
+                    }
+                    None => Err(Error::msg(format!(
+                        "ICE: expected to find bill {bill_id} after inserting it"
+                    ))),
+                }
+            })),
+            try_join_all(new_people.into_iter().map(|person_id| async move {
+                match find_person(read_conn, person_id.clone()).await? {
+                    Some(person) => {
+                        try_join(
+                            search::index_legislator(sql, &person),
+                            catalog::index_legislator(sql, &person),
+                        )
+                        .await?;
+                        Ok(())
+                    }
+                    None => Err(Error::msg(format!(
+                        "ICE: expected to find person {person_id} after inserting it"
+                    ))),
+                }
+            })),
+        )
+        .await?;
+
+        let updated_bills = update_bills
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        try_join(
+            try_join_all(updated_bills.iter().map(|id| async move {
+                match bill_by_id(read_conn, *id).await? {
+                    Some(bill) => {
+                        try_join(
+                            search::index_bill(sql, &bill),
+                            catalog::index_bill(sql, &bill),
+                        )
+                        .await?;
+                        Ok(())
+                    }
+                    None => Err(Error::msg(format!(
+                        "ICE: expected to find bill {id:?} after updating it"
+                    ))),
+                }
+            })),
+            try_join_all(update_people.iter().map(|(id, _)| async move {
+                match legislator_by_id(read_conn, *id).await? {
+                    Some(person) => {
+                        try_join(
+                            search::index_legislator(sql, &person),
+                            catalog::index_legislator(sql, &person),
+                        )
+                        .await?;
+                        Ok(())
+                    }
+                    None => Err(Error::msg(format!(
+                        "ICE: expected to find person {id:?} after updating it"
+                    ))),
+                }
+            })),
+        )
+        .await?;
+
+        // Record changes in the status of existing bills in feeds.
+        try_join_all(
+            status_changes
+                .iter()
+                .map(|(bill, status, date)| feed::record_status_change(sql, bill, status, date)),
+        )
+        .await?;
+
+        // Record new sponsors of existing bills in feeds. Then record the actions, votes and
+        // hearings on each bill, and the new votes and hearings in feeds.
+        try_join_all(new_sponsors.iter().map(|(bill, sponsor)| async move {
+            match try_join(
+                bill_by_id(read_conn, *bill),
+                legislator_by_id(read_conn, *sponsor),
+            )
+            .await?
+            {
+                (Some(bill), Some(sponsor)) => feed::record_new_sponsor(sql, &bill, &sponsor).await,
+                _ => Err(Error::msg(format!(
+                    "ICE: expected to find bill {bill:?} and sponsor {sponsor:?}"
+                ))),
+            }
+        }))
+        .await?;
+        try_join_all(bill_history.into_iter().map(
+            |(bill_id, history, roll_calls, calendar)| async move {
+                match find_bill(read_conn, bill_id.clone()).await? {
+                    Some(bill) => {
+                        actions::record(sql, &bill, &history).await?;
+                        for vote in votes::record(sql, &bill, &roll_calls).await? {
+                            feed::record_vote(sql, &bill, &vote).await?;
+                        }
+                        for hearing in hearings::record(sql, &bill, &calendar).await? {
+                            feed::record_hearing(sql, &bill, &hearing).await?;
+                        }
+                        Ok(())
+                    }
+                    None => Err(Error::msg(format!(
+                        "ICE: expected to find bill {bill_id} after inserting it"
+                    ))),
+                }
+            },
+        ))
+        .await?;
+        live::notify_bills_updated(sql, &updated_bills).await?;
+    }
+
+    // Rescore trending bills to take the new activity into account.
+    trending::refresh(sql).await?;
+
+    Ok(())
+}
+
+/// Suggest issues for every bill in the database, based on the bills' titles and summaries.
+///
+/// The suggestions are stored as [`IssueSuggestion`](schema::IssueSuggestion)s, which are distinct
+/// from the issues assigned to bills by Legiscan. A bill which has already been suggested an issue
+/// will not be suggested the same issue again, so it is safe to run this repeatedly.
+pub async fn retag(conn: &mut Connection, classifier: Classifier) -> Result<(), Error> {
+    // Load all bills. The bills also form the corpus used to weight terms in the classifier.
+    let bills = find_all::<schema::Bill>(conn).await?;
+    let mut classifier = classifier;
+    for bill in &bills {
+        classifier.train(&bill_text(bill));
+    }
+    tracing::info!("classifying {} bills", bills.len());
+
+    let read_conn = &conn;
+    let classifier = &classifier;
+    let suggestions = try_join_all(bills.iter().map(|bill| async move {
+        try_join_all(classifier.classify(&bill_text(bill)).into_iter().map(
+            |suggestion| async move {
+                let issue = match find_issue(read_conn, suggestion.issue.clone()).await? {
+                    Some(found) => found.id,
+                    None => {
+                        return Err(Error::msg(format!(
+                            "issue {} is not in the database; was it set up with a different \
+                                 taxonomy?",
+                            suggestion.issue
+                        )))
+                    }
+                };
+                if find_issue_suggestion(read_conn, bill.id, issue)
+                    .await?
+                    .is_some()
+                {
+                    // This bill has already been suggested this issue.
+                    return Ok(None);
+                }
+                tracing::info!(
+                    "suggesting issue {} for bill {} {} ({:.2})",
+                    suggestion.issue,
+                    bill.state.abbreviation,
+                    bill.name,
+                    suggestion.score
+                );
+                Ok(Some(schema::issue_suggestion::IssueSuggestionInput {
+                    bill: bill.id,
+                    issue,
+                    confidence: (suggestion.score * 100.0).round() as i32,
+                }))
+            },
+        ))
+        .await
+    }))
+    .await?;
+    conn.insert::<schema::IssueSuggestion, _>(suggestions.into_iter().flatten().flatten())
+        .await?;
+
+    Ok(())
+}
+
+/// Add an issue to the search index and the catalog.
+pub(crate) async fn index_issue(sql: &SqlClient, id: Id, name: &str) -> Result<(), Error> {
+    try_join(
+        search::index_issue(sql, id, name),
+        catalog::index_issue(sql, id, name),
+    )
+    .await?;
+    Ok(())
+}
+
+/// A table backing a resource, whose rows are updated directly.
+///
+/// The resource API can only insert, so changes to existing bills and legislators are written to
+/// the tables which relational-graphql creates for them. Only the columns listed here are written,
+/// and [`check_resource_tables`] makes sure they still exist, so that a change in the layout of the
+/// tables is caught before anything is written.
+struct ResourceTable {
+    table: &'static str,
+    columns: &'static [&'static str],
+}
+
+/// The resource tables which are updated directly.
+const RESOURCE_TABLES: &[ResourceTable] = &[BILLS, LEGISLATORS];
+
+/// The table backing the [`Bill`](schema::Bill) resource.
+const BILLS: ResourceTable = ResourceTable {
+    table: "bills",
+    columns: &[
+        "legiscan_hash",
+        "name",
+        "title",
+        "summary",
+        "status",
+        "status_date",
+        "session",
+    ],
+};
+
+/// The table backing the [`Legislator`](schema::Legislator) resource.
+const LEGISLATORS: ResourceTable = ResourceTable {
+    table: "legislators",
+    columns: &[
+        "legiscan_hash",
+        "first_name",
+        "middle_name",
+        "last_name",
+        "district",
+        "chamber",
+        "party",
+        "home_state",
+    ],
+};
+
+impl ResourceTable {
+    /// Overwrite the columns of the existing row `id` with `values`, in the order of `columns`.
+    ///
+    /// Relations are not affected.
+    async fn update(
+        &self,
+        sql: &SqlClient,
+        id: Id,
+        values: &[&(dyn ToSql + Sync)],
+    ) -> Result<(), Error> {
+        if values.len() != self.columns.len() {
+            return Err(Error::msg(format!(
+                "ICE: expected {} values to update {}, got {}",
+                self.columns.len(),
+                self.table,
+                values.len()
+            )));
+        }
+        let assignments = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| format!("{column} = ${}", i + 2))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let params = [&id as &(dyn ToSql + Sync)]
+            .into_iter()
+            .chain(values.iter().copied())
+            .collect::<Vec<_>>();
+        let updated = sql
+            .execute(
+                &format!("UPDATE {} SET {assignments} WHERE id = $1", self.table),
+                &params,
+            )
+            .await?;
+        if updated != 1 {
+            return Err(Error::msg(format!(
+                "ICE: expected to update {} {id}, but it does not exist",
+                self.table
+            )));
+        }
+        Ok(())
     }
+}
 
+/// Fail unless the resource tables which are updated directly have the columns that are written.
+pub async fn check_resource_tables(sql: &SqlClient) -> Result<(), Error> {
+    for table in RESOURCE_TABLES {
+        let rows = sql
+            .query(
+                "SELECT column_name FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = $1",
+                &[&table.table],
+            )
+            .await?;
+        let present = rows
+            .into_iter()
+            .map(|row| row.try_get("column_name"))
+            .collect::<Result<HashSet<String>, _>>()?;
+        let missing = table
+            .columns
+            .iter()
+            .filter(|column| !present.contains(**column))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::msg(format!(
+                "resource table {} is missing columns {missing:?}, which are updated directly; \
+                the layout of the resource tables has changed",
+                table.table
+            )));
+        }
+    }
     Ok(())
 }
 
+/// Overwrite the stored fields of the existing bill `id` with `bill`.
+async fn update_bill(sql: &SqlClient, id: Id, bill: &schema::bill::BillInput) -> Result<(), Error> {
+    BILLS
+        .update(
+            sql,
+            id,
+            &[
+                &bill.legiscan_hash,
+                &bill.name,
+                &bill.title,
+                &bill.summary,
+                &bill.status,
+                &bill.status_date,
+                &bill.session,
+            ],
+        )
+        .await
+}
+
+/// Overwrite the stored fields of the existing legislator `id` with `legislator`.
+async fn update_legislator(
+    sql: &SqlClient,
+    id: Id,
+    legislator: &schema::legislator::LegislatorInput,
+) -> Result<(), Error> {
+    LEGISLATORS
+        .update(
+            sql,
+            id,
+            &[
+                &legislator.legiscan_hash,
+                &legislator.first_name,
+                &legislator.middle_name,
+                &legislator.last_name,
+                &legislator.district,
+                &legislator.chamber,
+                &legislator.party,
+                &legislator.home_state,
+            ],
+        )
+        .await
+}
+
+/// The text of a bill to use for classification.
+fn bill_text(bill: &schema::Bill) -> String {
+    format!("{}\n{}", bill.title, bill.summary)
+}
+
+/// The Legiscan ID of a bill, and its actions, votes and hearings.
+type BillHistory = (
+    String,
+    Vec<legiscan::Action>,
+    Vec<legiscan::Vote>,
+    Vec<legiscan::Hearing>,
+);
+
 /// Actions to perform when updating the database.
 enum Action {
     InsertDistrict(InsertDistrict),
     InsertBill(schema::bill::BillInput),
     InsertIssue(String),
+    InsertSubject(String),
     LinkBill {
         bill: String,
         sponsors: Vec<String>,
         issues: Vec<String>,
     },
+    ChangeStatus {
+        bill: Box<schema::Bill>,
+        status: String,
+        date: String,
+    },
+    UpdateBill(Id, schema::bill::BillInput),
+    RecordHistory {
+        bill: String,
+        actions: Vec<legiscan::Action>,
+        votes: Vec<legiscan::Vote>,
+        hearings: Vec<legiscan::Hearing>,
+    },
+    InsertParty(legiscan::Party),
     InsertPerson(schema::legislator::LegislatorInput),
+    UpdatePerson(Id, schema::legislator::LegislatorInput),
     BuildPerson {
-        district: String,
+        /// The ID of the person, if they already exist and are being updated.
+        existing: Option<Id>,
+        district: Dependency,
+        party: Dependency,
         build: PersonBuilder,
     },
 }
 
+/// An object which must exist before some other object can be inserted.
+enum Dependency {
+    /// The object already exists in the database, with the given ID.
+    Existing(Id),
+    /// The object is being inserted in the same batch, and can be identified by the given key.
+    New(String),
+}
+
+impl Dependency {
+    /// Get the ID of this dependency, looking up newly inserted objects in `ids`.
+    fn resolve(self, ids: &HashMap<String, Id>, kind: &str) -> Result<Id, Error> {
+        match self {
+            Self::Existing(id) => Ok(id),
+            Self::New(key) => ids.get(&key).copied().ok_or_else(|| {
+                Error::msg(format!(
+                    "ICE: expected to find {kind} {key} after inserting it"
+                ))
+            }),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct InsertDistrict {
     state: State,
     name: String,
 }
 
-type PersonBuilder = Box<dyn Send + FnOnce(Id) -> schema::legislator::LegislatorInput>;
+type PersonBuilder = Box<dyn Send + FnOnce(Id, Id) -> schema::legislator::LegislatorInput>;
 
 impl From<InsertDistrict> for schema::district::DistrictInput {
     fn from(district: InsertDistrict) -> Self {
@@ -432,6 +1193,67 @@ impl From<InsertDistrict> for schema::district::DistrictInput {
     }
 }
 
+/// Load a bill by its ID in the database.
+pub(crate) async fn bill_by_id(conn: &Connection, id: Id) -> Result<Option<schema::Bill>, Error> {
+    find_one(
+        conn,
+        schema::Bill::has()
+            .id(I32Predicate::Is(Value::Lit(id.into())))
+            .into(),
+    )
+    .await
+}
+
+/// Load a legislator by their ID in the database.
+pub(crate) async fn legislator_by_id(
+    conn: &Connection,
+    id: Id,
+) -> Result<Option<schema::Legislator>, Error> {
+    find_one(
+        conn,
+        schema::Legislator::has()
+            .id(I32Predicate::Is(Value::Lit(id.into())))
+            .into(),
+    )
+    .await
+}
+
+/// Load a state by its ID in the database.
+pub(crate) async fn state_by_id(conn: &Connection, id: Id) -> Result<Option<schema::State>, Error> {
+    find_one(
+        conn,
+        schema::State::has()
+            .id(I32Predicate::Is(Value::Lit(id.into())))
+            .into(),
+    )
+    .await
+}
+
+/// Load a district by its ID in the database.
+pub(crate) async fn district_by_id(
+    conn: &Connection,
+    id: Id,
+) -> Result<Option<schema::District>, Error> {
+    find_one(
+        conn,
+        schema::District::has()
+            .id(I32Predicate::Is(Value::Lit(id.into())))
+            .into(),
+    )
+    .await
+}
+
+/// Load an issue by its ID in the database.
+pub(crate) async fn issue_by_id(conn: &Connection, id: Id) -> Result<Option<schema::Issue>, Error> {
+    find_one(
+        conn,
+        schema::Issue::has()
+            .id(I32Predicate::Is(Value::Lit(id.into())))
+            .into(),
+    )
+    .await
+}
+
 async fn find_bill(conn: &Connection, id: String) -> Result<Option<schema::Bill>, Error> {
     find_one(
         conn,
@@ -452,6 +1274,26 @@ async fn find_person(conn: &Connection, id: String) -> Result<Option<schema::Leg
     .await
 }
 
+async fn find_party(conn: &Connection, id: String) -> Result<Option<schema::Party>, Error> {
+    find_one(
+        conn,
+        schema::Party::has()
+            .legiscan_id(StringPredicate::Is(Value::Lit(id)))
+            .into(),
+    )
+    .await
+}
+
+async fn find_subject(conn: &Connection, name: String) -> Result<Option<schema::Subject>, Error> {
+    find_one(
+        conn,
+        schema::Subject::has()
+            .name(StringPredicate::Is(Value::Lit(name)))
+            .into(),
+    )
+    .await
+}
+
 async fn find_issue(conn: &Connection, id: String) -> Result<Option<schema::Issue>, Error> {
     find_one(
         conn,
@@ -462,7 +1304,50 @@ async fn find_issue(conn: &Connection, id: String) -> Result<Option<schema::Issu
     .await
 }
 
-async fn find_district(
+/// Look up the IDs of issues which are known to exist, indexing them by name.
+async fn find_issue_ids<I>(conn: &Connection, names: I) -> Result<HashMap<String, Id>, Error>
+where
+    I: IntoIterator,
+    I::Item: ToString,
+{
+    let ids = try_join_all(names.into_iter().map(|name| async move {
+        let name = name.to_string();
+        match find_issue(conn, name.clone()).await? {
+            Some(found) => Ok((name, found.id)),
+            None => Err(Error::msg(format!(
+                "ICE: expected to find issue {name} after inserting it"
+            ))),
+        }
+    }))
+    .await?;
+    Ok(ids.into_iter().collect())
+}
+
+async fn find_issue_suggestion(
+    conn: &Connection,
+    bill: Id,
+    issue: Id,
+) -> Result<Option<schema::IssueSuggestion>, Error> {
+    find_one(
+        conn,
+        schema::IssueSuggestion::has()
+            .bill(
+                schema::Bill::has()
+                    .id(I32Predicate::Is(Value::Lit(bill.into())))
+                    .into(),
+            )
+            .issue(
+                schema::Issue::has()
+                    .id(I32Predicate::Is(Value::Lit(issue.into())))
+                    .into(),
+            )
+            .into(),
+    )
+    .await
+}
+
+/// Find a district by the state containing it and its name, like "HD-001".
+pub async fn find_district(
     conn: &Connection,
     state: State,
     name: String,
@@ -481,6 +1366,12 @@ async fn find_district(
     .await
 }
 
+pub(crate) async fn find_all<T: Resource>(conn: &Connection) -> Result<Vec<T>, Error> {
+    let results = conn.query::<T>(None).await?;
+    let page = conn.load_page(&results, PageRequest::default()).await?;
+    Ok(page.into_iter().map(|edge| edge.into_node()).collect())
+}
+
 async fn find_one<T: Resource>(
     conn: &Connection,
     filter: T::Predicate,
@@ -0,0 +1,1205 @@
+//! Bulk export and import of the database.
+//!
+//! Exports are meant for analysis outside the app, and for seeding other databases with
+//! [`import`], without pulling from Legiscan. The database is exported as a set of tables, each of
+//! which can be written as CSV, JSON Lines or Parquet. Every column is a string, and entities are
+//! identified by natural keys (like Legiscan IDs and issue names) rather than internal database
+//! IDs, so exports of different databases with the same data are identical. The column schema is
+//! stable: columns may be added at the end of a table, but existing columns will not be renamed,
+//! removed or reordered.
+//!
+//! The tables and their columns are:
+//! * `bills`: `legiscan_id`, `legiscan_hash`, `state`, `session`, `name`, `title`, `summary`,
+//!   `status`, `status_date`
+//! * `legislators`: `legiscan_id`, `legiscan_hash`, `state`, `district`, `chamber`, `first_name`,
+//!   `middle_name`, `last_name`, `party`, `home_state`
+//! * `parties`: `legiscan_id`, `abbreviation`, `name`
+//! * `districts`: `state`, `name`
+//! * `issues`: `name`
+//! * `sponsorships`: `bill`, `legislator`
+//! * `bill_issues`: `bill`, `issue`
+//! * `votes`: `legiscan_id`, `bill`, `date`, `description`, `chamber`, `yea`, `nay`, `not_voting`,
+//!   `absent`, `passed`
+//! * `actions`: `bill`, `sequence`, `date`, `description`, `chamber`, `major`
+//! * `hearings`: `bill`, `kind`, `date`, `time`, `location`, `description`
+//!
+//! `state` and `home_state` columns hold state abbreviations; `district` holds the names of
+//! districts; `party`, `bill` and `legislator` hold Legiscan IDs; and `issue` holds issue names.
+//! Vote counts and the positions of actions in the histories of bills are decimal integers,
+//! `passed` and `major` are `true` or `false`, and `chamber`, `time` and `location` are empty when
+//! they are not known. Rows are sorted, so that exports of the same data are byte-for-byte
+//! identical.
+//!
+//! Exports can be narrowed to a state and a session. The state filter applies to bills,
+//! legislators, districts and the relations and histories of bills; the session filter applies to
+//! bills and their relations and histories. Parties and issues are always exported in full.
+
+use crate::{
+    catalog::{self, Params},
+    db::{self, SqlClient},
+    legiscan::State,
+    schema, search,
+};
+use anyhow::Error;
+use bytes::Bytes;
+use clap::Args;
+use futures::future::{try_join, try_join_all};
+use parquet::{
+    data_type::{ByteArray, ByteArrayType},
+    file::{
+        properties::WriterProperties,
+        reader::{FileReader, SerializedFileReader},
+        writer::SerializedFileWriter,
+    },
+    record::Field,
+    schema::parser::parse_message_type,
+};
+use relational_graphql::graphql::type_system::Id;
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    hash::Hash,
+    io::Write,
+    mem::take,
+    sync::Arc,
+};
+use strum::{Display, EnumIter, EnumString};
+use tokio_postgres::{Portal, Transaction};
+
+/// A table in an export.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString, EnumIter)]
+#[strum(serialize_all = "snake_case")]
+pub enum Table {
+    Bills,
+    Legislators,
+    Parties,
+    Districts,
+    Issues,
+    Sponsorships,
+    BillIssues,
+    Votes,
+    Actions,
+    Hearings,
+}
+
+impl Table {
+    /// The columns of this table, in order.
+    pub fn columns(&self) -> &'static [&'static str] {
+        match self {
+            Self::Bills => &[
+                "legiscan_id",
+                "legiscan_hash",
+                "state",
+                "session",
+                "name",
+                "title",
+                "summary",
+                "status",
+                "status_date",
+            ],
+            Self::Legislators => &[
+                "legiscan_id",
+                "legiscan_hash",
+                "state",
+                "district",
+                "chamber",
+                "first_name",
+                "middle_name",
+                "last_name",
+                "party",
+                "home_state",
+            ],
+            Self::Parties => &["legiscan_id", "abbreviation", "name"],
+            Self::Districts => &["state", "name"],
+            Self::Issues => &["name"],
+            Self::Sponsorships => &["bill", "legislator"],
+            Self::BillIssues => &["bill", "issue"],
+            Self::Votes => &[
+                "legiscan_id",
+                "bill",
+                "date",
+                "description",
+                "chamber",
+                "yea",
+                "nay",
+                "not_voting",
+                "absent",
+                "passed",
+            ],
+            Self::Actions => &[
+                "bill",
+                "sequence",
+                "date",
+                "description",
+                "chamber",
+                "major",
+            ],
+            Self::Hearings => &["bill", "kind", "date", "time", "location", "description"],
+        }
+    }
+}
+
+/// A format for exported tables.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    Csv,
+    Jsonl,
+    Parquet,
+}
+
+impl Format {
+    /// The MIME type of tables in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Jsonl => "application/x-ndjson; charset=utf-8",
+            Self::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    /// The name of the file holding `table` in this format.
+    pub fn file_name(&self, table: Table) -> String {
+        format!("{table}.{self}")
+    }
+}
+
+/// Criteria for narrowing an export.
+#[derive(Clone, Debug, Default, Args, Deserialize)]
+pub struct ExportFilter {
+    /// Only export data for STATE.
+    #[clap(short, long, name = "STATE")]
+    pub state: Option<State>,
+
+    /// Only export bills from SESSION.
+    #[clap(long, name = "SESSION")]
+    pub session: Option<String>,
+}
+
+impl ExportFilter {
+    /// Build a condition on `states s` selecting the state in this filter.
+    fn state_sql(&self, params: &mut Params) -> String {
+        match &self.state {
+            Some(state) => format!("s.abbreviation = {}", params.bind(state.to_string())),
+            None => "TRUE".into(),
+        }
+    }
+
+    /// Build a condition on `bills b` in `states s` selecting bills which match this filter.
+    fn bill_sql(&self, params: &mut Params) -> String {
+        let mut conditions = vec![self.state_sql(params)];
+        if let Some(session) = &self.session {
+            conditions.push(format!("b.session = {}", params.bind(session.clone())));
+        }
+        catalog::conjunction(conditions)
+    }
+}
+
+/// The rows of an exported table.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rows {
+    pub table: Table,
+    /// The rows, each with a value for every column in the table, in order.
+    pub rows: Vec<Vec<String>>,
+}
+
+/// The number of rows read from the database at a time.
+const BATCH_SIZE: i32 = 1000;
+
+/// Load the rows of `table` matching `filter`.
+///
+/// This reads the whole table into memory. Use [`Export`] to write large tables as they are read.
+pub async fn load(sql: &mut SqlClient, table: Table, filter: &ExportFilter) -> Result<Rows, Error> {
+    let mut export = Export::start(sql, table, filter).await?;
+    let mut rows = vec![];
+    while let Some(batch) = export.next_batch().await? {
+        rows.extend(batch);
+    }
+    Ok(Rows { table, rows })
+}
+
+/// An export of a table in progress.
+///
+/// The rows are filtered and sorted by the database, and read from it in batches, so that a large
+/// table never has to be held in memory. They are read through a portal in a transaction of their
+/// own, so the export holds on to the connection until it is dropped.
+pub struct Export<'a> {
+    table: Table,
+    transaction: Transaction<'a>,
+    portal: Portal,
+    /// Whether any of the file has been written yet.
+    started: bool,
+    finished: bool,
+}
+
+impl<'a> Export<'a> {
+    /// Start exporting the rows of `table` matching `filter`.
+    pub async fn start(
+        sql: &'a mut SqlClient,
+        table: Table,
+        filter: &ExportFilter,
+    ) -> Result<Self, Error> {
+        let mut params = Params::default();
+        let query = query(table, filter, &mut params);
+        let transaction = sql.transaction().await?;
+        let portal = transaction.bind(query.as_str(), &params.as_refs()).await?;
+        Ok(Self {
+            table,
+            transaction,
+            portal,
+            started: false,
+            finished: false,
+        })
+    }
+
+    /// The next chunk of the exported file in `format`, or `None` once the file is complete.
+    ///
+    /// CSV and JSON Lines files are written a batch of rows at a time. Parquet is columnar, so the
+    /// whole table is read before it is written, in a single chunk.
+    pub async fn next_chunk(&mut self, format: Format) -> Result<Option<Vec<u8>>, Error> {
+        if self.started && self.finished {
+            return Ok(None);
+        }
+        let mut rows = Rows {
+            table: self.table,
+            rows: vec![],
+        };
+        while let Some(batch) = self.next_batch().await? {
+            rows.rows.extend(batch);
+            if format != Format::Parquet {
+                break;
+            }
+        }
+        if self.started && rows.rows.is_empty() {
+            return Ok(None);
+        }
+
+        let mut chunk = vec![];
+        match format {
+            Format::Csv => {
+                if !self.started {
+                    writeln!(chunk, "{}", csv_row(self.table.columns()))?;
+                }
+                rows.write_csv(&mut chunk)?;
+            }
+            Format::Jsonl => rows.write_jsonl(&mut chunk)?,
+            Format::Parquet => rows.write_parquet(&mut chunk)?,
+        }
+        self.started = true;
+        Ok(Some(chunk))
+    }
+
+    /// The next batch of rows, or `None` once every row has been read.
+    async fn next_batch(&mut self) -> Result<Option<Vec<Vec<String>>>, Error> {
+        if self.finished {
+            return Ok(None);
+        }
+        let batch = self
+            .transaction
+            .query_portal(&self.portal, BATCH_SIZE)
+            .await?;
+        self.finished = batch.len() < BATCH_SIZE as usize;
+        if batch.is_empty() {
+            return Ok(None);
+        }
+        let columns = self.table.columns().len();
+        let rows = batch
+            .into_iter()
+            .map(|row| (0..columns).map(|i| Ok(row.try_get(i)?)).collect())
+            .collect::<Result<_, Error>>()?;
+        Ok(Some(rows))
+    }
+}
+
+/// Build a query selecting the rows of `table` matching `filter`.
+///
+/// The query selects a text column named after each column of the table, and sorts the rows by
+/// their columns in turn, comparing bytes, which is how [`Rows`] compare. Entities are identified
+/// by their natural keys, which are read from the tables backing the resources, and the relations
+/// between bills, their sponsors and their issues are read from the [catalog](crate::catalog).
+fn query(table: Table, filter: &ExportFilter, params: &mut Params) -> String {
+    let rows = match table {
+        Table::Bills => format!(
+            "SELECT
+                b.legiscan_id, b.legiscan_hash, s.abbreviation AS state, b.session, b.name,
+                b.title, b.summary, b.status, b.status_date
+            FROM bills b JOIN states s ON s.id = b.state
+            WHERE {}",
+            filter.bill_sql(params)
+        ),
+        Table::Legislators => format!(
+            "SELECT
+                l.legiscan_id, l.legiscan_hash, s.abbreviation AS state, d.name AS district,
+                l.chamber, l.first_name, l.middle_name, l.last_name, p.legiscan_id AS party,
+                h.abbreviation AS home_state
+            FROM legislators l
+            JOIN districts d ON d.id = l.district
+            JOIN states s ON s.id = d.state
+            JOIN parties p ON p.id = l.party
+            JOIN states h ON h.id = l.home_state
+            WHERE {}",
+            filter.state_sql(params)
+        ),
+        Table::Parties => "SELECT legiscan_id, abbreviation, name FROM parties".into(),
+        Table::Districts => format!(
+            "SELECT s.abbreviation AS state, d.name
+            FROM districts d JOIN states s ON s.id = d.state
+            WHERE {}",
+            filter.state_sql(params)
+        ),
+        Table::Issues => "SELECT name FROM issues".into(),
+        // Sponsors may be from other states, in the case of federal bills, so they are exported
+        // whatever the state filter.
+        Table::Sponsorships => format!(
+            "SELECT b.legiscan_id AS bill, l.legiscan_id AS legislator
+            FROM catalog_sponsors sp
+            JOIN bills b ON b.id = sp.bill
+            JOIN states s ON s.id = b.state
+            JOIN legislators l ON l.id = sp.legislator
+            WHERE {}",
+            filter.bill_sql(params)
+        ),
+        Table::BillIssues => format!(
+            "SELECT b.legiscan_id AS bill, i.name AS issue
+            FROM catalog_bill_issues bi
+            JOIN bills b ON b.id = bi.bill
+            JOIN states s ON s.id = b.state
+            JOIN issues i ON i.id = bi.issue
+            WHERE {}",
+            filter.bill_sql(params)
+        ),
+        Table::Votes => format!(
+            "SELECT
+                v.legiscan_id, b.legiscan_id AS bill, v.date, v.description,
+                coalesce(v.chamber, '') AS chamber, v.yea::text AS yea, v.nay::text AS nay,
+                v.not_voting::text AS not_voting, v.absent::text AS absent,
+                v.passed::text AS passed
+            FROM votes v
+            JOIN bills b ON b.id = v.bill
+            JOIN states s ON s.id = b.state
+            WHERE {}",
+            filter.bill_sql(params)
+        ),
+        Table::Actions => format!(
+            "SELECT
+                b.legiscan_id AS bill, a.sequence::text AS sequence, a.date, a.description,
+                coalesce(a.chamber, '') AS chamber, a.major::text AS major
+            FROM bill_actions a
+            JOIN bills b ON b.id = a.bill
+            JOIN states s ON s.id = b.state
+            WHERE {}",
+            filter.bill_sql(params)
+        ),
+        Table::Hearings => format!(
+            "SELECT b.legiscan_id AS bill, h.kind, h.date, h.time, h.location, h.description
+            FROM hearings h
+            JOIN bills b ON b.id = h.bill
+            JOIN states s ON s.id = b.state
+            WHERE {}",
+            filter.bill_sql(params)
+        ),
+    };
+    let order = table
+        .columns()
+        .iter()
+        .map(|column| format!("\"{column}\" COLLATE \"C\""))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("SELECT * FROM ({rows}) export ORDER BY {order}")
+}
+
+/// The scratch schema in which [`import`] stages the resources of a snapshot.
+const IMPORT_SCHEMA: &str = "import_scratch";
+
+/// Load a snapshot into an empty database.
+///
+/// `tables` are the tables of a snapshot written by [`load`] and [`Rows::write`]. The database must
+/// have been set up with [`db::setup`], but must not have any bills or legislators yet. The states,
+/// parties and issues created by the setup are left as they are, and everything else in the
+/// snapshot is inserted. The bills and legislators are added to the search index and the catalog,
+/// but nothing is added to feeds, since none of it is new to the world.
+///
+/// Every reference in the snapshot is checked before anything is written to the tables in use, so
+/// that a bad snapshot is rejected without changing the database. The import is then written in a
+/// single transaction, so that if any of it fails, the database is left empty and the import can
+/// be retried. Since the resource API writes through connections of its own, the resources are
+/// first inserted into a copy of the resource tables in a scratch schema, which is copied into the
+/// tables in use within the transaction, and dropped at the end.
+pub async fn import(opt: &db::Options, sql: &SqlClient, tables: &[Rows]) -> Result<(), Error> {
+    let populated: bool = sql
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM bills) OR EXISTS (SELECT 1 FROM legislators)",
+            &[],
+        )
+        .await?
+        .try_get(0)?;
+    if populated {
+        return Err(Error::msg(
+            "can only import into an empty database, but this one already has bills or legislators",
+        ));
+    }
+
+    let res = async {
+        let staged = stage(opt, sql).await?;
+        import_staged(opt, sql, &staged, tables).await
+    }
+    .await;
+    sql.batch_execute(&format!("DROP SCHEMA IF EXISTS {IMPORT_SCHEMA} CASCADE"))
+        .await?;
+    res
+}
+
+/// Import a snapshot through the scratch schema created by [`stage`].
+async fn import_staged(
+    opt: &db::Options,
+    sql: &SqlClient,
+    staged: &Staged,
+    tables: &[Rows],
+) -> Result<(), Error> {
+    let conn = &mut opt.connect_in_schema(IMPORT_SCHEMA).await?;
+    let parties = db::find_all::<schema::Party>(conn).await?;
+    let districts = db::find_all::<schema::District>(conn).await?;
+    let issues = db::find_all::<schema::Issue>(conn).await?;
+    check(tables, &parties, &districts, &issues)?;
+    let rows = |table: Table| table_rows(tables, table);
+
+    // Insert parties, districts and issues, which other entities refer to.
+    let parties = parties
+        .into_iter()
+        .map(|party| party.legiscan_id)
+        .collect::<HashSet<_>>();
+    conn.insert::<schema::Party, _>(
+        rows(Table::Parties)
+            .filter(|row| !parties.contains(&row[0]))
+            .map(|row| schema::party::PartyInput {
+                legiscan_id: row[0].clone(),
+                abbreviation: row[1].clone(),
+                name: row[2].clone(),
+            }),
+    )
+    .await?;
+    let districts = districts
+        .into_iter()
+        .map(|district| (district.state.abbreviation, district.name))
+        .collect::<HashSet<_>>();
+    let insert_districts = rows(Table::Districts)
+        .filter(|row| !districts.contains(&(row[0].clone(), row[1].clone())))
+        .map(|row| {
+            Ok(schema::district::DistrictInput {
+                state: state_id(&row[0])?,
+                name: row[1].clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    conn.insert::<schema::District, _>(insert_districts).await?;
+
+    // Issues which are not in the taxonomy are at the top of the hierarchy, so each one is its only
+    // ancestor.
+    let issues = issues
+        .into_iter()
+        .map(|issue| issue.name)
+        .collect::<HashSet<_>>();
+    let new_issues = rows(Table::Issues)
+        .map(|row| row[0].clone())
+        .filter(|name| !issues.contains(name))
+        .collect::<HashSet<_>>();
+    conn.insert::<schema::Issue, _>(
+        new_issues
+            .iter()
+            .map(|name| schema::issue::IssueInput { name: name.clone() }),
+    )
+    .await?;
+    let issue_ids = db::find_all::<schema::Issue>(conn)
+        .await?
+        .into_iter()
+        .map(|issue| (issue.name, issue.id))
+        .collect::<HashMap<_, _>>();
+    let new_issue_ids = new_issues
+        .iter()
+        .map(|name| Ok((name.clone(), lookup(&issue_ids, name.clone(), "issue")?)))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let ancestors = new_issue_ids
+        .iter()
+        .map(|(_, id)| (*id, *id))
+        .collect::<Vec<_>>();
+    conn.populate_relation::<schema::issue::fields::Ancestors, _>(ancestors.clone())
+        .await?;
+
+    // Insert legislators and bills.
+    let party_ids = db::find_all::<schema::Party>(conn)
+        .await?
+        .into_iter()
+        .map(|party| (party.legiscan_id, party.id))
+        .collect::<HashMap<_, _>>();
+    let district_ids = db::find_all::<schema::District>(conn)
+        .await?
+        .into_iter()
+        .map(|district| ((district.state.abbreviation, district.name), district.id))
+        .collect::<HashMap<_, _>>();
+    let insert_legislators = rows(Table::Legislators)
+        .map(|row| {
+            Ok(schema::legislator::LegislatorInput {
+                legiscan_id: row[0].clone(),
+                legiscan_hash: row[1].clone(),
+                district: lookup(&district_ids, (row[2].clone(), row[3].clone()), "district")?,
+                chamber: row[4].clone(),
+                first_name: row[5].clone(),
+                middle_name: row[6].clone(),
+                last_name: row[7].clone(),
+                party: lookup(&party_ids, row[8].clone(), "party")?,
+                home_state: state_id(&row[9])?,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    conn.insert::<schema::Legislator, _>(insert_legislators)
+        .await?;
+    let insert_bills = rows(Table::Bills)
+        .map(|row| {
+            Ok(schema::bill::BillInput {
+                legiscan_id: row[0].clone(),
+                legiscan_hash: row[1].clone(),
+                state: state_id(&row[2])?,
+                session: row[3].clone(),
+                name: row[4].clone(),
+                title: row[5].clone(),
+                summary: row[6].clone(),
+                status: row[7].clone(),
+                status_date: row[8].clone(),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    conn.insert::<schema::Bill, _>(insert_bills).await?;
+
+    // Relate the bills to their sponsors and issues.
+    let bills = db::find_all::<schema::Bill>(conn).await?;
+    let legislators = db::find_all::<schema::Legislator>(conn).await?;
+    let bill_ids = bills
+        .iter()
+        .map(|bill| (bill.legiscan_id.clone(), bill.id))
+        .collect::<HashMap<_, _>>();
+    let legislator_ids = legislators
+        .iter()
+        .map(|legislator| (legislator.legiscan_id.clone(), legislator.id))
+        .collect::<HashMap<_, _>>();
+    let sponsors = rows(Table::Sponsorships)
+        .map(|row| {
+            Ok((
+                lookup(&bill_ids, row[0].clone(), "bill")?,
+                lookup(&legislator_ids, row[1].clone(), "legislator")?,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let bill_issues = rows(Table::BillIssues)
+        .map(|row| {
+            Ok((
+                lookup(&bill_ids, row[0].clone(), "bill")?,
+                lookup(&issue_ids, row[1].clone(), "issue")?,
+            ))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    conn.populate_relation::<schema::bill::fields::Sponsors, _>(sponsors.clone())
+        .await?;
+    conn.populate_relation::<schema::bill::fields::Issues, _>(bill_issues.clone())
+        .await?;
+
+    // Copy the resources into the tables in use, index everything, and record the votes, actions
+    // and hearings on the bills, in one transaction.
+    sql.batch_execute("BEGIN").await?;
+    let res = async {
+        staged.publish(sql).await?;
+        try_join_all(
+            new_issue_ids
+                .iter()
+                .map(|(name, id)| db::index_issue(sql, *id, name)),
+        )
+        .await?;
+        catalog::index_issue_ancestors(sql, &ancestors).await?;
+        try_join(
+            catalog::index_sponsors(sql, &sponsors),
+            catalog::index_bill_issues(sql, &bill_issues),
+        )
+        .await?;
+        try_join(
+            try_join_all(bills.iter().map(|bill| {
+                try_join(
+                    search::index_bill(sql, bill),
+                    catalog::index_bill(sql, bill),
+                )
+            })),
+            try_join_all(legislators.iter().map(|legislator| {
+                try_join(
+                    search::index_legislator(sql, legislator),
+                    catalog::index_legislator(sql, legislator),
+                )
+            })),
+        )
+        .await?;
+        import_history(sql, tables, &bill_ids).await
+    }
+    .await;
+    match res {
+        Ok(()) => sql.batch_execute("COMMIT").await?,
+        Err(err) => {
+            sql.batch_execute("ROLLBACK").await?;
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// The resource tables in the scratch schema of an import.
+struct Staged {
+    /// The tables, in an order in which rows can be inserted without breaking foreign keys.
+    tables: Vec<StagedTable>,
+    /// The quoted names of the sequences generating IDs.
+    sequences: Vec<String>,
+}
+
+/// A resource table in the scratch schema of an import.
+struct StagedTable {
+    /// The quoted name of the table.
+    name: String,
+    /// The quoted names of the columns of the table, separated by commas.
+    columns: String,
+}
+
+/// Create the scratch schema of an import, with a copy of the resources in the database.
+///
+/// The resources are registered in the scratch schema, like [`db::setup`] does, which gives it the
+/// same tables, indexes and sequences as the schema in use. The rows of the tables and the values
+/// of the sequences are then copied over, so that resources inserted in the scratch schema get the
+/// IDs they would have had in the schema in use.
+async fn stage(opt: &db::Options, sql: &SqlClient) -> Result<Staged, Error> {
+    sql.batch_execute(&format!(
+        "DROP SCHEMA IF EXISTS {IMPORT_SCHEMA} CASCADE; CREATE SCHEMA {IMPORT_SCHEMA};"
+    ))
+    .await?;
+    let mut conn = opt.connect_in_schema(IMPORT_SCHEMA).await?;
+    schema::Query::register(&mut conn).await?;
+    drop(conn);
+
+    let columns = sql
+        .query(
+            "SELECT
+                quote_ident(t.relname) AS table_name,
+                string_agg(quote_ident(a.attname), ', ' ORDER BY a.attnum) AS columns
+            FROM pg_attribute a
+                JOIN pg_class t ON t.oid = a.attrelid
+                JOIN pg_namespace n ON n.oid = t.relnamespace
+            WHERE n.nspname = $1 AND t.relkind = 'r' AND a.attnum > 0 AND NOT a.attisdropped
+            GROUP BY t.relname",
+            &[&IMPORT_SCHEMA],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Ok((row.try_get("table_name")?, row.try_get("columns")?)))
+        .collect::<Result<HashMap<String, String>, Error>>()?;
+    let keys = sql
+        .query(
+            "SELECT quote_ident(t.relname) AS table_name, quote_ident(r.relname) AS referenced
+            FROM pg_constraint c
+                JOIN pg_class t ON t.oid = c.conrelid
+                JOIN pg_namespace n ON n.oid = t.relnamespace
+                JOIN pg_class r ON r.oid = c.confrelid
+            WHERE n.nspname = $1 AND c.contype = 'f' AND r.oid <> t.oid",
+            &[&IMPORT_SCHEMA],
+        )
+        .await?
+        .into_iter()
+        .map(|row| Ok((row.try_get("table_name")?, row.try_get("referenced")?)))
+        .collect::<Result<Vec<(String, String)>, Error>>()?;
+
+    // Order the tables so that each comes after the tables it refers to.
+    let mut remaining = columns.keys().cloned().collect::<Vec<_>>();
+    remaining.sort();
+    let mut tables = vec![];
+    while !remaining.is_empty() {
+        let (ready, blocked): (Vec<_>, Vec<_>) = remaining.iter().cloned().partition(|table| {
+            !keys
+                .iter()
+                .any(|(from, to)| from == table && remaining.contains(to))
+        });
+        if ready.is_empty() {
+            return Err(Error::msg(format!(
+                "ICE: foreign keys between resource tables form a cycle: {blocked:?}"
+            )));
+        }
+        tables.extend(ready.into_iter().map(|table| StagedTable {
+            name: table.clone(),
+            columns: columns[&table].clone(),
+        }));
+        remaining = blocked;
+    }
+
+    let sequences = sql
+        .query(
+            "SELECT quote_ident(s.relname) AS name
+            FROM pg_class s JOIN pg_namespace n ON n.oid = s.relnamespace
+            WHERE n.nspname = $1 AND s.relkind = 'S'",
+            &[&IMPORT_SCHEMA],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.try_get("name"))
+        .collect::<Result<Vec<String>, _>>()?;
+
+    for table in &tables {
+        sql.batch_execute(&format!(
+            "INSERT INTO {IMPORT_SCHEMA}.{name} ({columns}) SELECT {columns} FROM {name}",
+            name = table.name,
+            columns = table.columns,
+        ))
+        .await?;
+    }
+    for sequence in &sequences {
+        copy_sequence(sql, sequence, &format!("{IMPORT_SCHEMA}.{sequence}")).await?;
+    }
+    Ok(Staged { tables, sequences })
+}
+
+impl Staged {
+    /// Copy the rows added to the scratch schema into the tables in use.
+    async fn publish(&self, sql: &SqlClient) -> Result<(), Error> {
+        for table in &self.tables {
+            sql.batch_execute(&format!(
+                "INSERT INTO {name} ({columns})
+                SELECT {columns} FROM {IMPORT_SCHEMA}.{name}
+                EXCEPT SELECT {columns} FROM {name}",
+                name = table.name,
+                columns = table.columns,
+            ))
+            .await?;
+        }
+        for sequence in &self.sequences {
+            copy_sequence(sql, &format!("{IMPORT_SCHEMA}.{sequence}"), sequence).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Set the sequence `to` to the value of the sequence `from`.
+async fn copy_sequence(sql: &SqlClient, from: &str, to: &str) -> Result<(), Error> {
+    sql.query_one(
+        &format!("SELECT setval($1::text::regclass, last_value, is_called) FROM {from}"),
+        &[&to],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Record the votes, actions and hearings on the bills in a snapshot.
+///
+/// `bill_ids` are the IDs of the bills in the database, by Legiscan ID.
+async fn import_history(
+    sql: &SqlClient,
+    tables: &[Rows],
+    bill_ids: &HashMap<String, Id>,
+) -> Result<(), Error> {
+    try_join_all(table_rows(tables, Table::Votes).map(|row| async move {
+        sql.execute(
+            "INSERT INTO votes (
+                    legiscan_id, bill, date, description, chamber,
+                    yea, nay, not_voting, absent, passed
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
+            &[
+                &row[0],
+                &lookup(bill_ids, row[1].clone(), "bill")?,
+                &row[2],
+                &row[3],
+                &optional(&row[4]),
+                &row[5].parse::<i32>()?,
+                &row[6].parse::<i32>()?,
+                &row[7].parse::<i32>()?,
+                &row[8].parse::<i32>()?,
+                &row[9].parse::<bool>()?,
+            ],
+        )
+        .await?;
+        Ok::<_, Error>(())
+    }))
+    .await?;
+    try_join_all(table_rows(tables, Table::Actions).map(|row| async move {
+        sql.execute(
+            "INSERT INTO bill_actions (bill, sequence, date, description, chamber, major)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &lookup(bill_ids, row[0].clone(), "bill")?,
+                &row[1].parse::<i32>()?,
+                &row[2],
+                &row[3],
+                &optional(&row[4]),
+                &row[5].parse::<bool>()?,
+            ],
+        )
+        .await?;
+        Ok::<_, Error>(())
+    }))
+    .await?;
+    try_join_all(table_rows(tables, Table::Hearings).map(|row| async move {
+        sql.execute(
+            "INSERT INTO hearings (bill, kind, date, time, location, description)
+                VALUES ($1, $2, $3, $4, $5, $6)",
+            &[
+                &lookup(bill_ids, row[0].clone(), "bill")?,
+                &row[1],
+                &row[2],
+                &row[3],
+                &row[4],
+                &row[5],
+            ],
+        )
+        .await?;
+        Ok::<_, Error>(())
+    }))
+    .await?;
+    Ok(())
+}
+
+/// Check that every reference in a snapshot resolves, and that every value parses.
+///
+/// `parties`, `districts` and `issues` are the entities already in the database, which the
+/// snapshot may refer to without including them.
+fn check(
+    tables: &[Rows],
+    parties: &[schema::Party],
+    districts: &[schema::District],
+    issues: &[schema::Issue],
+) -> Result<(), Error> {
+    let rows = |table: Table| table_rows(tables, table);
+    let parties = parties
+        .iter()
+        .map(|party| party.legiscan_id.as_str())
+        .chain(rows(Table::Parties).map(|row| row[0].as_str()))
+        .collect::<HashSet<_>>();
+    let districts = districts
+        .iter()
+        .map(|district| (district.state.abbreviation.as_str(), district.name.as_str()))
+        .chain(rows(Table::Districts).map(|row| (row[0].as_str(), row[1].as_str())))
+        .collect::<HashSet<_>>();
+    let issues = issues
+        .iter()
+        .map(|issue| issue.name.as_str())
+        .chain(rows(Table::Issues).map(|row| row[0].as_str()))
+        .collect::<HashSet<_>>();
+    let legislators = rows(Table::Legislators)
+        .map(|row| row[0].as_str())
+        .collect::<HashSet<_>>();
+    let bills = rows(Table::Bills)
+        .map(|row| row[0].as_str())
+        .collect::<HashSet<_>>();
+
+    for row in rows(Table::Districts) {
+        state_id(&row[0])?;
+    }
+    for row in rows(Table::Legislators) {
+        if !districts.contains(&(row[2].as_str(), row[3].as_str())) {
+            return Err(Error::msg(format!(
+                "snapshot refers to unknown district {} {}",
+                row[2], row[3]
+            )));
+        }
+        require(&parties, &row[8], "party")?;
+        state_id(&row[9])?;
+    }
+    for row in rows(Table::Bills) {
+        state_id(&row[2])?;
+    }
+    for row in rows(Table::Sponsorships) {
+        require(&bills, &row[0], "bill")?;
+        require(&legislators, &row[1], "legislator")?;
+    }
+    for row in rows(Table::BillIssues) {
+        require(&bills, &row[0], "bill")?;
+        require(&issues, &row[1], "issue")?;
+    }
+    for row in rows(Table::Votes) {
+        require(&bills, &row[1], "bill")?;
+        for count in &row[5..9] {
+            count.parse::<i32>()?;
+        }
+        row[9].parse::<bool>()?;
+    }
+    for row in rows(Table::Actions) {
+        require(&bills, &row[0], "bill")?;
+        row[1].parse::<i32>()?;
+        row[5].parse::<bool>()?;
+    }
+    for row in rows(Table::Hearings) {
+        require(&bills, &row[0], "bill")?;
+    }
+    Ok(())
+}
+
+/// Fail if a snapshot refers to an entity `key` of `kind` which is not in `known`.
+fn require(known: &HashSet<&str>, key: &str, kind: &str) -> Result<(), Error> {
+    if known.contains(key) {
+        Ok(())
+    } else {
+        Err(Error::msg(format!(
+            "snapshot refers to unknown {kind} {key:?}"
+        )))
+    }
+}
+
+/// The rows of `table` in a snapshot.
+fn table_rows(tables: &[Rows], table: Table) -> impl Iterator<Item = &Vec<String>> {
+    tables
+        .iter()
+        .filter(move |snapshot| snapshot.table == table)
+        .flat_map(|snapshot| &snapshot.rows)
+}
+
+/// The ID of the state with abbreviation `abbreviation`.
+fn state_id(abbreviation: &str) -> Result<Id, Error> {
+    let state: State = abbreviation
+        .parse()
+        .map_err(|_| Error::msg(format!("unknown state {abbreviation}")))?;
+    Ok(state.id().into())
+}
+
+/// The value of an optional column, which is empty when the value is not known.
+fn optional(value: &str) -> Option<&str> {
+    (!value.is_empty()).then_some(value)
+}
+
+/// Look up the ID of an entity referred to by a snapshot.
+fn lookup<K: Debug + Eq + Hash>(ids: &HashMap<K, Id>, key: K, kind: &str) -> Result<Id, Error> {
+    ids.get(&key)
+        .copied()
+        .ok_or_else(|| Error::msg(format!("snapshot refers to unknown {kind} {key:?}")))
+}
+
+impl Rows {
+    /// Read a table written in `format` by [`write`](Self::write).
+    ///
+    /// Columns are matched by name, so they may be in any order, and unknown columns are ignored.
+    pub fn read(table: Table, format: Format, data: Vec<u8>) -> Result<Self, Error> {
+        let records = match format {
+            Format::Csv => read_csv(&String::from_utf8(data)?)?,
+            Format::Jsonl => String::from_utf8(data)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| Ok(serde_json::from_str(line)?))
+                .collect::<Result<_, Error>>()?,
+            Format::Parquet => read_parquet(data)?,
+        };
+        let rows = records
+            .into_iter()
+            .map(|mut record| {
+                table
+                    .columns()
+                    .iter()
+                    .map(|column| {
+                        record.remove(*column).ok_or_else(|| {
+                            Error::msg(format!("{table} is missing column {column}"))
+                        })
+                    })
+                    .collect::<Result<Vec<_>, Error>>()
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(Self { table, rows })
+    }
+
+    /// Write the table to `w` in `format`.
+    pub fn write(&self, format: Format, mut w: impl Write + Send) -> Result<(), Error> {
+        match format {
+            Format::Csv => {
+                writeln!(w, "{}", csv_row(self.table.columns()))?;
+                self.write_csv(w)
+            }
+            Format::Jsonl => self.write_jsonl(w),
+            Format::Parquet => self.write_parquet(w),
+        }
+    }
+
+    /// Write the rows as CSV, without the header.
+    fn write_csv(&self, mut w: impl Write) -> Result<(), Error> {
+        for row in &self.rows {
+            writeln!(w, "{}", csv_row(row))?;
+        }
+        Ok(())
+    }
+
+    fn write_jsonl(&self, mut w: impl Write) -> Result<(), Error> {
+        for row in &self.rows {
+            // Write the object by hand, rather than through a map, to keep the columns in order.
+            let fields = self
+                .table
+                .columns()
+                .iter()
+                .zip(row)
+                .map(|(column, value)| {
+                    Ok(format!(
+                        "{}:{}",
+                        serde_json::to_string(column)?,
+                        serde_json::to_string(value)?
+                    ))
+                })
+                .collect::<Result<Vec<_>, Error>>()?;
+            writeln!(w, "{{{}}}", fields.join(","))?;
+        }
+        Ok(())
+    }
+
+    fn write_parquet(&self, w: impl Write + Send) -> Result<(), Error> {
+        let schema = format!(
+            "message {} {{ {} }}",
+            self.table,
+            self.table
+                .columns()
+                .iter()
+                .map(|column| format!("REQUIRED BYTE_ARRAY {column} (UTF8);"))
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        let mut writer = SerializedFileWriter::new(
+            w,
+            Arc::new(parse_message_type(&schema)?),
+            Arc::new(WriterProperties::builder().build()),
+        )?;
+        let mut row_group = writer.next_row_group()?;
+        let mut i = 0;
+        while let Some(mut column) = row_group.next_column()? {
+            let values = self
+                .rows
+                .iter()
+                .map(|row| ByteArray::from(row[i].as_str()))
+                .collect::<Vec<_>>();
+            column
+                .typed::<ByteArrayType>()
+                .write_batch(&values, None, None)?;
+            column.close()?;
+            i += 1;
+        }
+        row_group.close()?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// Read the records of a CSV table with a header, indexing the fields of each by column.
+fn read_csv(text: &str) -> Result<Vec<HashMap<String, String>>, Error> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                // An escaped quote.
+                chars.next();
+                field.push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => record.push(take(&mut field)),
+            '\r' if !quoted && chars.peek() == Some(&'\n') => {}
+            '\n' if !quoted => {
+                record.push(take(&mut field));
+                records.push(take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(Error::msg("unterminated quoted field in CSV"));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    let mut records = records.into_iter();
+    let Some(header) = records.next() else {
+        return Err(Error::msg("CSV table has no header"));
+    };
+    Ok(records
+        .map(|record| header.iter().cloned().zip(record).collect())
+        .collect())
+}
+
+/// Read the records of a Parquet table, indexing the fields of each by column.
+fn read_parquet(data: Vec<u8>) -> Result<Vec<HashMap<String, String>>, Error> {
+    let reader = SerializedFileReader::new(Bytes::from(data))?;
+    let mut records = vec![];
+    for row in reader.get_row_iter(None)? {
+        let record = row?
+            .get_column_iter()
+            .map(|(column, field)| match field {
+                Field::Str(value) => Ok((column.clone(), value.clone())),
+                _ => Err(Error::msg(format!("column {column} is not a string"))),
+            })
+            .collect::<Result<_, Error>>()?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Format a row of CSV, without the line terminator.
+fn csv_row(fields: &[impl AsRef<str>]) -> String {
+    fields
+        .iter()
+        .map(|field| csv_field(field.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Quote a CSV field, if necessary.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rows() -> Rows {
+        Rows {
+            table: Table::Sponsorships,
+            rows: vec![
+                vec!["1".into(), "2".into()],
+                vec!["3, \"4\"".into(), "5\n6".into()],
+            ],
+        }
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let mut out = vec![];
+        rows().write(Format::Csv, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "bill,legislator\n1,2\n\"3, \"\"4\"\"\",\"5\n6\"\n"
+        );
+    }
+
+    #[test]
+    fn test_write_jsonl() {
+        let mut out = vec![];
+        rows().write(Format::Jsonl, &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "{\"bill\":\"1\",\"legislator\":\"2\"}\n\
+            {\"bill\":\"3, \\\"4\\\"\",\"legislator\":\"5\\n6\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_read() {
+        for format in [Format::Csv, Format::Jsonl] {
+            let mut out = vec![];
+            rows().write(format, &mut out).unwrap();
+            assert_eq!(
+                Rows::read(Table::Sponsorships, format, out).unwrap(),
+                rows(),
+                "{format}"
+            );
+        }
+    }
+}
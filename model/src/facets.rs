@@ -0,0 +1,230 @@
+//! Faceted counts for filtering bills and legislators.
+//!
+//! A facet breaks a selection of bills or legislators down by the values of one field, like the
+//! state or party, counting the matches for each value. This is what a filter panel needs to show
+//! "Colorado (42) · DC (7)" next to each option.
+//!
+//! Facets are computed from the [catalog](crate::catalog) tables, all in a single query. Each facet
+//! is counted under every criterion of the filter _except_ the one for the facet's own field, so
+//! that selecting "Colorado" narrows the party counts to Colorado, but still shows how many bills
+//! there are in other states.
+
+use crate::{
+    catalog::{BillFilter, LegislatorFilter, Params},
+    db::SqlClient,
+};
+use anyhow::Error;
+use async_graphql::{Context, Object, SimpleObject};
+use relational_graphql::graphql::type_system::Id;
+use std::collections::HashMap;
+
+/// The number of matches for one value of a facet.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct FacetValue {
+    /// The value, in the form used by the corresponding filter.
+    ///
+    /// For issues, this is the name of the issue, and `id` is used in filters.
+    pub value: String,
+    /// The ID of the entity identified by `value`, for facets over entities like issues.
+    pub id: Option<Id>,
+    /// The number of matches with this value.
+    pub count: i64,
+}
+
+/// Facet counts for a selection of bills.
+#[derive(Clone, Debug, Default, SimpleObject)]
+pub struct BillFacets {
+    /// Bill counts by state abbreviation.
+    pub state: Vec<FacetValue>,
+    /// Bill counts by the party abbreviations of their sponsors.
+    ///
+    /// A bill with sponsors from more than one party is counted under each party.
+    pub party: Vec<FacetValue>,
+    /// Bill counts by issue.
+    pub issue: Vec<FacetValue>,
+    /// Bill counts by status.
+    pub status: Vec<FacetValue>,
+    /// Bill counts by session name.
+    pub session: Vec<FacetValue>,
+}
+
+/// Facet counts for a selection of legislators.
+#[derive(Clone, Debug, Default, SimpleObject)]
+pub struct LegislatorFacets {
+    /// Legislator counts by state abbreviation.
+    pub state: Vec<FacetValue>,
+    /// Legislator counts by party abbreviation.
+    pub party: Vec<FacetValue>,
+    /// Legislator counts by chamber name.
+    pub chamber: Vec<FacetValue>,
+}
+
+/// Queries for facet counts.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FacetQuery;
+
+#[Object]
+impl FacetQuery {
+    /// Facet counts for the bills matching `filter`.
+    ///
+    /// Within each facet, values are ordered by count, most matches first.
+    async fn bill_facets(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<BillFilter>,
+    ) -> async_graphql::Result<BillFacets> {
+        Ok(bill_facets(ctx.data()?, filter.unwrap_or_default()).await?)
+    }
+
+    /// Facet counts for the legislators matching `filter`.
+    ///
+    /// Within each facet, values are ordered by count, most matches first.
+    async fn legislator_facets(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<LegislatorFilter>,
+    ) -> async_graphql::Result<LegislatorFacets> {
+        Ok(legislator_facets(ctx.data()?, filter.unwrap_or_default()).await?)
+    }
+}
+
+/// Count the bills matching `filter` by each facet.
+pub async fn bill_facets(sql: &SqlClient, filter: BillFilter) -> Result<BillFacets, Error> {
+    let mut params = Params::default();
+    let state = BillFilter {
+        state: None,
+        ..filter.clone()
+    }
+    .sql(&mut params);
+    let party = BillFilter {
+        party: None,
+        ..filter.clone()
+    }
+    .sql(&mut params);
+    let issue = BillFilter {
+        issue: None,
+        ..filter.clone()
+    }
+    .sql(&mut params);
+    let status = BillFilter {
+        status: None,
+        ..filter.clone()
+    }
+    .sql(&mut params);
+    let session = BillFilter {
+        session: None,
+        ..filter
+    }
+    .sql(&mut params);
+
+    let rows = sql
+        .query(
+            format!(
+                "SELECT 'state' AS facet, b.state AS value, NULL::INTEGER AS id, count(*) AS count
+                    FROM catalog_bills b WHERE {state}
+                    GROUP BY b.state
+                UNION ALL
+                SELECT 'party', l.party, NULL, count(DISTINCT b.id)
+                    FROM catalog_bills b
+                    JOIN catalog_sponsors s ON s.bill = b.id
+                    JOIN catalog_legislators l ON l.id = s.legislator
+                    WHERE {party}
+                    GROUP BY l.party
+                UNION ALL
+                SELECT 'issue', i.name, i.id, count(*)
+                    FROM catalog_bills b
+                    JOIN catalog_bill_issues bi ON bi.bill = b.id
+                    JOIN catalog_issues i ON i.id = bi.issue
+                    WHERE {issue}
+                    GROUP BY i.id, i.name
+                UNION ALL
+                SELECT 'status', b.status, NULL, count(*)
+                    FROM catalog_bills b WHERE {status}
+                    GROUP BY b.status
+                UNION ALL
+                SELECT 'session', b.session, NULL, count(*)
+                    FROM catalog_bills b WHERE {session}
+                    GROUP BY b.session
+                ORDER BY facet, count DESC, value"
+            )
+            .as_str(),
+            &params.as_refs(),
+        )
+        .await?;
+
+    let mut facets = group_facets(rows)?;
+    Ok(BillFacets {
+        state: facets.remove("state").unwrap_or_default(),
+        party: facets.remove("party").unwrap_or_default(),
+        issue: facets.remove("issue").unwrap_or_default(),
+        status: facets.remove("status").unwrap_or_default(),
+        session: facets.remove("session").unwrap_or_default(),
+    })
+}
+
+/// Count the legislators matching `filter` by each facet.
+pub async fn legislator_facets(
+    sql: &SqlClient,
+    filter: LegislatorFilter,
+) -> Result<LegislatorFacets, Error> {
+    let mut params = Params::default();
+    let state = LegislatorFilter {
+        state: None,
+        ..filter.clone()
+    }
+    .sql(&mut params);
+    let party = LegislatorFilter {
+        party: None,
+        ..filter.clone()
+    }
+    .sql(&mut params);
+    let chamber = LegislatorFilter {
+        chamber: None,
+        ..filter
+    }
+    .sql(&mut params);
+
+    let rows = sql
+        .query(
+            format!(
+                "SELECT 'state' AS facet, l.state AS value, NULL::INTEGER AS id, count(*) AS count
+                    FROM catalog_legislators l WHERE {state}
+                    GROUP BY l.state
+                UNION ALL
+                SELECT 'party', l.party, NULL, count(*)
+                    FROM catalog_legislators l WHERE {party}
+                    GROUP BY l.party
+                UNION ALL
+                SELECT 'chamber', l.chamber, NULL, count(*)
+                    FROM catalog_legislators l WHERE {chamber}
+                    GROUP BY l.chamber
+                ORDER BY facet, count DESC, value"
+            )
+            .as_str(),
+            &params.as_refs(),
+        )
+        .await?;
+
+    let mut facets = group_facets(rows)?;
+    Ok(LegislatorFacets {
+        state: facets.remove("state").unwrap_or_default(),
+        party: facets.remove("party").unwrap_or_default(),
+        chamber: facets.remove("chamber").unwrap_or_default(),
+    })
+}
+
+/// Group rows of `(facet, value, id, count)` by facet, preserving their order.
+fn group_facets(rows: Vec<tokio_postgres::Row>) -> Result<HashMap<String, Vec<FacetValue>>, Error> {
+    let mut facets: HashMap<String, Vec<FacetValue>> = HashMap::new();
+    for row in rows {
+        facets
+            .entry(row.try_get("facet")?)
+            .or_default()
+            .push(FacetValue {
+                value: row.try_get("value")?,
+                id: row.try_get("id")?,
+                count: row.try_get("count")?,
+            });
+    }
+    Ok(facets)
+}
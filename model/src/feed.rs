@@ -0,0 +1,404 @@
+//! Personalized feeds of changes to followed entities.
+//!
+//! When [`db::update`] finds something new in Legiscan data, like a new bill, a change in a bill's
+//! status, a new cosponsor, a new [vote](crate::votes) or a newly scheduled
+//! [hearing](crate::hearings), it records a feed event in a supplementary table, `feed_events`.
+//! Each event concerns a set of subjects: the bill itself, its state, its sponsors, and its issues
+//! along with every issue containing them, so that following an issue covers its sub-issues. A
+//! user's feed consists of the events concerning any entity they [follow](crate::follows), newest
+//! first.
+
+use crate::{
+    accounts::Viewer,
+    db::{self, SqlClient},
+    follows::FollowKind,
+    hearings::{self, Hearing},
+    live, schema,
+    votes::{self, Vote},
+};
+use anyhow::Error;
+use async_graphql::{
+    connection::{Connection, CursorType, Edge},
+    ComplexObject, Context, Enum, Object, SimpleObject,
+};
+use chrono::{DateTime, Utc};
+use relational_graphql::graphql::type_system::Id;
+use strum::{Display, EnumString};
+
+/// The maximum number of events returned in a single page.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// The kinds of changes recorded in feeds.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString, Enum)]
+#[strum(serialize_all = "snake_case")]
+pub enum FeedEventKind {
+    /// A bill was introduced.
+    NewBill,
+    /// A bill's status changed, for example from "Introduced" to "Passed".
+    StatusChanged,
+    /// A legislator started sponsoring a bill after it was introduced.
+    NewSponsor,
+    /// A roll-call vote was held on a bill.
+    NewVote,
+    /// A hearing or other meeting was scheduled on a bill.
+    HearingScheduled,
+}
+
+impl FeedEventKind {
+    /// A one-line summary of an event of this kind concerning the bill `name` in `state`.
+    ///
+    /// `detail` is the [detail](FeedEvent::detail) of the event.
+    pub fn summary(&self, state: &str, name: &str, status: &str, detail: &str) -> String {
+        match self {
+            Self::NewBill => format!("{state} {name} was introduced"),
+            Self::StatusChanged => format!("{state} {name} is now {status}"),
+            Self::NewSponsor => format!("{detail} is now sponsoring {state} {name}"),
+            Self::NewVote | Self::HearingScheduled => format!("{state} {name}: {detail}"),
+        }
+    }
+}
+
+/// A change to a bill which appears in users' feeds.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct FeedEvent {
+    pub id: i32,
+    /// What kind of change this is.
+    pub kind: FeedEventKind,
+    /// When the change was recorded.
+    pub occurred_at: DateTime<Utc>,
+    /// The bill's status after the change.
+    pub status: String,
+    /// The name of the new sponsor, a summary of the vote and its outcome, or a summary of the
+    /// hearing, for events of those kinds; otherwise empty.
+    pub detail: String,
+    #[graphql(skip)]
+    pub bill: Id,
+    #[graphql(skip)]
+    pub sponsor: Option<Id>,
+    #[graphql(skip)]
+    pub vote: Option<i32>,
+    #[graphql(skip)]
+    pub hearing: Option<i32>,
+}
+
+#[ComplexObject]
+impl FeedEvent {
+    /// The bill which changed.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        Ok(db::bill_by_id(ctx.data()?, self.bill).await?)
+    }
+
+    /// The new sponsor, if `kind` is `NEW_SPONSOR`.
+    async fn sponsor(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::Legislator>> {
+        match self.sponsor {
+            Some(id) => Ok(db::legislator_by_id(ctx.data()?, id).await?),
+            None => Ok(None),
+        }
+    }
+
+    /// The vote, if `kind` is `NEW_VOTE`.
+    async fn vote(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Vote>> {
+        match self.vote {
+            Some(id) => Ok(votes::vote(ctx.data()?, id).await?),
+            None => Ok(None),
+        }
+    }
+
+    /// The hearing, if `kind` is `HEARING_SCHEDULED`.
+    async fn hearing(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Hearing>> {
+        match self.hearing {
+            Some(id) => Ok(hearings::hearing(ctx.data()?, id).await?),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Queries for feeds.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FeedQuery;
+
+#[Object]
+impl FeedQuery {
+    /// The signed-in user's Home feed: changes to the entities they follow, newest first.
+    async fn feed(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<usize, FeedEvent>> {
+        let user = Viewer::of(ctx).require()?;
+        let before = after
+            .map(|cursor| usize::decode_cursor(&cursor))
+            .transpose()?;
+        let limit = first.map(i64::from).unwrap_or(MAX_PAGE_SIZE);
+        Ok(feed(ctx.data()?, user, before, limit).await?)
+    }
+}
+
+/// Create the tables for feed events.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS feed_events (
+            id SERIAL PRIMARY KEY,
+            kind TEXT NOT NULL,
+            bill INTEGER NOT NULL,
+            status TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            -- Identifies a change, so that it is only recorded once even if it is seen in several
+            -- pulls from Legiscan.
+            dedup_key TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS feed_event_subjects (
+            event INTEGER NOT NULL REFERENCES feed_events (id) ON DELETE CASCADE,
+            kind TEXT NOT NULL,
+            id INTEGER NOT NULL,
+            PRIMARY KEY (kind, id, event)
+        );
+        -- Feeds and notifications look up the subjects of each event they consider.
+        CREATE INDEX IF NOT EXISTS feed_event_subjects_event ON feed_event_subjects (event);
+        -- The detail of an event, and the sponsor, vote or hearing it is about.
+        ALTER TABLE feed_events
+            ADD COLUMN IF NOT EXISTS detail TEXT NOT NULL DEFAULT '',
+            ADD COLUMN IF NOT EXISTS sponsor INTEGER,
+            ADD COLUMN IF NOT EXISTS vote INTEGER,
+            ADD COLUMN IF NOT EXISTS hearing INTEGER;",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Record that `bill` was introduced.
+///
+/// The bill must already be in the [catalog](crate::catalog), which is used to find its sponsors
+/// and issues.
+pub async fn record_new_bill(sql: &SqlClient, bill: &schema::Bill) -> Result<(), Error> {
+    record(
+        sql,
+        FeedEventKind::NewBill,
+        bill,
+        &bill.status,
+        Details::default(),
+        format!("new_bill:{:?}", bill.id),
+    )
+    .await
+}
+
+/// Record that the status of `bill` changed to `status` on `date`.
+///
+/// `bill` is the bill as stored in the database, which may not yet reflect the new status.
+pub async fn record_status_change(
+    sql: &SqlClient,
+    bill: &schema::Bill,
+    status: &str,
+    date: &str,
+) -> Result<(), Error> {
+    record(
+        sql,
+        FeedEventKind::StatusChanged,
+        bill,
+        status,
+        Details::default(),
+        format!("status_changed:{:?}:{status}:{date}", bill.id),
+    )
+    .await
+}
+
+/// Record that `sponsor` started sponsoring `bill`, an existing bill.
+///
+/// The sponsor must already be in the [catalog](crate::catalog) as a sponsor of the bill.
+pub async fn record_new_sponsor(
+    sql: &SqlClient,
+    bill: &schema::Bill,
+    sponsor: &schema::Legislator,
+) -> Result<(), Error> {
+    let name = format!("{} {}", sponsor.first_name, sponsor.last_name);
+    record(
+        sql,
+        FeedEventKind::NewSponsor,
+        bill,
+        &bill.status,
+        Details {
+            detail: name,
+            sponsor: Some(sponsor.id),
+            ..Default::default()
+        },
+        format!("new_sponsor:{:?}:{:?}", bill.id, sponsor.id),
+    )
+    .await
+}
+
+/// Record that `vote` was held on `bill`.
+pub async fn record_vote(sql: &SqlClient, bill: &schema::Bill, vote: &Vote) -> Result<(), Error> {
+    record(
+        sql,
+        FeedEventKind::NewVote,
+        bill,
+        &bill.status,
+        Details {
+            detail: vote.summary(),
+            vote: Some(vote.id),
+            ..Default::default()
+        },
+        format!("new_vote:{}", vote.id),
+    )
+    .await
+}
+
+/// Record that `hearing` was scheduled on `bill`.
+pub async fn record_hearing(
+    sql: &SqlClient,
+    bill: &schema::Bill,
+    hearing: &Hearing,
+) -> Result<(), Error> {
+    record(
+        sql,
+        FeedEventKind::HearingScheduled,
+        bill,
+        &bill.status,
+        Details {
+            detail: hearing.summary(),
+            hearing: Some(hearing.id),
+            ..Default::default()
+        },
+        format!("hearing_scheduled:{}", hearing.id),
+    )
+    .await
+}
+
+/// What an event is about, besides the bill.
+#[derive(Default)]
+struct Details {
+    detail: String,
+    sponsor: Option<Id>,
+    vote: Option<i32>,
+    hearing: Option<i32>,
+}
+
+async fn record(
+    sql: &SqlClient,
+    kind: FeedEventKind,
+    bill: &schema::Bill,
+    status: &str,
+    details: Details,
+    dedup_key: String,
+) -> Result<(), Error> {
+    let Some(row) = sql
+        .query_opt(
+            "INSERT INTO feed_events (kind, bill, status, detail, sponsor, vote, hearing, dedup_key)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (dedup_key) DO NOTHING
+                RETURNING id",
+            &[
+                &kind.to_string(),
+                &bill.id,
+                &status,
+                &details.detail,
+                &details.sponsor,
+                &details.vote,
+                &details.hearing,
+                &dedup_key,
+            ],
+        )
+        .await?
+    else {
+        // This change has already been recorded.
+        return Ok(());
+    };
+    let event: i32 = row.try_get("id")?;
+    sql.execute(
+        "INSERT INTO feed_event_subjects (event, kind, id)
+            SELECT $1::INTEGER, $3::TEXT, $2::INTEGER
+            UNION SELECT $1, $5::TEXT, $4::INTEGER
+            UNION SELECT $1, $6::TEXT, legislator FROM catalog_sponsors WHERE bill = $2
+            UNION SELECT $1, $7::TEXT, a.ancestor FROM catalog_bill_issues bi
+                JOIN catalog_issue_ancestors a ON a.issue = bi.issue
+                WHERE bi.bill = $2",
+        &[
+            &event,
+            &bill.id,
+            &FollowKind::Bill.to_string(),
+            &bill.state.id,
+            &FollowKind::State.to_string(),
+            &FollowKind::Legislator.to_string(),
+            &FollowKind::Issue.to_string(),
+        ],
+    )
+    .await?;
+    live::notify_feed_event(sql, event).await
+}
+
+/// Load a page of `user`'s feed, newest first.
+///
+/// If `before` is given, only events older than the event with that ID are returned.
+pub async fn feed(
+    sql: &SqlClient,
+    user: i32,
+    before: Option<usize>,
+    limit: i64,
+) -> Result<Connection<usize, FeedEvent>, Error> {
+    let limit = limit.clamp(0, MAX_PAGE_SIZE);
+    let before = before.map(|id| id as i32);
+    // Fetch one more event than requested, to find out if there is a next page.
+    let rows = sql
+        .query(
+            "SELECT e.* FROM feed_events e
+                WHERE ($2::INTEGER IS NULL OR e.id < $2) AND EXISTS (
+                    SELECT 1 FROM feed_event_subjects s
+                    JOIN follows f ON f.kind = s.kind AND f.id = s.id
+                    WHERE s.event = e.id AND f.user_id = $1
+                )
+                ORDER BY e.id DESC
+                LIMIT $3",
+            &[&user, &before, &(limit + 1)],
+        )
+        .await?;
+    let has_next = rows.len() as i64 > limit;
+    let mut connection = Connection::new(before.is_some(), has_next);
+    for row in rows.iter().take(limit as usize) {
+        let event = event_from_row(row)?;
+        connection.edges.push(Edge::new(event.id as usize, event));
+    }
+    Ok(connection)
+}
+
+/// Load the event `event`, if it is in `user`'s feed.
+pub async fn event_for(sql: &SqlClient, user: i32, event: i32) -> Result<Option<FeedEvent>, Error> {
+    sql.query_opt(
+        "SELECT e.* FROM feed_events e
+            WHERE e.id = $2 AND EXISTS (
+                SELECT 1 FROM feed_event_subjects s
+                JOIN follows f ON f.kind = s.kind AND f.id = s.id
+                WHERE s.event = e.id AND f.user_id = $1
+            )",
+        &[&user, &event],
+    )
+    .await?
+    .map(|row| event_from_row(&row))
+    .transpose()
+}
+
+/// Load the event `event`.
+pub async fn event(sql: &SqlClient, event: i32) -> Result<Option<FeedEvent>, Error> {
+    sql.query_opt("SELECT * FROM feed_events WHERE id = $1", &[&event])
+        .await?
+        .map(|row| event_from_row(&row))
+        .transpose()
+}
+
+fn event_from_row(row: &tokio_postgres::Row) -> Result<FeedEvent, Error> {
+    Ok(FeedEvent {
+        id: row.try_get("id")?,
+        kind: row.try_get::<_, String>("kind")?.parse()?,
+        bill: row.try_get("bill")?,
+        status: row.try_get("status")?,
+        detail: row.try_get("detail")?,
+        sponsor: row.try_get("sponsor")?,
+        vote: row.try_get("vote")?,
+        hearing: row.try_get("hearing")?,
+        occurred_at: row.try_get("created_at")?,
+    })
+}
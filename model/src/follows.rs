@@ -0,0 +1,289 @@
+//! Users following bills, legislators, issues and states.
+//!
+//! Following an entity means a user is interested in news about it. Follows are kept in a
+//! supplementary table, `follows`, alongside the [accounts](crate::accounts) of the users who own
+//! them.
+
+use crate::{
+    accounts::Viewer,
+    db::{self, SqlClient},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{ComplexObject, Context, Enum, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use relational_graphql::graphql::type_system::Id;
+use strum::{Display, EnumIter, EnumString};
+
+/// The kinds of entities which can be followed.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumIter, EnumString, Enum)]
+#[strum(serialize_all = "lowercase")]
+pub enum FollowKind {
+    Bill,
+    Legislator,
+    Issue,
+    State,
+}
+
+/// An entity followed by a user.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Followed {
+    /// The kind of entity being followed.
+    pub kind: FollowKind,
+    /// When the user started following the entity.
+    pub followed_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub id: Id,
+}
+
+#[ComplexObject]
+impl Followed {
+    /// The followed bill, if `kind` is `BILL`.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        self.entity().bill(ctx).await
+    }
+
+    /// The followed legislator, if `kind` is `LEGISLATOR`.
+    async fn legislator(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::Legislator>> {
+        self.entity().legislator(ctx).await
+    }
+
+    /// The followed issue, if `kind` is `ISSUE`.
+    async fn issue(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Issue>> {
+        self.entity().issue(ctx).await
+    }
+
+    /// The followed state, if `kind` is `STATE`.
+    async fn state(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::State>> {
+        self.entity().state(ctx).await
+    }
+}
+
+impl Followed {
+    fn entity(&self) -> Entity {
+        Entity {
+            kind: self.kind,
+            id: self.id,
+        }
+    }
+}
+
+/// A reference to a bill, legislator, issue or state.
+///
+/// This resolves the reference for the GraphQL types which point to an entity of any kind, like
+/// [`Followed`] and search results.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Entity {
+    pub(crate) kind: FollowKind,
+    pub(crate) id: Id,
+}
+
+impl Entity {
+    pub(crate) async fn bill(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::Bill>> {
+        if self.kind != FollowKind::Bill {
+            return Ok(None);
+        }
+        Ok(db::bill_by_id(ctx.data()?, self.id).await?)
+    }
+
+    pub(crate) async fn legislator(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::Legislator>> {
+        if self.kind != FollowKind::Legislator {
+            return Ok(None);
+        }
+        Ok(db::legislator_by_id(ctx.data()?, self.id).await?)
+    }
+
+    pub(crate) async fn issue(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::Issue>> {
+        if self.kind != FollowKind::Issue {
+            return Ok(None);
+        }
+        Ok(db::issue_by_id(ctx.data()?, self.id).await?)
+    }
+
+    pub(crate) async fn state(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::State>> {
+        if self.kind != FollowKind::State {
+            return Ok(None);
+        }
+        Ok(db::state_by_id(ctx.data()?, self.id).await?)
+    }
+}
+
+/// Queries about follows.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FollowQuery;
+
+#[Object]
+impl FollowQuery {
+    /// Whether the signed-in user follows an entity.
+    ///
+    /// This is always `false` if the request is not authenticated.
+    async fn is_following(
+        &self,
+        ctx: &Context<'_>,
+        kind: FollowKind,
+        id: Id,
+    ) -> async_graphql::Result<bool> {
+        match Viewer::of(ctx).user {
+            Some(user) => Ok(is_following(ctx.data()?, user, kind, id).await?),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Mutations for following and unfollowing.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FollowMutation;
+
+#[Object]
+impl FollowMutation {
+    /// Follow an entity as the signed-in user.
+    ///
+    /// Returns whether the user was not already following the entity.
+    async fn follow(
+        &self,
+        ctx: &Context<'_>,
+        kind: FollowKind,
+        id: Id,
+    ) -> async_graphql::Result<bool> {
+        let user = Viewer::of(ctx).require()?;
+        if !exists(ctx.data()?, kind, id).await? {
+            return Err(format!("no such {kind} {id:?}").into());
+        }
+        Ok(follow(ctx.data()?, user, kind, id).await?)
+    }
+
+    /// Stop following an entity as the signed-in user.
+    ///
+    /// Returns whether the user was following the entity.
+    async fn unfollow(
+        &self,
+        ctx: &Context<'_>,
+        kind: FollowKind,
+        id: Id,
+    ) -> async_graphql::Result<bool> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(unfollow(ctx.data()?, user, kind, id).await?)
+    }
+}
+
+/// Create the table of follows.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS follows (
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            kind TEXT NOT NULL,
+            id INTEGER NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            PRIMARY KEY (user_id, kind, id)
+        );
+        CREATE INDEX IF NOT EXISTS follows_entity ON follows (kind, id);",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Make `user` follow an entity.
+///
+/// Returns whether the user was not already following the entity.
+pub async fn follow(sql: &SqlClient, user: i32, kind: FollowKind, id: Id) -> Result<bool, Error> {
+    let inserted = sql
+        .execute(
+            "INSERT INTO follows (user_id, kind, id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+            &[&user, &kind.to_string(), &id],
+        )
+        .await?;
+    Ok(inserted > 0)
+}
+
+/// Make `user` stop following an entity.
+///
+/// Returns whether the user was following the entity.
+pub async fn unfollow(sql: &SqlClient, user: i32, kind: FollowKind, id: Id) -> Result<bool, Error> {
+    let deleted = sql
+        .execute(
+            "DELETE FROM follows WHERE user_id = $1 AND kind = $2 AND id = $3",
+            &[&user, &kind.to_string(), &id],
+        )
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// Whether `user` follows an entity.
+pub async fn is_following(
+    sql: &SqlClient,
+    user: i32,
+    kind: FollowKind,
+    id: Id,
+) -> Result<bool, Error> {
+    let row = sql
+        .query_opt(
+            "SELECT 1 FROM follows WHERE user_id = $1 AND kind = $2 AND id = $3",
+            &[&user, &kind.to_string(), &id],
+        )
+        .await?;
+    Ok(row.is_some())
+}
+
+/// The number of users following an entity.
+pub async fn follower_count(sql: &SqlClient, kind: FollowKind, id: Id) -> Result<i64, Error> {
+    let row = sql
+        .query_one(
+            "SELECT count(*) FROM follows WHERE kind = $1 AND id = $2",
+            &[&kind.to_string(), &id],
+        )
+        .await?;
+    Ok(row.try_get(0)?)
+}
+
+/// The entities followed by `user`, most recently followed first.
+///
+/// If `kind` is given, only entities of that kind are returned.
+pub async fn following(
+    sql: &SqlClient,
+    user: i32,
+    kind: Option<FollowKind>,
+) -> Result<Vec<Followed>, Error> {
+    let rows = sql
+        .query(
+            "SELECT kind, id, created_at FROM follows
+                WHERE user_id = $1 AND ($2::TEXT IS NULL OR kind = $2)
+                ORDER BY created_at DESC, kind, id",
+            &[&user, &kind.map(|kind| kind.to_string())],
+        )
+        .await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(Followed {
+                kind: row.try_get::<_, String>("kind")?.parse()?,
+                id: row.try_get("id")?,
+                followed_at: row.try_get("created_at")?,
+            })
+        })
+        .collect()
+}
+
+/// Check whether an entity exists.
+pub(crate) async fn exists(conn: &db::Connection, kind: FollowKind, id: Id) -> Result<bool, Error> {
+    Ok(match kind {
+        FollowKind::Bill => db::bill_by_id(conn, id).await?.is_some(),
+        FollowKind::Legislator => db::legislator_by_id(conn, id).await?.is_some(),
+        FollowKind::Issue => db::issue_by_id(conn, id).await?.is_some(),
+        FollowKind::State => db::state_by_id(conn, id).await?.is_some(),
+    })
+}
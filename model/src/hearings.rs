@@ -0,0 +1,159 @@
+//! Hearings and other meetings scheduled on bills.
+//!
+//! Legiscan lists the committee meetings on each bill in its calendar: hearings, executive sessions
+//! and the like, with their date, time and place. [`db::update`] records the meetings on each new
+//! or changed bill in a supplementary table, `hearings`, and records each meeting it hasn't seen
+//! before in [feeds](crate::feed). A meeting which is moved to another time or place is recorded
+//! again, so that followers hear about the change.
+
+use crate::{
+    db::{self, SqlClient},
+    legiscan, schema,
+};
+use anyhow::Error;
+use async_graphql::{ComplexObject, Context, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
+use relational_graphql::graphql::type_system::Id;
+
+/// A meeting scheduled on a bill, like a committee hearing.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Hearing {
+    pub id: i32,
+    /// What kind of meeting this is, like "Hearing" or "Executive Session".
+    pub kind: String,
+    /// The date of the meeting, in the form YYYY-MM-DD.
+    pub date: String,
+    /// The time of the meeting, in the form HH:MM, or `null` if it hasn't been set.
+    pub time: Option<String>,
+    /// Where the meeting will be held, or `null` if it hasn't been set.
+    pub location: Option<String>,
+    /// What the meeting is, like "House Education Hearing".
+    pub description: String,
+    /// When the meeting was first pulled from Legiscan.
+    pub recorded_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub bill: Id,
+}
+
+#[ComplexObject]
+impl Hearing {
+    /// The bill the meeting is about.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        Ok(db::bill_by_id(ctx.data()?, self.bill).await?)
+    }
+}
+
+impl Hearing {
+    /// A one-line summary of the meeting, like "House Education Hearing scheduled for 2023-02-10
+    /// at 13:30 in Room 0112".
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} scheduled for {}", self.description, self.date);
+        if let Some(time) = &self.time {
+            summary += &format!(" at {time}");
+        }
+        if let Some(location) = &self.location {
+            summary += &format!(" in {location}");
+        }
+        summary
+    }
+}
+
+/// Queries for hearings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HearingQuery;
+
+#[Object]
+impl HearingQuery {
+    /// The meetings scheduled on a bill, soonest first.
+    async fn hearings(&self, ctx: &Context<'_>, bill: Id) -> async_graphql::Result<Vec<Hearing>> {
+        Ok(hearings(ctx.data()?, bill).await?)
+    }
+}
+
+/// Create the table of hearings.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS hearings (
+            id SERIAL PRIMARY KEY,
+            bill INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            date TEXT NOT NULL,
+            -- Empty if not set, so that unscheduled meetings are still deduplicated.
+            time TEXT NOT NULL,
+            location TEXT NOT NULL,
+            description TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            UNIQUE (bill, kind, date, time, location)
+        );",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Record the meetings scheduled on `bill`.
+///
+/// Returns the meetings which had not been recorded before.
+pub async fn record(
+    sql: &SqlClient,
+    bill: &schema::Bill,
+    calendar: &[legiscan::Hearing],
+) -> Result<Vec<Hearing>, Error> {
+    let recorded = try_join_all(calendar.iter().map(|hearing| async move {
+        sql.query_opt(
+            "INSERT INTO hearings (bill, kind, date, time, location, description)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (bill, kind, date, time, location) DO NOTHING
+                RETURNING *",
+            &[
+                &bill.id,
+                &hearing.kind,
+                &hearing.date,
+                &hearing.time,
+                &hearing.location,
+                &hearing.description,
+            ],
+        )
+        .await?
+        .map(|row| hearing_from_row(&row))
+        .transpose()
+    }))
+    .await?;
+    Ok(recorded.into_iter().flatten().collect())
+}
+
+/// Load the meetings scheduled on `bill`, soonest first.
+pub async fn hearings(sql: &SqlClient, bill: Id) -> Result<Vec<Hearing>, Error> {
+    sql.query(
+        "SELECT * FROM hearings WHERE bill = $1 ORDER BY date, time, id",
+        &[&bill],
+    )
+    .await?
+    .iter()
+    .map(hearing_from_row)
+    .collect()
+}
+
+/// Load the hearing `id`.
+pub async fn hearing(sql: &SqlClient, id: i32) -> Result<Option<Hearing>, Error> {
+    sql.query_opt("SELECT * FROM hearings WHERE id = $1", &[&id])
+        .await?
+        .map(|row| hearing_from_row(&row))
+        .transpose()
+}
+
+fn hearing_from_row(row: &tokio_postgres::Row) -> Result<Hearing, Error> {
+    let time: String = row.try_get("time")?;
+    let location: String = row.try_get("location")?;
+    Ok(Hearing {
+        id: row.try_get("id")?,
+        bill: row.try_get("bill")?,
+        kind: row.try_get("kind")?,
+        date: row.try_get("date")?,
+        time: Some(time).filter(|time| !time.is_empty()),
+        location: Some(location).filter(|location| !location.is_empty()),
+        description: row.try_get("description")?,
+        recorded_at: row.try_get("created_at")?,
+    })
+}
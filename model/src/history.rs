@@ -0,0 +1,315 @@
+//! Per-user browsing history.
+//!
+//! Clients record the bills, legislators, issues and states a signed-in user views with the
+//! `recordView` mutation. Views are kept in a supplementary table, `views`, with one row for each
+//! entity a user has viewed, holding the time of the most recent view. Users can clear their
+//! history, and can turn off history collection altogether, in which case views are not recorded.
+
+use crate::{
+    accounts::Viewer,
+    db::{self, SqlClient},
+    follows::{self, Entity, FollowKind},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{
+    connection::{Connection, CursorType, Edge},
+    ComplexObject, Context, Object, SimpleObject,
+};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use relational_graphql::graphql::type_system::Id;
+
+/// The maximum number of entries returned in a single page.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// An entity viewed by a user.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Viewed {
+    /// The kind of entity viewed.
+    pub kind: FollowKind,
+    /// When the user last viewed the entity.
+    pub viewed_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub id: Id,
+}
+
+#[ComplexObject]
+impl Viewed {
+    /// The viewed bill, if `kind` is `BILL`.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        self.entity().bill(ctx).await
+    }
+
+    /// The viewed legislator, if `kind` is `LEGISLATOR`.
+    async fn legislator(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::Legislator>> {
+        self.entity().legislator(ctx).await
+    }
+
+    /// The viewed issue, if `kind` is `ISSUE`.
+    async fn issue(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Issue>> {
+        self.entity().issue(ctx).await
+    }
+
+    /// The viewed state, if `kind` is `STATE`.
+    async fn state(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::State>> {
+        self.entity().state(ctx).await
+    }
+}
+
+impl Viewed {
+    fn entity(&self) -> Entity {
+        Entity {
+            kind: self.kind,
+            id: self.id,
+        }
+    }
+}
+
+/// The position of an entry in a user's history.
+///
+/// History is ordered by the time of the most recent view, so a page continues from the view time,
+/// kind and ID of the last entry on the previous page. Unlike an offset, this stays correct when
+/// entities are viewed again, or history is cleared, between pages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryCursor {
+    viewed_at: DateTime<Utc>,
+    kind: FollowKind,
+    id: i32,
+}
+
+impl CursorType for HistoryCursor {
+    type Error = Error;
+
+    fn decode_cursor(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::msg(format!("invalid cursor {s}"));
+        let mut parts = s.splitn(3, ':');
+        let (Some(viewed_at), Some(kind), Some(id)) = (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(invalid());
+        };
+        let viewed_at =
+            NaiveDateTime::from_timestamp_micros(viewed_at.parse()?).ok_or_else(invalid)?;
+        Ok(Self {
+            viewed_at: Utc.from_utc_datetime(&viewed_at),
+            kind: kind.parse()?,
+            id: id.parse()?,
+        })
+    }
+
+    fn encode_cursor(&self) -> String {
+        format!(
+            "{}:{}:{}",
+            self.viewed_at.timestamp_micros(),
+            self.kind,
+            self.id
+        )
+    }
+}
+
+/// Queries for browsing history.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HistoryQuery;
+
+#[Object]
+impl HistoryQuery {
+    /// The entities the signed-in user has viewed, most recently viewed first.
+    ///
+    /// If `kind` is given, only entities of that kind are returned.
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        kind: Option<FollowKind>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<HistoryCursor, Viewed>> {
+        let user = Viewer::of(ctx).require()?;
+        let after = after
+            .map(|cursor| HistoryCursor::decode_cursor(&cursor))
+            .transpose()?;
+        let limit = first.map(i64::from).unwrap_or(MAX_PAGE_SIZE);
+        Ok(history(ctx.data()?, user, kind, after, limit).await?)
+    }
+}
+
+/// Mutations for recording and managing browsing history.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct HistoryMutation;
+
+#[Object]
+impl HistoryMutation {
+    /// Record that the signed-in user viewed an entity.
+    ///
+    /// Returns whether the view was recorded, which it is not if the user has turned off history.
+    async fn record_view(
+        &self,
+        ctx: &Context<'_>,
+        kind: FollowKind,
+        id: Id,
+    ) -> async_graphql::Result<bool> {
+        let user = Viewer::of(ctx).require()?;
+        if !follows::exists(ctx.data()?, kind, id).await? {
+            return Err(format!("no such {kind} {id:?}").into());
+        }
+        Ok(record_view(ctx.data()?, user, kind, id).await?)
+    }
+
+    /// Delete the signed-in user's browsing history.
+    ///
+    /// Returns the number of entries deleted.
+    async fn clear_history(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(clear_history(ctx.data()?, user).await?)
+    }
+
+    /// Turn collection of the signed-in user's browsing history on or off.
+    ///
+    /// Turning history off does not delete the existing history; use `clearHistory` for that.
+    async fn set_history_enabled(
+        &self,
+        ctx: &Context<'_>,
+        enabled: bool,
+    ) -> async_graphql::Result<bool> {
+        let user = Viewer::of(ctx).require()?;
+        set_history_enabled(ctx.data()?, user, enabled).await?;
+        Ok(enabled)
+    }
+}
+
+/// Create the table of views.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS views (
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            kind TEXT NOT NULL,
+            id INTEGER NOT NULL,
+            viewed_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            PRIMARY KEY (user_id, kind, id)
+        );
+        CREATE INDEX IF NOT EXISTS views_user_viewed_at ON views (user_id, viewed_at DESC);
+        CREATE INDEX IF NOT EXISTS views_entity ON views (kind, id);",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Record that `user` viewed an entity, unless they have turned off history.
+///
+/// Returns whether the view was recorded.
+pub async fn record_view(
+    sql: &SqlClient,
+    user: i32,
+    kind: FollowKind,
+    id: Id,
+) -> Result<bool, Error> {
+    let recorded = sql
+        .execute(
+            "INSERT INTO views (user_id, kind, id)
+                SELECT id, $2, $3 FROM users WHERE id = $1 AND history_enabled
+                ON CONFLICT (user_id, kind, id) DO UPDATE SET viewed_at = now()",
+            &[&user, &kind.to_string(), &id],
+        )
+        .await?;
+    Ok(recorded > 0)
+}
+
+/// Delete `user`'s browsing history.
+///
+/// Returns the number of entries deleted.
+pub async fn clear_history(sql: &SqlClient, user: i32) -> Result<i64, Error> {
+    let deleted = sql
+        .execute("DELETE FROM views WHERE user_id = $1", &[&user])
+        .await?;
+    Ok(deleted as i64)
+}
+
+/// Turn collection of `user`'s browsing history on or off.
+pub async fn set_history_enabled(sql: &SqlClient, user: i32, enabled: bool) -> Result<(), Error> {
+    sql.execute(
+        "UPDATE users SET history_enabled = $2 WHERE id = $1",
+        &[&user, &enabled],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Load a page of `user`'s browsing history, most recently viewed first.
+///
+/// If `after` is given, only entries after that position are returned.
+pub async fn history(
+    sql: &SqlClient,
+    user: i32,
+    kind: Option<FollowKind>,
+    after: Option<HistoryCursor>,
+    limit: i64,
+) -> Result<Connection<HistoryCursor, Viewed>, Error> {
+    let limit = limit.clamp(0, MAX_PAGE_SIZE);
+    let (after_viewed_at, after_kind, after_id) = match &after {
+        Some(cursor) => (
+            Some(cursor.viewed_at),
+            Some(cursor.kind.to_string()),
+            Some(cursor.id),
+        ),
+        None => (None, None, None),
+    };
+    // Fetch one more entry than requested, to find out if there is a next page.
+    let rows = sql
+        .query(
+            "SELECT kind, id, viewed_at FROM views
+                WHERE user_id = $1 AND ($2::TEXT IS NULL OR kind = $2) AND (
+                    $3::TIMESTAMPTZ IS NULL
+                    OR viewed_at < $3
+                    OR (viewed_at = $3 AND (kind, id) > ($4::TEXT, $5::INTEGER))
+                )
+                ORDER BY viewed_at DESC, kind, id
+                LIMIT $6",
+            &[
+                &user,
+                &kind.map(|kind| kind.to_string()),
+                &after_viewed_at,
+                &after_kind,
+                &after_id,
+                &(limit + 1),
+            ],
+        )
+        .await?;
+    let has_next = rows.len() as i64 > limit;
+    let mut connection = Connection::new(after.is_some(), has_next);
+    for row in rows.iter().take(limit as usize) {
+        let cursor = HistoryCursor {
+            viewed_at: row.try_get("viewed_at")?,
+            kind: row.try_get::<_, String>("kind")?.parse()?,
+            id: row.try_get("id")?,
+        };
+        let viewed = Viewed {
+            kind: cursor.kind,
+            id: row.try_get("id")?,
+            viewed_at: cursor.viewed_at,
+        };
+        connection.edges.push(Edge::new(cursor, viewed));
+    }
+    Ok(connection)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cursor() {
+        let cursor = HistoryCursor {
+            viewed_at: Utc.timestamp_opt(1_700_000_000, 123_456_000).unwrap(),
+            kind: FollowKind::Legislator,
+            id: 42,
+        };
+        let encoded = cursor.encode_cursor();
+        assert_eq!(encoded, "1700000000123456:legislator:42");
+        assert_eq!(HistoryCursor::decode_cursor(&encoded).unwrap(), cursor);
+
+        assert!(HistoryCursor::decode_cursor("1700000000123456:legislator").is_err());
+        assert!(HistoryCursor::decode_cursor("yesterday:bill:1").is_err());
+    }
+}
@@ -33,7 +33,12 @@ pub trait Legiscan {
     async fn load_dataset(&self, dataset: &Self::DatasetMetadata) -> Result<Self::Dataset, Error>;
 }
 
-/// A US state.
+/// A US state, or another jurisdiction with its own legislature.
+///
+/// In addition to the 50 states, Legiscan tracks Washington, D.C. and the federal US Congress.
+///
+/// The variants are in the order of their Legiscan state IDs, which are alphabetical by the full
+/// name of the state (so West Virginia comes before Wisconsin), followed by D.C. and Congress.
 #[derive(
     Clone,
     Copy,
@@ -97,10 +102,11 @@ pub enum State {
     VT,
     VA,
     WA,
-    WI,
     WV,
+    WI,
     WY,
     DC,
+    US,
 }
 
 impl TryFrom<u8> for State {
@@ -125,63 +131,593 @@ impl State {
 
     /// The full name of this state.
     pub fn name(&self) -> &'static str {
+        self.metadata().name
+    }
+
+    /// The name of a chamber in this state's legislature.
+    ///
+    /// In unicameral legislatures, both chambers refer to the only chamber.
+    pub fn chamber_name(&self, chamber: Chamber) -> &'static str {
+        let metadata = self.metadata();
+        match chamber {
+            Chamber::Upper => metadata.upper_chamber,
+            Chamber::Lower => metadata.lower_chamber.unwrap_or(metadata.upper_chamber),
+        }
+    }
+
+    /// Static information about this state and its legislature.
+    pub fn metadata(&self) -> StateMetadata {
+        use SessionCalendar::*;
         use State::*;
         match self {
-            AL => "Alabama",
-            AK => "Alaska",
-            AZ => "Arizona",
-            AR => "Arkansas",
-            CA => "California",
-            CO => "Colorado",
-            CT => "Connecticut",
-            DE => "Delaware",
-            FL => "Florida",
-            GA => "Georgia",
-            HI => "Hawaii",
-            ID => "Idaho",
-            IL => "Illinois",
-            IN => "Indiana",
-            IA => "Iowa",
-            KS => "Kansas",
-            KY => "Kentucky",
-            LA => "Louisiana",
-            ME => "Maine",
-            MD => "Maryland",
-            MA => "Massachusetts",
-            MI => "Michigan",
-            MN => "Minnesota",
-            MS => "Mississippi",
-            MO => "Missouri",
-            MT => "Montana",
-            NE => "Nebraska",
-            NV => "Nevada",
-            NH => "New Hampshire",
-            NJ => "New Jersey",
-            NM => "New Mexico",
-            NY => "New York",
-            NC => "North Carolina",
-            ND => "North Dakota",
-            OH => "Ohio",
-            OK => "Oklahoma",
-            OR => "Oregon",
-            PA => "Pennsylvania",
-            RI => "Rhode Island",
-            SC => "South Carolina",
-            SD => "South Dakota",
-            TN => "Tennessee",
-            TX => "Texas",
-            UT => "Utah",
-            VT => "Vermont",
-            VA => "Virginia",
-            WA => "Washington",
-            WI => "West Virginia",
-            WV => "Wisconsin",
-            WY => "Wyoming",
-            DC => "Washington, D.C.",
+            AL => StateMetadata {
+                name: "Alabama",
+                fips_code: "01",
+                capital: "Montgomery",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://alison.legislature.state.al.us",
+            },
+            AK => StateMetadata {
+                name: "Alaska",
+                fips_code: "02",
+                capital: "Juneau",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.akleg.gov",
+            },
+            AZ => StateMetadata {
+                name: "Arizona",
+                fips_code: "04",
+                capital: "Phoenix",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.azleg.gov",
+            },
+            AR => StateMetadata {
+                name: "Arkansas",
+                fips_code: "05",
+                capital: "Little Rock",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.arkleg.state.ar.us",
+            },
+            CA => StateMetadata {
+                name: "California",
+                fips_code: "06",
+                capital: "Sacramento",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("Assembly"),
+                session_calendar: Annual,
+                website: "https://www.legislature.ca.gov",
+            },
+            CO => StateMetadata {
+                name: "Colorado",
+                fips_code: "08",
+                capital: "Denver",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://leg.colorado.gov",
+            },
+            CT => StateMetadata {
+                name: "Connecticut",
+                fips_code: "09",
+                capital: "Hartford",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.cga.ct.gov",
+            },
+            DE => StateMetadata {
+                name: "Delaware",
+                fips_code: "10",
+                capital: "Dover",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://legis.delaware.gov",
+            },
+            FL => StateMetadata {
+                name: "Florida",
+                fips_code: "12",
+                capital: "Tallahassee",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.leg.state.fl.us",
+            },
+            GA => StateMetadata {
+                name: "Georgia",
+                fips_code: "13",
+                capital: "Atlanta",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.legis.ga.gov",
+            },
+            HI => StateMetadata {
+                name: "Hawaii",
+                fips_code: "15",
+                capital: "Honolulu",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.capitol.hawaii.gov",
+            },
+            ID => StateMetadata {
+                name: "Idaho",
+                fips_code: "16",
+                capital: "Boise",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://legislature.idaho.gov",
+            },
+            IL => StateMetadata {
+                name: "Illinois",
+                fips_code: "17",
+                capital: "Springfield",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.ilga.gov",
+            },
+            IN => StateMetadata {
+                name: "Indiana",
+                fips_code: "18",
+                capital: "Indianapolis",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://iga.in.gov",
+            },
+            IA => StateMetadata {
+                name: "Iowa",
+                fips_code: "19",
+                capital: "Des Moines",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.legis.iowa.gov",
+            },
+            KS => StateMetadata {
+                name: "Kansas",
+                fips_code: "20",
+                capital: "Topeka",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.kslegislature.org",
+            },
+            KY => StateMetadata {
+                name: "Kentucky",
+                fips_code: "21",
+                capital: "Frankfort",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://legislature.ky.gov",
+            },
+            LA => StateMetadata {
+                name: "Louisiana",
+                fips_code: "22",
+                capital: "Baton Rouge",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://legis.la.gov",
+            },
+            ME => StateMetadata {
+                name: "Maine",
+                fips_code: "23",
+                capital: "Augusta",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://legislature.maine.gov",
+            },
+            MD => StateMetadata {
+                name: "Maryland",
+                fips_code: "24",
+                capital: "Annapolis",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Delegates"),
+                session_calendar: Annual,
+                website: "https://mgaleg.maryland.gov",
+            },
+            MA => StateMetadata {
+                name: "Massachusetts",
+                fips_code: "25",
+                capital: "Boston",
+                legislature: "General Court",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://malegislature.gov",
+            },
+            MI => StateMetadata {
+                name: "Michigan",
+                fips_code: "26",
+                capital: "Lansing",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.legislature.mi.gov",
+            },
+            MN => StateMetadata {
+                name: "Minnesota",
+                fips_code: "27",
+                capital: "Saint Paul",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.leg.mn.gov",
+            },
+            MS => StateMetadata {
+                name: "Mississippi",
+                fips_code: "28",
+                capital: "Jackson",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.legislature.ms.gov",
+            },
+            MO => StateMetadata {
+                name: "Missouri",
+                fips_code: "29",
+                capital: "Jefferson City",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.moga.mo.gov",
+            },
+            MT => StateMetadata {
+                name: "Montana",
+                fips_code: "30",
+                capital: "Helena",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Biennial,
+                website: "https://leg.mt.gov",
+            },
+            NE => StateMetadata {
+                name: "Nebraska",
+                fips_code: "31",
+                capital: "Lincoln",
+                legislature: "Legislature",
+                upper_chamber: "Legislature",
+                lower_chamber: None,
+                session_calendar: Annual,
+                website: "https://nebraskalegislature.gov",
+            },
+            NV => StateMetadata {
+                name: "Nevada",
+                fips_code: "32",
+                capital: "Carson City",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("Assembly"),
+                session_calendar: Biennial,
+                website: "https://www.leg.state.nv.us",
+            },
+            NH => StateMetadata {
+                name: "New Hampshire",
+                fips_code: "33",
+                capital: "Concord",
+                legislature: "General Court",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.gencourt.state.nh.us",
+            },
+            NJ => StateMetadata {
+                name: "New Jersey",
+                fips_code: "34",
+                capital: "Trenton",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("General Assembly"),
+                session_calendar: Annual,
+                website: "https://www.njleg.state.nj.us",
+            },
+            NM => StateMetadata {
+                name: "New Mexico",
+                fips_code: "35",
+                capital: "Santa Fe",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.nmlegis.gov",
+            },
+            NY => StateMetadata {
+                name: "New York",
+                fips_code: "36",
+                capital: "Albany",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("Assembly"),
+                session_calendar: Annual,
+                website: "https://www.nysenate.gov",
+            },
+            NC => StateMetadata {
+                name: "North Carolina",
+                fips_code: "37",
+                capital: "Raleigh",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.ncleg.gov",
+            },
+            ND => StateMetadata {
+                name: "North Dakota",
+                fips_code: "38",
+                capital: "Bismarck",
+                legislature: "Legislative Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Biennial,
+                website: "https://www.ndlegis.gov",
+            },
+            OH => StateMetadata {
+                name: "Ohio",
+                fips_code: "39",
+                capital: "Columbus",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.legislature.ohio.gov",
+            },
+            OK => StateMetadata {
+                name: "Oklahoma",
+                fips_code: "40",
+                capital: "Oklahoma City",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.oklegislature.gov",
+            },
+            OR => StateMetadata {
+                name: "Oregon",
+                fips_code: "41",
+                capital: "Salem",
+                legislature: "Legislative Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.oregonlegislature.gov",
+            },
+            PA => StateMetadata {
+                name: "Pennsylvania",
+                fips_code: "42",
+                capital: "Harrisburg",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.palegis.us",
+            },
+            RI => StateMetadata {
+                name: "Rhode Island",
+                fips_code: "44",
+                capital: "Providence",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.rilegislature.gov",
+            },
+            SC => StateMetadata {
+                name: "South Carolina",
+                fips_code: "45",
+                capital: "Columbia",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.scstatehouse.gov",
+            },
+            SD => StateMetadata {
+                name: "South Dakota",
+                fips_code: "46",
+                capital: "Pierre",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://sdlegislature.gov",
+            },
+            TN => StateMetadata {
+                name: "Tennessee",
+                fips_code: "47",
+                capital: "Nashville",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.capitol.tn.gov",
+            },
+            TX => StateMetadata {
+                name: "Texas",
+                fips_code: "48",
+                capital: "Austin",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Biennial,
+                website: "https://capitol.texas.gov",
+            },
+            UT => StateMetadata {
+                name: "Utah",
+                fips_code: "49",
+                capital: "Salt Lake City",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://le.utah.gov",
+            },
+            VT => StateMetadata {
+                name: "Vermont",
+                fips_code: "50",
+                capital: "Montpelier",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://legislature.vermont.gov",
+            },
+            VA => StateMetadata {
+                name: "Virginia",
+                fips_code: "51",
+                capital: "Richmond",
+                legislature: "General Assembly",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Delegates"),
+                session_calendar: Annual,
+                website: "https://virginiageneralassembly.gov",
+            },
+            WA => StateMetadata {
+                name: "Washington",
+                fips_code: "53",
+                capital: "Olympia",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://leg.wa.gov",
+            },
+            WV => StateMetadata {
+                name: "West Virginia",
+                fips_code: "54",
+                capital: "Charleston",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Delegates"),
+                session_calendar: Annual,
+                website: "https://www.wvlegislature.gov",
+            },
+            WI => StateMetadata {
+                name: "Wisconsin",
+                fips_code: "55",
+                capital: "Madison",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("Assembly"),
+                session_calendar: Annual,
+                website: "https://legis.wisconsin.gov",
+            },
+            WY => StateMetadata {
+                name: "Wyoming",
+                fips_code: "56",
+                capital: "Cheyenne",
+                legislature: "Legislature",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.wyoleg.gov",
+            },
+            DC => StateMetadata {
+                name: "Washington, D.C.",
+                fips_code: "11",
+                capital: "Washington",
+                legislature: "Council of the District of Columbia",
+                upper_chamber: "Council",
+                lower_chamber: None,
+                session_calendar: Annual,
+                website: "https://dccouncil.gov",
+            },
+            US => StateMetadata {
+                name: "United States",
+                fips_code: "",
+                capital: "Washington",
+                legislature: "Congress",
+                upper_chamber: "Senate",
+                lower_chamber: Some("House of Representatives"),
+                session_calendar: Annual,
+                website: "https://www.congress.gov",
+            },
         }
     }
 }
 
+/// Static information about a state and its legislature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StateMetadata {
+    /// The full name of the state.
+    pub name: &'static str,
+    /// The 2-digit FIPS code identifying the state.
+    ///
+    /// This is empty for the federal jurisdiction, which has no FIPS code.
+    pub fips_code: &'static str,
+    /// The capital city of the state.
+    pub capital: &'static str,
+    /// The name of the state legislature, like "General Assembly".
+    pub legislature: &'static str,
+    /// The name of the upper chamber of the legislature.
+    ///
+    /// For unicameral legislatures, this is the name of the only chamber.
+    pub upper_chamber: &'static str,
+    /// The name of the lower chamber of the legislature, if it has one.
+    pub lower_chamber: Option<&'static str>,
+    /// How often the legislature meets in regular session.
+    pub session_calendar: SessionCalendar,
+    /// The official website of the legislature.
+    pub website: &'static str,
+}
+
+/// A chamber of a legislature.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Chamber {
+    /// The upper chamber, usually called the Senate.
+    Upper,
+    /// The lower chamber, usually called the House of Representatives or the Assembly.
+    Lower,
+}
+
+/// How often a legislature meets in regular session.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum SessionCalendar {
+    /// The legislature meets every year.
+    Annual,
+    /// The legislature meets every other year.
+    Biennial,
+}
+
 /// The possible statuses of a bill.
 #[derive(Clone, Copy, Debug, Display, EnumString)]
 pub enum Status {
@@ -194,38 +730,70 @@ pub enum Status {
     Failed,
 }
 
-/// A US political party.
-#[derive(
-    Clone, Copy, Debug, Display, PartialEq, Eq, PartialOrd, Ord, Hash, EnumString, EnumIter,
-)]
-pub enum Party {
-    Unknown,
-    Democrat,
-    Republican,
-    Independent,
-    Green,
-    Libertarian,
-    Nonpartisan,
+/// A political party, as identified by Legiscan.
+///
+/// Legiscan does not have a fixed set of parties: minor and state-specific parties (like the
+/// Vermont Progressive Party or the Working Families Party) are identified by whatever code and
+/// abbreviation Legiscan assigns them.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Party {
+    /// The unique identifier for this party in the Legiscan API.
+    pub id: String,
+    /// The short name for this party (like "D" for Democrat).
+    pub abbreviation: String,
 }
 
-impl Party {
-    /// The numeric ID of this party.
-    pub fn id(&self) -> u8 {
-        Self::iter().position(|party| party == *self).unwrap() as u8 + 1
-    }
+/// An action taken on a bill, like being introduced, referred to a committee, or passed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Action {
+    /// The date of the action, in the form YYYY-MM-DD.
+    pub date: String,
+    /// What happened, like "Introduced In House - Assigned to Education".
+    pub description: String,
+    /// The chamber in which the action was taken, or [`None`] for actions outside the legislature,
+    /// like the governor signing the bill.
+    pub chamber: Option<Chamber>,
+    /// Whether this is a major step in the bill's progress, like passing a chamber, rather than a
+    /// procedural one, like a committee referral.
+    pub major: bool,
+}
 
-    /// The one-letter abbreviation for this party.
-    pub fn abbreviation(&self) -> &'static str {
-        match self {
-            Self::Unknown => "?",
-            Self::Democrat => "D",
-            Self::Republican => "R",
-            Self::Independent => "I",
-            Self::Green => "G",
-            Self::Libertarian => "L",
-            Self::Nonpartisan => "n/a",
-        }
-    }
+/// A roll-call vote on a bill.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Vote {
+    /// The unique identifier for this roll call in the Legiscan API.
+    pub id: String,
+    /// The date of the vote, in the form YYYY-MM-DD.
+    pub date: String,
+    /// What was being voted on, like "Third Reading".
+    pub description: String,
+    /// The chamber in which the vote was held, or [`None`] for a joint session.
+    pub chamber: Option<Chamber>,
+    /// The number of votes in favor.
+    pub yea: u32,
+    /// The number of votes against.
+    pub nay: u32,
+    /// The number of legislators who were present but did not vote.
+    pub not_voting: u32,
+    /// The number of legislators who were absent.
+    pub absent: u32,
+    /// Whether the motion passed.
+    pub passed: bool,
+}
+
+/// A meeting scheduled on a bill, like a committee hearing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hearing {
+    /// What kind of meeting this is, like "Hearing" or "Executive Session".
+    pub kind: String,
+    /// The date of the meeting, in the form YYYY-MM-DD.
+    pub date: String,
+    /// The time of the meeting, in the form HH:MM, or empty if it hasn't been set.
+    pub time: String,
+    /// Where the meeting will be held, or empty if it hasn't been set.
+    pub location: String,
+    /// What the meeting is, like "House Education Hearing".
+    pub description: String,
 }
 
 /// A first, middle, and last name.
@@ -303,6 +871,12 @@ pub trait Bill: Send + 'static {
     /// The status of this bill.
     fn status(&self) -> Status;
 
+    /// The date on which this bill reached its current status, in the form YYYY-MM-DD.
+    fn status_date(&self) -> String;
+
+    /// The name of the legislative session in which this bill was introduced.
+    fn session(&self) -> String;
+
     /// The short name of this bill (usually a body abbreviation and a number).
     fn name(&self) -> String;
 
@@ -320,6 +894,15 @@ pub trait Bill: Send + 'static {
 
     /// The name of each issue this bill pertains to.
     fn issues(&self) -> Vec<String>;
+
+    /// The actions taken on this bill so far, oldest first.
+    fn history(&self) -> Vec<Action>;
+
+    /// The roll-call votes which have been held on this bill.
+    fn votes(&self) -> Vec<Vote>;
+
+    /// The meetings scheduled on this bill, like committee hearings.
+    fn calendar(&self) -> Vec<Hearing>;
 }
 
 /// Information about a person.
@@ -334,14 +917,89 @@ pub trait Person: Send + 'static {
     fn hash(&self) -> String;
 
     /// The state where this person is a legislator.
+    ///
+    /// For members of Congress, this is [`State::US`].
     fn state(&self) -> State;
 
+    /// The state this person comes from.
+    ///
+    /// For state legislators, this is the same as [`state`](Self::state). For members of Congress,
+    /// it is the state which elected them.
+    fn home_state(&self) -> State;
+
     /// The political party of which this person is a member.
     fn party(&self) -> Party;
 
     /// The person's full name.
     fn name(&self) -> Name;
 
+    /// The chamber of the legislature in which this person serves.
+    fn chamber(&self) -> Chamber;
+
     /// The name of the district this person represents.
+    ///
+    /// For members of Congress, the district name includes the home state of the member, as in
+    /// "HD-CA-12" for a representative (or "HD-WY-AL" for an at-large one) or "SD-CA" for a
+    /// senator.
     fn district(&self) -> String;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde::Deserialize;
+    use std::fs::File;
+
+    /// An entry in the state reference file.
+    #[derive(Debug, Deserialize)]
+    struct ReferenceState {
+        abbreviation: State,
+        state_id: u8,
+        name: String,
+        fips_code: String,
+        capital: String,
+        legislature: String,
+        upper_chamber: String,
+        lower_chamber: Option<String>,
+        session_calendar: String,
+        website: String,
+    }
+
+    #[test]
+    fn test_state_metadata() {
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let reference: Vec<ReferenceState> =
+            serde_json::from_reader(File::open(workspace.join("db/test/states.json")).unwrap())
+                .unwrap();
+        assert_eq!(
+            reference
+                .iter()
+                .map(|state| state.abbreviation)
+                .collect::<Vec<_>>(),
+            State::iter().collect::<Vec<_>>()
+        );
+
+        for expected in reference {
+            let state = expected.abbreviation;
+            assert_eq!(state.id(), expected.state_id, "{state}");
+            assert_eq!(State::try_from(expected.state_id).unwrap(), state);
+            let metadata = state.metadata();
+            assert_eq!(metadata.name, expected.name, "{state}");
+            assert_eq!(metadata.fips_code, expected.fips_code, "{state}");
+            assert_eq!(metadata.capital, expected.capital, "{state}");
+            assert_eq!(metadata.legislature, expected.legislature, "{state}");
+            assert_eq!(metadata.upper_chamber, expected.upper_chamber, "{state}");
+            assert_eq!(
+                metadata.lower_chamber.map(String::from),
+                expected.lower_chamber,
+                "{state}"
+            );
+            assert_eq!(
+                metadata.session_calendar.to_string(),
+                expected.session_calendar,
+                "{state}"
+            );
+            assert_eq!(metadata.website, expected.website, "{state}");
+        }
+    }
+}
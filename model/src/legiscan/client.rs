@@ -1,6 +1,6 @@
 //! A Legiscan client.
 
-use super::{Legiscan, Name, Party, State, Status};
+use super::{Chamber, Legiscan, Name, Party, State, Status};
 use anyhow::Error;
 use async_trait::async_trait;
 use base64::prelude::*;
@@ -286,13 +286,21 @@ pub struct Bill {
     bill_id: u64,
     change_hash: String,
     session_id: u64,
+    session: Session,
     status: u8,
+    status_date: String,
     state: State,
     bill_number: String,
     title: String,
     description: String,
     sponsors: Vec<Person>,
     subjects: Vec<Subject>,
+    #[serde(default)]
+    history: Vec<HistoryItem>,
+    #[serde(default)]
+    votes: Vec<RollCall>,
+    #[serde(default)]
+    calendar: Vec<CalendarItem>,
 }
 
 impl super::Bill for Bill {
@@ -328,6 +336,14 @@ impl super::Bill for Bill {
         }
     }
 
+    fn status_date(&self) -> String {
+        self.status_date.clone()
+    }
+
+    fn session(&self) -> String {
+        self.session.session_name.clone()
+    }
+
     fn name(&self) -> String {
         self.bill_number.clone()
     }
@@ -372,6 +388,48 @@ impl super::Bill for Bill {
             )
             .collect()
     }
+
+    fn history(&self) -> Vec<super::Action> {
+        self.history
+            .iter()
+            .map(|item| super::Action {
+                date: item.date.clone(),
+                description: item.action.clone(),
+                chamber: parse_chamber(&item.chamber),
+                major: item.importance != 0,
+            })
+            .collect()
+    }
+
+    fn votes(&self) -> Vec<super::Vote> {
+        self.votes
+            .iter()
+            .map(|vote| super::Vote {
+                id: vote.roll_call_id.to_string(),
+                date: vote.date.clone(),
+                description: vote.desc.clone(),
+                chamber: parse_chamber(&vote.chamber),
+                yea: vote.yea,
+                nay: vote.nay,
+                not_voting: vote.nv,
+                absent: vote.absent,
+                passed: vote.passed != 0,
+            })
+            .collect()
+    }
+
+    fn calendar(&self) -> Vec<super::Hearing> {
+        self.calendar
+            .iter()
+            .map(|item| super::Hearing {
+                kind: item.kind.clone(),
+                date: item.date.clone(),
+                time: item.time.clone().unwrap_or_default(),
+                location: item.location.clone().unwrap_or_default(),
+                description: item.description.clone(),
+            })
+            .collect()
+    }
 }
 
 /// Response from the `getPerson` endpoint.
@@ -394,6 +452,8 @@ pub struct Person {
     #[serde(deserialize_with = "deserialize_state_id")]
     state_id: State,
     party_id: String,
+    party: String,
+    role_id: u8,
     name: String,
     first_name: String,
     middle_name: String,
@@ -414,26 +474,32 @@ impl super::Person for Person {
         self.state_id
     }
 
-    fn party(&self) -> Party {
-        match self.party_id.as_str() {
-            "1" => Party::Democrat,
-            "2" => Party::Republican,
-            "3" => Party::Independent,
-            "4" => Party::Green,
-            "5" => Party::Libertarian,
-            "6" => Party::Nonpartisan,
-            p => {
+    fn home_state(&self) -> State {
+        if self.state_id != State::US {
+            return self.state_id;
+        }
+        match self.congressional_district() {
+            Some((state, _)) => state,
+            None => {
                 tracing::warn!(
-                    "person {} ({} {}) has unknown party ID {p}",
+                    "member of Congress {} ({} {}) has malformed district {}",
                     self.people_id,
                     self.first_name,
-                    self.last_name
+                    self.last_name,
+                    self.district
                 );
-                Party::Unknown
+                State::US
             }
         }
     }
 
+    fn party(&self) -> Party {
+        Party {
+            id: self.party_id.clone(),
+            abbreviation: self.party.clone(),
+        }
+    }
+
     fn name(&self) -> Name {
         Name {
             first: self.first_name.clone(),
@@ -442,12 +508,55 @@ impl super::Person for Person {
         }
     }
 
+    fn chamber(&self) -> Chamber {
+        match self.role_id {
+            1 => Chamber::Lower,
+            2 => Chamber::Upper,
+            r => {
+                tracing::warn!(
+                    "person {} ({} {}) has unknown role {r}",
+                    self.people_id,
+                    self.first_name,
+                    self.last_name
+                );
+                Chamber::Upper
+            }
+        }
+    }
+
     fn district(&self) -> String {
-        self.district.clone()
+        if self.state_id != State::US {
+            return self.district.clone();
+        }
+        match (self.congressional_district(), super::Person::chamber(self)) {
+            (Some((state, seat)), Chamber::Lower) => {
+                format!("HD-{state}-{}", seat.unwrap_or_else(|| "AL".into()))
+            }
+            (Some((state, _)), Chamber::Upper) => format!("SD-{state}"),
+            // Already logged by `home_state`.
+            (None, _) => self.district.clone(),
+        }
     }
 }
 
 impl Person {
+    /// The home state and seat of a member of Congress.
+    ///
+    /// Legiscan names congressional districts after the chamber and the member's state, as in
+    /// "HD-CA-12" or "HD-CA-012" for a representative and "SD-CA" for a senator. The seat is the
+    /// district number without leading zeros, or [`None`] if there isn't one, as for senators and
+    /// at-large representatives.
+    fn congressional_district(&self) -> Option<(State, Option<String>)> {
+        let mut parts = self.district.split('-').skip(1);
+        let state = parts.next()?.parse().ok()?;
+        let seat = parts
+            .next()
+            .and_then(|seat| seat.parse::<u32>().ok())
+            .filter(|seat| *seat > 0)
+            .map(|seat| seat.to_string());
+        Some((state, seat))
+    }
+
     /// Is this "person" actually an issue?
     ///
     /// Sometimes, for some states, the Legiscan API erroneously categorizes subjects (or topic) as
@@ -496,6 +605,62 @@ impl<I: Iterator<Item = Person>> Iterator for People<I> {
     }
 }
 
+/// A legislative session, in the Legiscan data model.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Session {
+    session_id: u64,
+    session_name: String,
+}
+
+/// An entry in the history of a bill, in the Legiscan data model.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct HistoryItem {
+    date: String,
+    action: String,
+    chamber: String,
+    /// 1 for major steps in the bill's progress, 0 otherwise.
+    importance: u8,
+}
+
+/// A summary of a roll-call vote, in the Legiscan data model.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct RollCall {
+    roll_call_id: u64,
+    date: String,
+    desc: String,
+    yea: u32,
+    nay: u32,
+    nv: u32,
+    absent: u32,
+    passed: u8,
+    chamber: String,
+}
+
+/// A meeting scheduled on a bill, in the Legiscan data model.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CalendarItem {
+    #[serde(rename = "type")]
+    kind: String,
+    date: String,
+    /// Legiscan leaves out the time and location of meetings which haven't been fully scheduled.
+    #[serde(default)]
+    time: Option<String>,
+    #[serde(default)]
+    location: Option<String>,
+    description: String,
+}
+
+/// Interpret a Legiscan chamber code: "H" (or "A", for Assembly) for the lower chamber, "S" for the
+/// upper chamber. Anything else, like "J" for a joint session or an empty code for actions outside
+/// the legislature, is not a single chamber.
+fn parse_chamber(code: &str) -> Option<Chamber> {
+    match code {
+        "H" | "A" => Some(Chamber::Lower),
+        "S" => Some(Chamber::Upper),
+        _ => None,
+    }
+}
+
 /// A subject, or topic, in the Legiscan data model.
 #[derive(Clone, Debug, Deserialize, Serialize)]
 struct Subject {
@@ -10,6 +10,7 @@ use copy_dir::copy_dir;
 use std::marker::PhantomData;
 use std::{
     fs::{self, File, ReadDir},
+    io::ErrorKind,
     path::{Path, PathBuf},
 };
 
@@ -180,6 +181,9 @@ impl<T> DatasetIter<T> {
         Self {
             iter: match path.as_ref().read_dir() {
                 Ok(reader) => Some(reader),
+                // A dataset with nothing of some kind yet, like a session in which no bills have
+                // been introduced, has no directory for it.
+                Err(err) if err.kind() == ErrorKind::NotFound => None,
                 Err(err) => {
                     tracing::error!("unable to read dataset {}: {err}", path.as_ref().display());
                     None
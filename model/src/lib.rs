@@ -2,6 +2,28 @@
 
 pub use relational_graphql::init_logging;
 
+pub mod accounts;
+pub mod actions;
+pub mod catalog;
+pub mod classifier;
+pub mod comments;
 pub mod db;
+pub mod export;
+pub mod facets;
+pub mod feed;
+pub mod follows;
+pub mod hearings;
+pub mod history;
 pub mod legiscan;
+pub mod live;
+pub mod migrations;
+pub mod notifications;
 pub mod schema;
+pub mod search;
+pub mod stances;
+pub mod stats;
+pub mod syndication;
+pub mod taxonomy;
+pub mod trending;
+pub mod votes;
+pub mod webhooks;
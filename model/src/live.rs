@@ -0,0 +1,290 @@
+//! Live updates, delivered through GraphQL subscriptions.
+//!
+//! The process which ingests data from Legiscan and the server which answers GraphQL requests may
+//! run separately, so changes are published through Postgres: [`db::update`] sends a notification
+//! with `NOTIFY` for each change, and each server `LISTEN`s for them on a dedicated connection. A
+//! single [`Changes`] hub per server fans the notifications out to the active subscriptions, each
+//! of which only receives changes on the [topic](Topic) it subscribed to.
+//!
+//! Each subscription buffers a limited number of changes. If a subscriber falls that far behind,
+//! further changes are dropped until it catches up, rather than piling up in memory.
+//!
+//! If the listening connection is lost, for example because the database restarted, the hub
+//! reconnects, backing off exponentially between failed attempts, and resumes listening.
+//! Subscriptions stay open across reconnections, but miss any changes announced while the hub was
+//! disconnected.
+
+use crate::{
+    accounts::Viewer,
+    db::{self, SqlClient},
+    feed::{self, FeedEvent},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{ComplexObject, Context, SimpleObject, Subscription};
+use async_std::task::{sleep, spawn};
+use chrono::{DateTime, Utc};
+use futures::{
+    channel::mpsc::{channel, Receiver, Sender, UnboundedReceiver},
+    future::ready,
+    Stream, StreamExt,
+};
+use relational_graphql::graphql::type_system::Id;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_postgres::Notification;
+
+/// The channel on which updates to bills are announced, with the ID of the bill as the payload.
+const BILL_UPDATED: &str = "bill_updated";
+
+/// The channel on which new feed events are announced, with the ID of the event as the payload.
+const FEED_EVENTS: &str = "feed_events";
+
+/// How long to wait before the first attempt to reconnect after losing the listening connection.
+const MIN_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// The longest to wait between attempts to reconnect.
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+/// The number of changes buffered for each subscription before further changes are dropped.
+pub const SUBSCRIPTION_BUFFER: usize = 64;
+
+/// A change announced by the ingest process.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// The bill with the given ID changed.
+    BillUpdated(i32),
+    /// The feed event with the given ID was recorded.
+    FeedEvent(i32),
+}
+
+impl Change {
+    /// The topic this change is published on.
+    pub fn topic(&self) -> Topic {
+        match self {
+            Self::BillUpdated(bill) => Topic::Bill(*bill),
+            Self::FeedEvent(_) => Topic::FeedEvents,
+        }
+    }
+
+    fn parse(notification: &Notification) -> Result<Self, Error> {
+        let id = notification.payload().parse()?;
+        match notification.channel() {
+            BILL_UPDATED => Ok(Self::BillUpdated(id)),
+            FEED_EVENTS => Ok(Self::FeedEvent(id)),
+            channel => Err(Error::msg(format!(
+                "unexpected notification channel {channel}"
+            ))),
+        }
+    }
+}
+
+/// A kind of change which can be subscribed to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Topic {
+    /// Changes to the bill with the given ID.
+    Bill(i32),
+    /// New feed events.
+    FeedEvents,
+}
+
+/// Announce that `bills` have changed.
+pub async fn notify_bills_updated(sql: &SqlClient, bills: &[Id]) -> Result<(), Error> {
+    sql.execute(
+        "SELECT pg_notify($1, bill::TEXT) FROM UNNEST($2::INTEGER[]) AS bill",
+        &[&BILL_UPDATED, &bills],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Announce that the feed event `event` was recorded.
+pub async fn notify_feed_event(sql: &SqlClient, event: i32) -> Result<(), Error> {
+    sql.execute(
+        "SELECT pg_notify($1, $2::INTEGER::TEXT)",
+        &[&FEED_EVENTS, &event],
+    )
+    .await?;
+    Ok(())
+}
+
+/// A hub which listens for changes and forwards them to subscribers.
+#[derive(Clone)]
+pub struct Changes {
+    /// The listening connection, which is also used to run the queries needed by subscriptions.
+    ///
+    /// This is replaced whenever the hub reconnects.
+    sql: Arc<Mutex<Arc<SqlClient>>>,
+    subscribers: Arc<Mutex<HashMap<Topic, Vec<Sender<Change>>>>>,
+}
+
+impl Changes {
+    /// Start listening for changes.
+    ///
+    /// This fails if the first connection can't be made. After that, the hub reconnects by itself
+    /// whenever the connection is lost.
+    pub async fn listen(opt: &db::Options) -> Result<Self, Error> {
+        let (sql, mut notifications) = connect(opt).await?;
+        let hub = Self {
+            sql: Arc::new(Mutex::new(Arc::new(sql))),
+            subscribers: Default::default(),
+        };
+        let opt = opt.clone();
+        let listener = hub.clone();
+        spawn(async move {
+            loop {
+                while let Some(notification) = notifications.next().await {
+                    listener.forward(&notification);
+                }
+                tracing::warn!("lost connection listening for changes");
+                notifications = listener.reconnect(&opt).await;
+            }
+        });
+        Ok(hub)
+    }
+
+    /// Receive the changes on `topic` from now on.
+    ///
+    /// At most [`SUBSCRIPTION_BUFFER`] changes are buffered; if the receiver falls further behind,
+    /// it misses changes until it catches up.
+    pub fn subscribe(&self, topic: Topic) -> Receiver<Change> {
+        let (sender, receiver) = channel(SUBSCRIPTION_BUFFER);
+        self.subscribers
+            .lock()
+            .unwrap()
+            .entry(topic)
+            .or_default()
+            .push(sender);
+        receiver
+    }
+
+    /// The client for running the queries needed by subscriptions.
+    fn sql(&self) -> Arc<SqlClient> {
+        self.sql.lock().unwrap().clone()
+    }
+
+    /// Forward a notification to each subscriber to its topic, forgetting those which have gone
+    /// away.
+    fn forward(&self, notification: &Notification) {
+        let change = match Change::parse(notification) {
+            Ok(change) => change,
+            Err(err) => {
+                tracing::warn!("ignoring malformed notification {notification:?}: {err}");
+                return;
+            }
+        };
+        let topic = change.topic();
+        let mut subscribers = self.subscribers.lock().unwrap();
+        let Some(senders) = subscribers.get_mut(&topic) else {
+            return;
+        };
+        senders.retain_mut(|sender| match sender.try_send(change) {
+            Ok(()) => true,
+            Err(err) if err.is_full() => {
+                tracing::warn!("dropping {change:?} for a subscriber which is falling behind");
+                true
+            }
+            Err(_) => false,
+        });
+        if senders.is_empty() {
+            subscribers.remove(&topic);
+        }
+    }
+
+    /// Keep trying to connect until it works, and start using the new connection.
+    async fn reconnect(&self, opt: &db::Options) -> UnboundedReceiver<Notification> {
+        let mut delay = MIN_RECONNECT_DELAY;
+        loop {
+            sleep(delay).await;
+            match connect(opt).await {
+                Ok((sql, notifications)) => {
+                    tracing::info!("reconnected to listen for changes");
+                    *self.sql.lock().unwrap() = Arc::new(sql);
+                    return notifications;
+                }
+                Err(err) => {
+                    delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+                    tracing::error!("failed to reconnect, retrying in {delay:?}: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Connect to the database and listen for changes.
+async fn connect(opt: &db::Options) -> Result<(SqlClient, UnboundedReceiver<Notification>), Error> {
+    let (sql, notifications) = opt.connect_sql_notifications().await?;
+    sql.batch_execute(&format!("LISTEN {BILL_UPDATED}; LISTEN {FEED_EVENTS};"))
+        .await?;
+    Ok((sql, notifications))
+}
+
+/// A notice that a bill changed.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct BillUpdate {
+    /// When the change was received.
+    pub received_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub id: Id,
+}
+
+#[ComplexObject]
+impl BillUpdate {
+    /// The bill, as it is after the change.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        Ok(db::bill_by_id(ctx.data()?, self.id).await?)
+    }
+}
+
+/// Entrypoint for all GraphQL subscriptions.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Subscription;
+
+#[Subscription]
+impl Subscription {
+    /// Changes to the bill with the given ID.
+    async fn bill_updated(
+        &self,
+        ctx: &Context<'_>,
+        id: Id,
+    ) -> async_graphql::Result<impl Stream<Item = BillUpdate>> {
+        let bill: i32 = id.into();
+        Ok(ctx
+            .data::<Changes>()?
+            .subscribe(Topic::Bill(bill))
+            .filter_map(move |change| {
+                ready(match change {
+                    Change::BillUpdated(updated) if updated == bill => Some(BillUpdate {
+                        received_at: Utc::now(),
+                        id,
+                    }),
+                    _ => None,
+                })
+            }))
+    }
+
+    /// New events in the signed-in user's Home feed, as they are recorded.
+    async fn feed_events(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<impl Stream<Item = async_graphql::Result<FeedEvent>>> {
+        let user = Viewer::of(ctx).require()?;
+        let changes = ctx.data::<Changes>()?.clone();
+        Ok(changes
+            .subscribe(Topic::FeedEvents)
+            .filter_map(move |change| {
+                let sql = changes.sql();
+                async move {
+                    match change {
+                        Change::FeedEvent(event) => feed::event_for(&sql, user, event)
+                            .await
+                            .map_err(async_graphql::Error::from)
+                            .transpose(),
+                        _ => None,
+                    }
+                }
+            }))
+    }
+}
@@ -0,0 +1,716 @@
+//! Versioned migrations of the database schema.
+//!
+//! [`db::setup`] creates the latest schema directly, and records that the database is at the
+//! [latest version](latest). Databases set up by older versions of the application are brought up
+//! to date by [applying](up), in order, each of the [`MIGRATIONS`] which they have not seen yet.
+//! The version of a database is the number of migrations which have been applied to it, and the
+//! migrations applied so far are recorded in the `schema_migrations` table.
+//!
+//! To change the schema, update the setup code so that new databases get the new schema, and append
+//! a migration to [`MIGRATIONS`] which transforms the previous schema into the new one. Migrations
+//! are written as plain SQL rather than by calling the setup code, which will keep changing, and
+//! any data they need, like the metadata of states, is copied into them as it is when they are
+//! written. The exception is the [baseline](baseline), which runs the current setup of the
+//! supplementary tables, so every later migration must also apply cleanly to tables which already
+//! have its change (with `IF NOT EXISTS` and the like).
+//!
+//! The tables backing the resources in [`schema`] are created by relational-graphql, which can't
+//! alter them, so a change to the resources is migrated by comparing the tables with a fresh copy
+//! registered in a scratch schema, and adding whatever tables and columns are missing (see the
+//! "resource tables" migration). The new columns of existing rows are filled in with empty values,
+//! so such a change usually needs a second migration which fills in the real ones. Migrations must
+//! never be changed or reordered once they have been released, since databases in the wild may
+//! already have applied them.
+//!
+//! The server [refuses](check) to start against a database whose schema is not at the latest
+//! version; use `db migrate up` to upgrade it first.
+
+use super::{
+    db::{self, SqlClient},
+    schema,
+};
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use futures::future::{BoxFuture, FutureExt};
+
+/// A step in the evolution of the database schema.
+pub struct Migration {
+    /// The version of the schema after applying this migration.
+    ///
+    /// The migration at index `i` of [`MIGRATIONS`] has version `i + 1`.
+    pub version: i32,
+    /// A short description of the change.
+    pub name: &'static str,
+    apply: for<'a> fn(&'a db::Options, &'a SqlClient) -> BoxFuture<'a, Result<(), Error>>,
+}
+
+/// Every migration, in the order they are applied.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        apply: baseline,
+    },
+    Migration {
+        version: 2,
+        name: "abbreviations of West Virginia and Wisconsin",
+        apply: fix_state_abbreviations,
+    },
+    Migration {
+        version: 3,
+        name: "resource tables",
+        apply: sync_resources,
+    },
+    Migration {
+        version: 4,
+        name: "resource backfill",
+        apply: backfill_resources,
+    },
+    Migration {
+        version: 5,
+        name: "feed event subjects index",
+        apply: add_feed_event_subjects_index,
+    },
+    Migration {
+        version: 6,
+        name: "home states of users in congressional districts",
+        apply: fix_user_home_states,
+    },
+];
+
+/// The version of the schema created by [`db::setup`], and required by the server.
+pub fn latest() -> i32 {
+    MIGRATIONS.len() as i32
+}
+
+/// The migrations which must be applied to a database at `version` to bring it up to date.
+pub fn pending(version: i32) -> &'static [Migration] {
+    MIGRATIONS
+        .get(version.max(0) as usize..)
+        .unwrap_or_default()
+}
+
+/// A migration which has been applied to the database.
+#[derive(Clone, Debug)]
+pub struct AppliedMigration {
+    pub version: i32,
+    pub name: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+/// The migrations which have been applied to the database, in order.
+///
+/// This is empty for databases which were set up before schema versions were introduced.
+pub async fn status(sql: &SqlClient) -> Result<Vec<AppliedMigration>, Error> {
+    if !versioned(sql).await? {
+        return Ok(vec![]);
+    }
+    sql.query(
+        "SELECT version, name, applied_at FROM schema_migrations ORDER BY version",
+        &[],
+    )
+    .await?
+    .into_iter()
+    .map(|row| {
+        Ok(AppliedMigration {
+            version: row.try_get("version")?,
+            name: row.try_get("name")?,
+            applied_at: row.try_get("applied_at")?,
+        })
+    })
+    .collect()
+}
+
+/// The current version of the database schema.
+pub async fn version(sql: &SqlClient) -> Result<i32, Error> {
+    if !versioned(sql).await? {
+        return Ok(0);
+    }
+    let row = sql
+        .query_one(
+            "SELECT coalesce(max(version), 0) AS version FROM schema_migrations",
+            &[],
+        )
+        .await?;
+    Ok(row.try_get("version")?)
+}
+
+/// Apply pending migrations, bringing the database schema up to date.
+///
+/// The migrations are applied in a single transaction, so if any of them fails, the database is
+/// left as it was. With `dry_run`, the transaction is always rolled back, which checks that the
+/// migrations would succeed without changing anything.
+///
+/// Returns the migrations which were (or, with `dry_run`, would have been) applied.
+///
+/// `opt` must refer to the same database as `sql`. Some migrations use it to make connections of
+/// their own.
+pub async fn up(
+    opt: &db::Options,
+    sql: &SqlClient,
+    dry_run: bool,
+) -> Result<&'static [Migration], Error> {
+    if !versioned(sql).await? && !initialized(sql).await? {
+        return Err(Error::msg(
+            "database has not been set up; use `db init` to create the latest schema",
+        ));
+    }
+
+    sql.batch_execute("BEGIN").await?;
+    match apply(opt, sql).await {
+        Ok(applied) if !dry_run => {
+            sql.batch_execute("COMMIT").await?;
+            Ok(applied)
+        }
+        res => {
+            sql.batch_execute("ROLLBACK").await?;
+            res
+        }
+    }
+}
+
+/// Fail unless the database schema is at the [latest] version.
+///
+/// This also [checks](db::check_resource_tables) that the resource tables which the application
+/// writes to directly have the layout it expects.
+pub async fn check(sql: &SqlClient) -> Result<(), Error> {
+    let version = version(sql).await?;
+    let latest = latest();
+    if version < latest {
+        Err(Error::msg(format!(
+            "database schema is at version {version}, but version {latest} is required; \
+            use `db migrate up` to upgrade it"
+        )))
+    } else if version > latest {
+        Err(Error::msg(format!(
+            "database schema is at version {version}, \
+            which is newer than this version of the application supports ({latest})"
+        )))
+    } else {
+        db::check_resource_tables(sql).await
+    }
+}
+
+/// Record that a newly set up database has the latest schema.
+pub(crate) async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    create_table(sql).await?;
+    for migration in MIGRATIONS {
+        record(sql, migration).await?;
+    }
+    Ok(())
+}
+
+/// Apply pending migrations within the current transaction.
+async fn apply(opt: &db::Options, sql: &SqlClient) -> Result<&'static [Migration], Error> {
+    create_table(sql).await?;
+    // Lock out concurrent migrations until the transaction ends.
+    sql.batch_execute("LOCK TABLE schema_migrations IN EXCLUSIVE MODE")
+        .await?;
+
+    let pending = pending(version(sql).await?);
+    for migration in pending {
+        tracing::info!(
+            "applying migration {}: {}",
+            migration.version,
+            migration.name
+        );
+        (migration.apply)(opt, sql).await?;
+        record(sql, migration).await?;
+    }
+    Ok(pending)
+}
+
+async fn create_table(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );",
+    )
+    .await?;
+    Ok(())
+}
+
+async fn record(sql: &SqlClient, migration: &Migration) -> Result<(), Error> {
+    sql.execute(
+        "INSERT INTO schema_migrations (version, name) VALUES ($1, $2)",
+        &[&migration.version, &migration.name],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Whether the database has a table of applied migrations.
+async fn versioned(sql: &SqlClient) -> Result<bool, Error> {
+    let row = sql
+        .query_one(
+            "SELECT to_regclass('schema_migrations') IS NOT NULL AS versioned",
+            &[],
+        )
+        .await?;
+    Ok(row.try_get("versioned")?)
+}
+
+/// Whether the database has been set up at all.
+async fn initialized(sql: &SqlClient) -> Result<bool, Error> {
+    let row = sql
+        .query_one(
+            "SELECT EXISTS (
+                SELECT 1 FROM information_schema.tables WHERE table_schema = current_schema()
+            ) AS initialized",
+            &[],
+        )
+        .await?;
+    Ok(row.try_get("initialized")?)
+}
+
+/// Adopt a database set up before schema versions were introduced.
+///
+/// Such a database may be missing supplementary tables added by later versions of the application,
+/// like the tables of votes, actions, hearings, comment quotas and failed sign-ins. Their setup is
+/// idempotent, so just run it again. Its resource tables are brought up to date later, by the
+/// "resource tables" migration.
+fn baseline<'a>(_: &'a db::Options, sql: &'a SqlClient) -> BoxFuture<'a, Result<(), Error>> {
+    db::setup_tables(sql).boxed()
+}
+
+/// Swap the abbreviations of West Virginia and Wisconsin, which were the wrong way round.
+///
+/// Legiscan numbers states alphabetically by name, so its state 48 is West Virginia and 49 is
+/// Wisconsin, and so are the states with those IDs here. Older versions of the application
+/// abbreviated them as WI and WV respectively, so the bills and legislators of each state were
+/// filed under the other's abbreviation. The states are relabelled, along with the abbreviations
+/// which were copied from them into the catalog, the home states of users, and the states which
+/// webhooks are filtered by, so that all of these still refer to the same bills as before.
+///
+/// Databases which already have the right abbreviations are left as they are.
+fn fix_state_abbreviations<'a>(
+    _: &'a db::Options,
+    sql: &'a SqlClient,
+) -> BoxFuture<'a, Result<(), Error>> {
+    async move {
+        sql.batch_execute(
+            "UPDATE catalog_bills SET state = CASE state WHEN 'WI' THEN 'WV' ELSE 'WI' END
+                WHERE state IN ('WI', 'WV')
+                    AND EXISTS (SELECT 1 FROM states WHERE id = 48 AND abbreviation = 'WI');
+            UPDATE catalog_legislators SET state = CASE state WHEN 'WI' THEN 'WV' ELSE 'WI' END
+                WHERE state IN ('WI', 'WV')
+                    AND EXISTS (SELECT 1 FROM states WHERE id = 48 AND abbreviation = 'WI');
+            UPDATE users SET state = CASE state WHEN 'WI' THEN 'WV' ELSE 'WI' END
+                WHERE state IN ('WI', 'WV')
+                    AND EXISTS (SELECT 1 FROM states WHERE id = 48 AND abbreviation = 'WI');
+            UPDATE webhooks SET states = ARRAY(
+                    SELECT CASE s WHEN 'WI' THEN 'WV' WHEN 'WV' THEN 'WI' ELSE s END
+                    FROM unnest(states) WITH ORDINALITY AS u (s, i)
+                    ORDER BY i
+                )
+                WHERE states && ARRAY['WI', 'WV']
+                    AND EXISTS (SELECT 1 FROM states WHERE id = 48 AND abbreviation = 'WI');
+            -- Relabel the states last, since the updates above check whether they are swapped.
+            -- The abbreviations are unique, so they go through temporary ones to be swapped.
+            UPDATE states SET abbreviation = '~' || abbreviation
+                WHERE id IN (48, 49)
+                    AND EXISTS (SELECT 1 FROM states WHERE id = 48 AND abbreviation = 'WI');
+            UPDATE states SET
+                    abbreviation = CASE id WHEN 48 THEN 'WV' ELSE 'WI' END,
+                    name = CASE id WHEN 48 THEN 'West Virginia' ELSE 'Wisconsin' END
+                WHERE id IN (48, 49) AND abbreviation LIKE '~%';",
+        )
+        .await?;
+        Ok(())
+    }
+    .boxed()
+}
+
+/// The scratch schema in which [`sync_resources`] registers a fresh copy of the resource tables.
+const SCRATCH_SCHEMA: &str = "resource_migration_scratch";
+
+/// Add the resource tables and columns which are missing from the database.
+///
+/// This registers the resources in [`schema`] in an empty scratch schema, and compares the tables
+/// it gets with the ones in use. Tables which are missing altogether, like those of new resources
+/// and relations, are moved over from the scratch schema, along with their indexes and sequences.
+/// Columns which are missing from existing tables are added, and if they can't be null, they are
+/// filled in with an empty value of their type in existing rows. So are indexes on existing tables.
+///
+/// The registration happens on separate connections, outside the migration transaction, but the
+/// scratch schema is recreated each time this runs and dropped at the end of it.
+fn sync_resources<'a>(
+    opt: &'a db::Options,
+    sql: &'a SqlClient,
+) -> BoxFuture<'a, Result<(), Error>> {
+    async move {
+        let scratch = opt.connect_sql().await?;
+        scratch
+            .batch_execute(&format!(
+                "DROP SCHEMA IF EXISTS {SCRATCH_SCHEMA} CASCADE; CREATE SCHEMA {SCRATCH_SCHEMA};"
+            ))
+            .await?;
+        let mut conn = opt.connect_in_schema(SCRATCH_SCHEMA).await?;
+        schema::Query::register(&mut conn).await?;
+        drop(conn);
+
+        let current: String = sql
+            .query_one("SELECT current_schema() AS name", &[])
+            .await?
+            .try_get("name")?;
+
+        // Move over the missing tables.
+        let tables = sql
+            .query(
+                "SELECT t.relname AS table_name
+                FROM pg_class t JOIN pg_namespace n ON n.oid = t.relnamespace
+                WHERE n.nspname = $1 AND t.relkind = 'r'
+                    AND to_regclass(format('%I.%I', current_schema(), t.relname)) IS NULL
+                ORDER BY t.relname",
+                &[&SCRATCH_SCHEMA],
+            )
+            .await?;
+        for row in tables {
+            let table: String = row.try_get("table_name")?;
+            tracing::info!("creating table {table}");
+            sql.batch_execute(&format!(
+                "ALTER TABLE {SCRATCH_SCHEMA}.{} SET SCHEMA {}",
+                ident(&table),
+                ident(&current)
+            ))
+            .await?;
+        }
+
+        // Add the missing columns to the remaining tables.
+        let columns = sql
+            .query(
+                "SELECT
+                    t.relname AS table_name,
+                    a.attname AS column_name,
+                    format_type(a.atttypid, a.atttypmod) AS column_type,
+                    a.atttypid::regtype::text AS base_type,
+                    a.attnotnull AS not_null
+                FROM pg_attribute a
+                    JOIN pg_class t ON t.oid = a.attrelid
+                    JOIN pg_namespace n ON n.oid = t.relnamespace
+                WHERE n.nspname = $1 AND t.relkind = 'r' AND a.attnum > 0 AND NOT a.attisdropped
+                    AND NOT EXISTS (
+                        SELECT 1 FROM information_schema.columns c
+                        WHERE c.table_schema = current_schema()
+                            AND c.table_name = t.relname
+                            AND c.column_name = a.attname
+                    )
+                ORDER BY t.relname, a.attnum",
+                &[&SCRATCH_SCHEMA],
+            )
+            .await?;
+        for row in columns {
+            let table: String = row.try_get("table_name")?;
+            let column: String = row.try_get("column_name")?;
+            let ty: String = row.try_get("column_type")?;
+            tracing::info!("adding column {table}.{column}");
+            let add = format!(
+                "ALTER TABLE {} ADD COLUMN {} {ty}",
+                ident(&table),
+                ident(&column)
+            );
+            if row.try_get("not_null")? {
+                let base_type: String = row.try_get("base_type")?;
+                let empty = empty_value(&base_type).ok_or_else(|| {
+                    Error::msg(format!(
+                        "ICE: no value to fill in new column {table}.{column} of type {ty}"
+                    ))
+                })?;
+                sql.batch_execute(&format!(
+                    "{add} NOT NULL DEFAULT {empty};
+                    ALTER TABLE {} ALTER COLUMN {} DROP DEFAULT;",
+                    ident(&table),
+                    ident(&column)
+                ))
+                .await?;
+            } else {
+                sql.batch_execute(&add).await?;
+            }
+        }
+
+        // Add the missing indexes to the remaining tables.
+        let indexes = sql
+            .query(
+                "SELECT indexname AS name, indexdef AS definition FROM pg_indexes
+                WHERE schemaname = $1
+                    AND to_regclass(format('%I.%I', current_schema(), indexname)) IS NULL",
+                &[&SCRATCH_SCHEMA],
+            )
+            .await?;
+        for row in indexes {
+            let name: String = row.try_get("name")?;
+            let definition: String = row.try_get("definition")?;
+            tracing::info!("creating index {name}");
+            sql.batch_execute(&definition.replace(&format!(" ON {SCRATCH_SCHEMA}."), " ON "))
+                .await?;
+        }
+
+        // The tables we moved over may have foreign keys to tables which are still in the scratch
+        // schema. Point those at the tables in use instead.
+        let keys = sql
+            .query(
+                "SELECT
+                    t.relname AS table_name,
+                    c.conname AS name,
+                    pg_get_constraintdef(c.oid) AS definition
+                FROM pg_constraint c
+                    JOIN pg_class t ON t.oid = c.conrelid
+                    JOIN pg_namespace tn ON tn.oid = t.relnamespace
+                    JOIN pg_class r ON r.oid = c.confrelid
+                    JOIN pg_namespace rn ON rn.oid = r.relnamespace
+                WHERE c.contype = 'f' AND tn.nspname = current_schema() AND rn.nspname = $1",
+                &[&SCRATCH_SCHEMA],
+            )
+            .await?;
+        for row in keys {
+            let table: String = row.try_get("table_name")?;
+            let name: String = row.try_get("name")?;
+            let definition: String = row.try_get("definition")?;
+            sql.batch_execute(&format!(
+                "ALTER TABLE {table} DROP CONSTRAINT {name}, ADD CONSTRAINT {name} {definition}",
+                table = ident(&table),
+                name = ident(&name),
+                definition =
+                    definition.replace(&format!("REFERENCES {SCRATCH_SCHEMA}."), "REFERENCES "),
+            ))
+            .await?;
+        }
+
+        sql.batch_execute(&format!("DROP SCHEMA {SCRATCH_SCHEMA} CASCADE"))
+            .await?;
+        Ok(())
+    }
+    .boxed()
+}
+
+/// Fill in the resource fields which were added by the "resource tables" migration.
+///
+/// The resource API can only insert, so the fields are filled in by writing to the resource tables
+/// directly. Only the columns which that migration added with empty values are written, and if the
+/// tables don't have the layout this expects, the migration fails and is rolled back.
+///
+/// The state metadata and the Legiscan IDs of the well-known parties are static, so they are copied
+/// in as they were when this migration was written. Legislators get the state of their district as
+/// their home state, which is right for everyone but members of Congress. Bill statuses and
+/// sessions and legislator chambers come from Legiscan, so the Legiscan hashes of the bills and
+/// legislators which are missing them are cleared, which makes the next pull update them (and the
+/// home states of members of Congress).
+///
+/// The issue hierarchy and the mapping from Legiscan subjects to issues are not filled in, since
+/// they come from a taxonomy, which migrations don't have. Until they are populated, issues have no
+/// parents, children or ancestors, and new bills get no issues from their subjects.
+fn backfill_resources<'a>(
+    _: &'a db::Options,
+    sql: &'a SqlClient,
+) -> BoxFuture<'a, Result<(), Error>> {
+    async move {
+        sql.batch_execute(&format!(
+            "UPDATE states s SET
+                fips_code = v.fips_code,
+                capital = v.capital,
+                legislature = v.legislature,
+                upper_chamber = v.upper_chamber,
+                lower_chamber = v.lower_chamber,
+                session_calendar = v.session_calendar,
+                website = v.website
+            FROM ({BACKFILL_STATES}) AS v (
+                abbreviation, fips_code, capital, legislature, upper_chamber, lower_chamber,
+                session_calendar, website
+            )
+            WHERE s.abbreviation = v.abbreviation AND s.website = '';
+            UPDATE parties p SET legiscan_id = v.legiscan_id
+            FROM (VALUES
+                ('0', '?'), ('1', 'D'), ('2', 'R'), ('3', 'I'), ('4', 'G'), ('5', 'L'), ('6', 'n/a')
+            ) AS v (legiscan_id, abbreviation)
+            WHERE p.abbreviation = v.abbreviation AND p.legiscan_id = '';
+            UPDATE legislators l SET home_state = d.state
+                FROM districts d WHERE d.id = l.district AND l.home_state = 0;
+            UPDATE bills SET legiscan_hash = '' WHERE session = '';
+            UPDATE legislators SET legiscan_hash = '' WHERE chamber = '';"
+        ))
+        .await?;
+        Ok(())
+    }
+    .boxed()
+}
+
+/// The metadata of each state filled in by [`backfill_resources`], as SQL `VALUES` of the
+/// abbreviation, FIPS code, capital, legislature, upper and lower chambers, session calendar and
+/// website of the state.
+const BACKFILL_STATES: &str = "VALUES
+    ('AL', '01', 'Montgomery', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://alison.legislature.state.al.us'),
+    ('AK', '02', 'Juneau', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.akleg.gov'),
+    ('AZ', '04', 'Phoenix', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.azleg.gov'),
+    ('AR', '05', 'Little Rock', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.arkleg.state.ar.us'),
+    ('CA', '06', 'Sacramento', 'Legislature', 'Senate', 'Assembly',
+        'annual', 'https://www.legislature.ca.gov'),
+    ('CO', '08', 'Denver', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://leg.colorado.gov'),
+    ('CT', '09', 'Hartford', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.cga.ct.gov'),
+    ('DE', '10', 'Dover', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://legis.delaware.gov'),
+    ('FL', '12', 'Tallahassee', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.leg.state.fl.us'),
+    ('GA', '13', 'Atlanta', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.legis.ga.gov'),
+    ('HI', '15', 'Honolulu', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.capitol.hawaii.gov'),
+    ('ID', '16', 'Boise', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://legislature.idaho.gov'),
+    ('IL', '17', 'Springfield', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.ilga.gov'),
+    ('IN', '18', 'Indianapolis', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://iga.in.gov'),
+    ('IA', '19', 'Des Moines', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.legis.iowa.gov'),
+    ('KS', '20', 'Topeka', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.kslegislature.org'),
+    ('KY', '21', 'Frankfort', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://legislature.ky.gov'),
+    ('LA', '22', 'Baton Rouge', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://legis.la.gov'),
+    ('ME', '23', 'Augusta', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://legislature.maine.gov'),
+    ('MD', '24', 'Annapolis', 'General Assembly', 'Senate', 'House of Delegates',
+        'annual', 'https://mgaleg.maryland.gov'),
+    ('MA', '25', 'Boston', 'General Court', 'Senate', 'House of Representatives',
+        'annual', 'https://malegislature.gov'),
+    ('MI', '26', 'Lansing', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.legislature.mi.gov'),
+    ('MN', '27', 'Saint Paul', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.leg.mn.gov'),
+    ('MS', '28', 'Jackson', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.legislature.ms.gov'),
+    ('MO', '29', 'Jefferson City', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.moga.mo.gov'),
+    ('MT', '30', 'Helena', 'Legislature', 'Senate', 'House of Representatives',
+        'biennial', 'https://leg.mt.gov'),
+    ('NE', '31', 'Lincoln', 'Legislature', 'Legislature', '',
+        'annual', 'https://nebraskalegislature.gov'),
+    ('NV', '32', 'Carson City', 'Legislature', 'Senate', 'Assembly',
+        'biennial', 'https://www.leg.state.nv.us'),
+    ('NH', '33', 'Concord', 'General Court', 'Senate', 'House of Representatives',
+        'annual', 'https://www.gencourt.state.nh.us'),
+    ('NJ', '34', 'Trenton', 'Legislature', 'Senate', 'General Assembly',
+        'annual', 'https://www.njleg.state.nj.us'),
+    ('NM', '35', 'Santa Fe', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.nmlegis.gov'),
+    ('NY', '36', 'Albany', 'Legislature', 'Senate', 'Assembly',
+        'annual', 'https://www.nysenate.gov'),
+    ('NC', '37', 'Raleigh', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.ncleg.gov'),
+    ('ND', '38', 'Bismarck', 'Legislative Assembly', 'Senate', 'House of Representatives',
+        'biennial', 'https://www.ndlegis.gov'),
+    ('OH', '39', 'Columbus', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.legislature.ohio.gov'),
+    ('OK', '40', 'Oklahoma City', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.oklegislature.gov'),
+    ('OR', '41', 'Salem', 'Legislative Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.oregonlegislature.gov'),
+    ('PA', '42', 'Harrisburg', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.palegis.us'),
+    ('RI', '44', 'Providence', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.rilegislature.gov'),
+    ('SC', '45', 'Columbia', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.scstatehouse.gov'),
+    ('SD', '46', 'Pierre', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://sdlegislature.gov'),
+    ('TN', '47', 'Nashville', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://www.capitol.tn.gov'),
+    ('TX', '48', 'Austin', 'Legislature', 'Senate', 'House of Representatives',
+        'biennial', 'https://capitol.texas.gov'),
+    ('UT', '49', 'Salt Lake City', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://le.utah.gov'),
+    ('VT', '50', 'Montpelier', 'General Assembly', 'Senate', 'House of Representatives',
+        'annual', 'https://legislature.vermont.gov'),
+    ('VA', '51', 'Richmond', 'General Assembly', 'Senate', 'House of Delegates',
+        'annual', 'https://virginiageneralassembly.gov'),
+    ('WA', '53', 'Olympia', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://leg.wa.gov'),
+    ('WV', '54', 'Charleston', 'Legislature', 'Senate', 'House of Delegates',
+        'annual', 'https://www.wvlegislature.gov'),
+    ('WI', '55', 'Madison', 'Legislature', 'Senate', 'Assembly',
+        'annual', 'https://legis.wisconsin.gov'),
+    ('WY', '56', 'Cheyenne', 'Legislature', 'Senate', 'House of Representatives',
+        'annual', 'https://www.wyoleg.gov'),
+    ('DC', '11', 'Washington', 'Council of the District of Columbia', 'Council', '',
+        'annual', 'https://dccouncil.gov'),
+    ('US', '', 'Washington', 'Congress', 'Senate', 'House of Representatives',
+        'annual', 'https://www.congress.gov')";
+
+/// Index the subjects of feed events by event, which is how feeds and notifications look them up.
+fn add_feed_event_subjects_index<'a>(
+    _: &'a db::Options,
+    sql: &'a SqlClient,
+) -> BoxFuture<'a, Result<(), Error>> {
+    async move {
+        sql.batch_execute(
+            "CREATE INDEX IF NOT EXISTS feed_event_subjects_event ON feed_event_subjects (event)",
+        )
+        .await?;
+        Ok(())
+    }
+    .boxed()
+}
+
+/// Record the state of users living in a congressional district as the one the district belongs
+/// to, rather than the federal state.
+fn fix_user_home_states<'a>(
+    _: &'a db::Options,
+    sql: &'a SqlClient,
+) -> BoxFuture<'a, Result<(), Error>> {
+    async move {
+        sql.batch_execute(
+            "UPDATE users u SET state = split_part(d.name, '-', 2)
+            FROM districts d
+            JOIN states s ON s.id = d.state
+            WHERE d.id = u.district AND s.abbreviation = 'US' AND d.name LIKE '%-%'",
+        )
+        .await?;
+        Ok(())
+    }
+    .boxed()
+}
+
+/// The value with which to fill in a new column of type `ty` in existing rows.
+fn empty_value(ty: &str) -> Option<&'static str> {
+    match ty {
+        "text" | "character varying" | "character" => Some("''"),
+        "smallint" | "integer" | "bigint" | "real" | "double precision" | "numeric" => Some("0"),
+        "boolean" => Some("false"),
+        _ => None,
+    }
+}
+
+/// Quote `name` as an SQL identifier.
+fn ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pending() {
+        assert_eq!(pending(0).len(), MIGRATIONS.len());
+        assert!(pending(latest()).is_empty());
+        assert!(pending(latest() + 1).is_empty());
+        for (i, migration) in MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version, i as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn test_ident() {
+        assert_eq!(ident("bills"), "\"bills\"");
+        assert_eq!(ident("odd\"name"), "\"odd\"\"name\"");
+    }
+}
@@ -0,0 +1,538 @@
+//! Email notifications about followed entities.
+//!
+//! Each user chooses, for each kind of entity they [follow](crate::follows), whether to be
+//! emailed about changes to those entities as they happen (`INSTANT`), in a daily digest (`DAILY`),
+//! or not at all (`OFF`, the default). Preferences are kept in a supplementary table,
+//! `notification_preferences`.
+//!
+//! Notifications are built from [feed](crate::feed) events, so users hear about the same changes
+//! that appear in their feeds: new bills, status changes, new sponsors, votes and hearings.
+//! Delivery happens in two steps, so that each notification is sent exactly once even if delivery
+//! is interrupted:
+//! 1. [`enqueue`] adds a row to `email_notifications` for each new event which a user wants to be
+//!    notified about.
+//! 2. [`send`] emails the pending notifications in one mode, instant or daily, and marks them as
+//!    sent. Instant notifications are sent one email per event; daily notifications are collected
+//!    into one digest per user.
+//!
+//! Emails are delivered over SMTP, so any server will do, including a local sink for testing.
+
+use crate::{
+    accounts::Viewer,
+    db::{self, SqlClient},
+    feed::FeedEventKind,
+    follows::FollowKind,
+    live::{Change, Changes, Topic},
+};
+use anyhow::Error;
+use async_graphql::{Context, Enum, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use futures::StreamExt;
+use lettre::{
+    message::{Mailbox, MultiPart},
+    transport::smtp::authentication::Credentials,
+    AsyncSmtpTransport, AsyncStd1Executor, AsyncTransport, Message,
+};
+use std::fmt::Write;
+use strum::{Display, EnumString, IntoEnumIterator};
+use surf::Url;
+
+/// Only events from this many days ago or later are considered for notifications.
+const WINDOW_DAYS: i32 = 7;
+
+/// How a user wants to be notified about changes to entities they follow.
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq, Eq, EnumString, Enum)]
+#[strum(serialize_all = "lowercase")]
+pub enum NotificationMode {
+    /// Don't send notifications.
+    #[default]
+    Off,
+    /// Send an email as soon as something changes.
+    Instant,
+    /// Send a daily digest of changes.
+    Daily,
+}
+
+/// A user's notification preference for one kind of entity.
+#[derive(Clone, Copy, Debug, SimpleObject)]
+pub struct NotificationPreference {
+    /// The kind of followed entity this preference applies to.
+    pub kind: FollowKind,
+    /// How the user wants to be notified about changes to followed entities of this kind.
+    pub mode: NotificationMode,
+}
+
+/// Mutations for notification preferences.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NotificationMutation;
+
+#[Object]
+impl NotificationMutation {
+    /// Set how the signed-in user wants to be notified about changes to followed entities of a
+    /// kind.
+    async fn set_notification_preference(
+        &self,
+        ctx: &Context<'_>,
+        kind: FollowKind,
+        mode: NotificationMode,
+    ) -> async_graphql::Result<NotificationPreference> {
+        let user = Viewer::of(ctx).require()?;
+        set_preference(ctx.data()?, user, kind, mode).await?;
+        Ok(NotificationPreference { kind, mode })
+    }
+}
+
+/// Options for delivering email.
+#[derive(Clone, Debug, Args)]
+pub struct SmtpOptions {
+    /// The SMTP server to deliver email through.
+    #[clap(long, env = "BILL_TRACKER_SMTP_HOST", default_value = "localhost")]
+    pub smtp_host: String,
+
+    /// The port of the SMTP server.
+    #[clap(long, env = "BILL_TRACKER_SMTP_PORT", default_value = "25")]
+    pub smtp_port: u16,
+
+    /// User as which to authenticate with the SMTP server.
+    #[clap(long, env = "BILL_TRACKER_SMTP_USER", requires = "smtp_password")]
+    pub smtp_user: Option<String>,
+
+    /// Password for authenticating with the SMTP server.
+    #[clap(long, env = "BILL_TRACKER_SMTP_PASSWORD")]
+    pub smtp_password: Option<String>,
+
+    /// Connect to the SMTP server with STARTTLS.
+    ///
+    /// Without this, email is sent unencrypted, which is only appropriate for a local server.
+    #[clap(long, env = "BILL_TRACKER_SMTP_TLS")]
+    pub smtp_tls: bool,
+
+    /// The address emails are sent from.
+    #[clap(
+        long,
+        env = "BILL_TRACKER_EMAIL_FROM",
+        default_value = "Bill Tracker <notifications@localhost>"
+    )]
+    pub email_from: String,
+
+    /// Base URL of the app, for linking to bills from emails.
+    #[clap(long, env = "BILL_TRACKER_APP_URL")]
+    pub app_url: Option<Url>,
+}
+
+impl SmtpOptions {
+    /// Connect to the SMTP server.
+    pub fn connect(&self) -> Result<Mailer, Error> {
+        let mut transport = if self.smtp_tls {
+            AsyncSmtpTransport::<AsyncStd1Executor>::starttls_relay(&self.smtp_host)?
+        } else {
+            AsyncSmtpTransport::<AsyncStd1Executor>::builder_dangerous(&self.smtp_host)
+        }
+        .port(self.smtp_port);
+        if let (Some(user), Some(password)) = (&self.smtp_user, &self.smtp_password) {
+            transport = transport.credentials(Credentials::new(user.clone(), password.clone()));
+        }
+        Ok(Mailer {
+            transport: transport.build(),
+            from: self.email_from.parse()?,
+            app_url: self.app_url.clone(),
+        })
+    }
+}
+
+/// A connection to an SMTP server.
+pub struct Mailer {
+    transport: AsyncSmtpTransport<AsyncStd1Executor>,
+    from: Mailbox,
+    app_url: Option<Url>,
+}
+
+impl Mailer {
+    async fn send(&self, to: &str, email: Email) -> Result<(), Error> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to.parse()?)
+            .subject(email.subject)
+            .multipart(MultiPart::alternative_plain_html(email.text, email.html))?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+/// Create the tables for notifications.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS notification_preferences (
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            kind TEXT NOT NULL,
+            mode TEXT NOT NULL,
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            PRIMARY KEY (user_id, kind)
+        );
+        CREATE TABLE IF NOT EXISTS email_notifications (
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            event INTEGER NOT NULL REFERENCES feed_events (id) ON DELETE CASCADE,
+            mode TEXT NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            sent_at TIMESTAMPTZ,
+            PRIMARY KEY (user_id, event)
+        );
+        CREATE INDEX IF NOT EXISTS email_notifications_pending ON email_notifications (mode)
+            WHERE sent_at IS NULL;",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Set how `user` wants to be notified about changes to followed entities of `kind`.
+pub async fn set_preference(
+    sql: &SqlClient,
+    user: i32,
+    kind: FollowKind,
+    mode: NotificationMode,
+) -> Result<(), Error> {
+    sql.execute(
+        "INSERT INTO notification_preferences (user_id, kind, mode) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, kind) DO UPDATE SET mode = EXCLUDED.mode, updated_at = now()",
+        &[&user, &kind.to_string(), &mode.to_string()],
+    )
+    .await?;
+    Ok(())
+}
+
+/// `user`'s notification preferences for every kind of entity.
+pub async fn preferences(sql: &SqlClient, user: i32) -> Result<Vec<NotificationPreference>, Error> {
+    let rows = sql
+        .query(
+            "SELECT kind, mode FROM notification_preferences WHERE user_id = $1",
+            &[&user],
+        )
+        .await?;
+    let mut prefs = FollowKind::iter()
+        .map(|kind| NotificationPreference {
+            kind,
+            mode: NotificationMode::Off,
+        })
+        .collect::<Vec<_>>();
+    for row in rows {
+        let kind: FollowKind = row.try_get::<_, String>("kind")?.parse()?;
+        let mode = row.try_get::<_, String>("mode")?.parse()?;
+        if let Some(pref) = prefs.iter_mut().find(|pref| pref.kind == kind) {
+            pref.mode = mode;
+        }
+    }
+    Ok(prefs)
+}
+
+/// Queue notifications for new feed events.
+///
+/// Each user is notified about the events concerning entities they follow, as long as they have
+/// turned on notifications for that kind of entity. If an event concerns several followed entities
+/// with different preferences, the user is notified instantly if any of the preferences are
+/// instant. Only events recorded after the user set their preference are considered.
+///
+/// Returns the number of notifications queued.
+pub async fn enqueue(sql: &SqlClient) -> Result<u64, Error> {
+    Ok(sql
+        .execute(
+            "INSERT INTO email_notifications (user_id, event, mode)
+                SELECT f.user_id, e.id,
+                    CASE WHEN bool_or(p.mode = $1) THEN $1 ELSE $2 END
+                FROM feed_events e
+                JOIN feed_event_subjects s ON s.event = e.id
+                JOIN follows f ON f.kind = s.kind AND f.id = s.id
+                JOIN notification_preferences p ON p.user_id = f.user_id AND p.kind = s.kind
+                WHERE p.mode <> $3
+                    AND e.created_at >= p.updated_at
+                    AND e.created_at > now() - make_interval(days => $4)
+                GROUP BY f.user_id, e.id
+                ON CONFLICT DO NOTHING",
+            &[
+                &NotificationMode::Instant.to_string(),
+                &NotificationMode::Daily.to_string(),
+                &NotificationMode::Off.to_string(),
+                &WINDOW_DAYS,
+            ],
+        )
+        .await?)
+}
+
+/// Email the pending notifications in `mode`.
+///
+/// Notifications which can't be delivered are left pending, to be retried the next time this
+/// runs. Returns the number of emails sent.
+pub async fn send(
+    sql: &SqlClient,
+    mailer: &Mailer,
+    mode: NotificationMode,
+) -> Result<usize, Error> {
+    let rows = sql
+        .query(
+            "SELECT n.user_id, u.email, u.username,
+                e.id AS event, e.kind, e.status, e.detail, e.bill, e.created_at,
+                b.name, b.title, b.state
+            FROM email_notifications n
+            JOIN users u ON u.id = n.user_id
+            JOIN feed_events e ON e.id = n.event
+            LEFT JOIN catalog_bills b ON b.id = e.bill
+            WHERE n.sent_at IS NULL AND n.mode = $1
+            ORDER BY n.user_id, e.id",
+            &[&mode.to_string()],
+        )
+        .await?;
+
+    // Group the notifications by recipient.
+    let mut recipients: Vec<Recipient> = vec![];
+    for row in rows {
+        let user: i32 = row.try_get("user_id")?;
+        let notice = Notice {
+            event: row.try_get("event")?,
+            kind: row.try_get::<_, String>("kind")?.parse()?,
+            status: row.try_get("status")?,
+            detail: row.try_get("detail")?,
+            bill: row.try_get("bill")?,
+            name: row
+                .try_get::<_, Option<String>>("name")?
+                .unwrap_or_default(),
+            title: row
+                .try_get::<_, Option<String>>("title")?
+                .unwrap_or_default(),
+            state: row
+                .try_get::<_, Option<String>>("state")?
+                .unwrap_or_default(),
+            occurred_at: row.try_get("created_at")?,
+        };
+        match recipients.last_mut() {
+            Some(recipient) if recipient.user == user => recipient.notices.push(notice),
+            _ => recipients.push(Recipient {
+                user,
+                email: row.try_get("email")?,
+                username: row.try_get("username")?,
+                notices: vec![notice],
+            }),
+        }
+    }
+
+    let app_url = mailer.app_url.as_ref();
+    let mut sent = 0;
+    for recipient in recipients {
+        let batches = match mode {
+            NotificationMode::Instant => recipient
+                .notices
+                .iter()
+                .map(|notice| {
+                    (
+                        render_instant(&recipient.username, notice, app_url),
+                        vec![notice.event],
+                    )
+                })
+                .collect(),
+            NotificationMode::Daily => vec![(
+                render_digest(&recipient.username, &recipient.notices, app_url),
+                recipient
+                    .notices
+                    .iter()
+                    .map(|notice| notice.event)
+                    .collect(),
+            )],
+            NotificationMode::Off => vec![],
+        };
+        for (email, events) in batches {
+            if let Err(err) = mailer.send(&recipient.email, email).await {
+                tracing::error!("failed to email user {}: {err}", recipient.user);
+                continue;
+            }
+            sql.execute(
+                "UPDATE email_notifications SET sent_at = now()
+                    WHERE user_id = $1 AND event = ANY($2)",
+                &[&recipient.user, &events],
+            )
+            .await?;
+            sent += 1;
+        }
+    }
+    Ok(sent)
+}
+
+/// Queue notifications for new feed events, and then email the pending notifications in `mode`.
+///
+/// Returns the number of emails sent.
+pub async fn notify(
+    sql: &SqlClient,
+    mailer: &Mailer,
+    mode: NotificationMode,
+) -> Result<usize, Error> {
+    let queued = enqueue(sql).await?;
+    tracing::info!("queued {queued} notifications");
+    send(sql, mailer, mode).await
+}
+
+/// Send instant notifications as soon as feed events are recorded.
+///
+/// This runs until the connection to the database is lost.
+pub async fn watch(opt: &db::Options, sql: &SqlClient, mailer: &Mailer) -> Result<(), Error> {
+    let mut changes = Changes::listen(opt).await?.subscribe(Topic::FeedEvents);
+    loop {
+        let sent = notify(sql, mailer, NotificationMode::Instant).await?;
+        tracing::info!("sent {sent} emails");
+
+        // Wait for the next feed event.
+        loop {
+            match changes.next().await {
+                Some(Change::FeedEvent(_)) => break,
+                Some(_) => continue,
+                None => return Err(Error::msg("stopped receiving changes")),
+            }
+        }
+    }
+}
+
+/// A user with pending notifications.
+struct Recipient {
+    user: i32,
+    email: String,
+    username: String,
+    notices: Vec<Notice>,
+}
+
+/// A feed event to notify a user about.
+#[derive(Clone, Debug)]
+struct Notice {
+    event: i32,
+    kind: FeedEventKind,
+    status: String,
+    detail: String,
+    bill: i32,
+    name: String,
+    title: String,
+    state: String,
+    occurred_at: DateTime<Utc>,
+}
+
+impl Notice {
+    /// A one-line summary of the event.
+    fn summary(&self) -> String {
+        self.kind
+            .summary(&self.state, &self.name, &self.status, &self.detail)
+    }
+
+    /// A link to the bill in the app, if the app's URL is known.
+    fn link(&self, app_url: Option<&Url>) -> Option<Url> {
+        app_url?.join(&format!("bills/{}", self.bill)).ok()
+    }
+}
+
+/// The contents of an email.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Email {
+    subject: String,
+    text: String,
+    html: String,
+}
+
+/// Render the email for a single event.
+fn render_instant(username: &str, notice: &Notice, app_url: Option<&Url>) -> Email {
+    render(
+        username,
+        notice.summary(),
+        std::slice::from_ref(notice),
+        app_url,
+    )
+}
+
+/// Render a digest of several events.
+fn render_digest(username: &str, notices: &[Notice], app_url: Option<&Url>) -> Email {
+    let subject = match notices.len() {
+        1 => "1 update on bills you follow".to_string(),
+        n => format!("{n} updates on bills you follow"),
+    };
+    render(username, subject, notices, app_url)
+}
+
+fn render(username: &str, subject: String, notices: &[Notice], app_url: Option<&Url>) -> Email {
+    let mut text = format!("Hi {username},\n\nHere's what changed on the bills you follow:\n\n");
+    let mut html = format!(
+        "<p>Hi {},</p>\n<p>Here's what changed on the bills you follow:</p>\n<ul>\n",
+        escape_html(username)
+    );
+    for notice in notices {
+        let summary = notice.summary();
+        let date = notice.occurred_at.format("%B %-d, %Y");
+        let link = notice.link(app_url);
+
+        // Writing to a string can't fail.
+        writeln!(text, "* {summary} ({date})\n  {}", notice.title).unwrap();
+        if let Some(link) = &link {
+            writeln!(text, "  {link}").unwrap();
+        }
+
+        let summary = escape_html(&summary);
+        let summary = match &link {
+            Some(link) => format!("<a href=\"{}\">{summary}</a>", escape_html(link.as_str())),
+            None => summary,
+        };
+        writeln!(
+            html,
+            "<li><p><strong>{summary}</strong> ({date})<br>{}</p></li>",
+            escape_html(&notice.title)
+        )
+        .unwrap();
+    }
+    text.push_str(
+        "\nYou are receiving this email because you turned on notifications for the \
+        bills, legislators, issues or states you follow. You can turn them off in your settings.\n",
+    );
+    html.push_str(
+        "</ul>\n<p><small>You are receiving this email because you turned on \
+        notifications for the bills, legislators, issues or states you follow. You can turn them \
+        off in your settings.</small></p>\n",
+    );
+    Email {
+        subject,
+        text,
+        html,
+    }
+}
+
+pub(crate) fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_digest() {
+        let notice = Notice {
+            event: 1,
+            kind: FeedEventKind::StatusChanged,
+            status: "Passed".into(),
+            detail: String::new(),
+            bill: 1,
+            name: "HB1".into(),
+            title: "Students <& Teachers>".into(),
+            state: "CO".into(),
+            occurred_at: "2023-03-07T12:00:00Z".parse().unwrap(),
+        };
+        let email = render_digest("ada", &[notice.clone(), notice], None);
+        assert_eq!(email.subject, "2 updates on bills you follow");
+        assert!(email
+            .text
+            .contains("* CO HB1 is now Passed (March 7, 2023)\n  Students <& Teachers>\n"));
+        assert!(email.html.contains("Students &lt;&amp; Teachers&gt;"));
+        assert!(!email.html.contains("<&"));
+    }
+}
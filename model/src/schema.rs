@@ -1,12 +1,37 @@
 //! The schema describing the entities and relationships in the GraphQL API.
 
-use crate::db;
+use crate::{
+    accounts::{AccountMutation, AccountQuery},
+    actions::ActionQuery,
+    catalog::{self, BillFilter, CatalogQuery},
+    comments::{self, Comment, CommentMutation, CommentQuery},
+    db,
+    facets::FacetQuery,
+    feed::FeedQuery,
+    follows::{self, FollowKind, FollowMutation, FollowQuery},
+    hearings::HearingQuery,
+    history::{HistoryMutation, HistoryQuery},
+    legiscan::{self, Status},
+    live::{Changes, Subscription},
+    notifications::NotificationMutation,
+    search::SearchQuery,
+    stances::{self, StanceMutation, StanceQuery, SupporterFilter},
+    stats::{self, BillCounts, StatsQuery},
+    trending::TrendingQuery,
+    votes::VoteQuery,
+    webhooks::{WebhookMutation, WebhookQuery},
+};
 use anyhow::Error;
-use async_graphql::SchemaBuilder;
+use async_graphql::{ComplexObject, Context, MergedObject, SchemaBuilder};
+use chrono::{DateTime, Utc};
 use relational_graphql::prelude::*;
 
-/// A US state.
+/// A US state, or another jurisdiction with its own legislature.
+///
+/// Besides the 50 states, this includes Washington, D.C. and the federal government, whose
+/// legislature is the US Congress.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
+#[graphql(complex)]
 pub struct State {
     pub id: Id,
     /// The 2-letter abbreviation for this state as recognized by the US postal service.
@@ -16,13 +41,63 @@ pub struct State {
     /// The full name of this state.
     #[resource(searchable)]
     pub name: String,
+    /// The 2-digit FIPS code identifying this state.
+    ///
+    /// This is empty for the federal government.
+    #[resource(searchable)]
+    pub fips_code: String,
+    /// The capital city of this state.
+    pub capital: String,
+    /// The name of the state legislature, like "General Assembly".
+    pub legislature: String,
+    /// The name of the upper chamber of the state legislature.
+    ///
+    /// For unicameral legislatures, this is the name of the only chamber.
+    pub upper_chamber: String,
+    /// The name of the lower chamber of the state legislature.
+    ///
+    /// This is empty if the legislature is unicameral.
+    pub lower_chamber: String,
+    /// How often the legislature meets in regular session: "annual" or "biennial".
+    pub session_calendar: String,
+    /// The official website of the state legislature.
+    pub website: String,
     /// Bills introduced in this state.
     pub bills: BelongsTo<Bill>,
     /// Districts making up this state.
     pub districts: BelongsTo<District>,
+    /// Legislators from this state, including the members of Congress it elects.
+    pub legislators: BelongsTo<Legislator>,
+}
+
+#[ComplexObject]
+impl State {
+    /// The bills introduced in this state, counted in total and by status.
+    ///
+    /// If `session` is given, only bills introduced in one of the given sessions are counted.
+    async fn bill_count(
+        &self,
+        ctx: &Context<'_>,
+        session: Option<Vec<String>>,
+    ) -> async_graphql::Result<BillCounts> {
+        let filter = BillFilter {
+            state: Some(vec![self.abbreviation.clone()]),
+            session,
+            ..Default::default()
+        };
+        Ok(stats::bill_counts(ctx.data()?, filter).await?)
+    }
+
+    /// The number of users following the state.
+    async fn follower_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        Ok(follows::follower_count(ctx.data()?, FollowKind::State, self.id).await?)
+    }
 }
 
 /// A subdivision of a [`State`] with its own representatives in the state legislature.
+///
+/// Congressional districts and states (which elect US senators) are modeled as districts of the
+/// federal [`State`], with names like "HD-CA-12" or "SD-CA".
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
 pub struct District {
     pub id: Id,
@@ -39,8 +114,24 @@ pub struct District {
     pub representatives: BelongsTo<Legislator>,
 }
 
+impl District {
+    /// The abbreviation of the state whose residents live in this district.
+    ///
+    /// This is the state containing the district, except for congressional districts, which are
+    /// districts of the federal [`State`] and name their state instead.
+    pub fn home_state(&self) -> &str {
+        if self.state.abbreviation == legiscan::State::US.to_string() {
+            if let Some(state) = self.name.split('-').nth(1) {
+                return state;
+            }
+        }
+        &self.state.abbreviation
+    }
+}
+
 /// A piece of legislation.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
+#[graphql(complex)]
 pub struct Bill {
     pub id: Id,
     /// The ID of this bill in Legiscan.
@@ -67,18 +158,100 @@ pub struct Bill {
     /// the bill. Unlike the text of the bill itself, it is not legally binding.
     #[resource(searchable)]
     pub summary: String,
+    /// The current status of the bill, like "Introduced" or "Passed".
+    #[resource(searchable)]
+    pub status: String,
+    /// The date on which the bill reached its current status, in the form YYYY-MM-DD.
+    #[resource(searchable)]
+    pub status_date: String,
+    /// The name of the legislative session in which this bill was introduced.
+    #[resource(searchable)]
+    pub session: String,
     /// The state in which this bill was introduced.
     #[resource(searchable)]
     pub state: State,
     /// Legislators sponsoring the bill.
     #[resource(inverse(sponsored_bills))]
     pub sponsors: Many<Legislator>,
-    /// Issues that the bill relates to.
+    /// Issues that the bill relates to, according to Legiscan.
+    #[resource(inverse(bills))]
     pub issues: Many<Issue>,
+    /// Issues that the bill may relate to, as suggested automatically from its text.
+    pub suggested_issues: BelongsTo<IssueSuggestion>,
+}
+
+#[ComplexObject]
+impl Bill {
+    /// When the bill was first ingested.
+    async fn created_at(&self, ctx: &Context<'_>) -> async_graphql::Result<DateTime<Utc>> {
+        let (created_at, _) = catalog::bill_timestamps(ctx.data()?, self.id).await?;
+        Ok(created_at)
+    }
+
+    /// When the bill last changed in Legiscan.
+    async fn updated_at(&self, ctx: &Context<'_>) -> async_graphql::Result<DateTime<Utc>> {
+        let (_, updated_at) = catalog::bill_timestamps(ctx.data()?, self.id).await?;
+        Ok(updated_at)
+    }
+
+    /// The number of users following the bill.
+    async fn follower_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        Ok(follows::follower_count(ctx.data()?, FollowKind::Bill, self.id).await?)
+    }
+
+    /// The number of users supporting the bill.
+    ///
+    /// If `filter` is given, only users matching it are counted.
+    async fn support_count(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<SupporterFilter>,
+    ) -> async_graphql::Result<i64> {
+        let counts = stances::stance_counts(ctx.data()?, self.id, filter.unwrap_or_default());
+        Ok(counts.await?.support_count)
+    }
+
+    /// The number of users opposing the bill.
+    ///
+    /// If `filter` is given, only users matching it are counted.
+    async fn oppose_count(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<SupporterFilter>,
+    ) -> async_graphql::Result<i64> {
+        let counts = stances::stance_counts(ctx.data()?, self.id, filter.unwrap_or_default());
+        Ok(counts.await?.oppose_count)
+    }
+
+    /// The number of users who are neutral on the bill.
+    ///
+    /// If `filter` is given, only users matching it are counted.
+    async fn neutral_count(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<SupporterFilter>,
+    ) -> async_graphql::Result<i64> {
+        let counts = stances::stance_counts(ctx.data()?, self.id, filter.unwrap_or_default());
+        Ok(counts.await?.neutral_count)
+    }
+
+    /// The top-level comments on the bill, oldest first.
+    ///
+    /// Replies to each comment are listed by its `replies` field.
+    async fn comments(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<async_graphql::connection::Connection<usize, Comment>> {
+        let (offset, limit) = comments::page(first, after)?;
+        Ok(comments::comments(ctx.data()?, self.id, None, offset, limit).await?)
+    }
 }
 
-/// A state lawmaker.
+/// A state or federal lawmaker.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
+#[graphql(complex)]
 pub struct Legislator {
     pub id: Id,
     /// The ID of this legislator in Legiscan.
@@ -94,9 +267,20 @@ pub struct Legislator {
     /// The legislator's last name.
     #[resource(searchable)]
     pub last_name: String,
-    /// The district in `state` which the legislator represents.
+    /// The district which the legislator represents.
     #[resource(searchable)]
     pub district: District,
+    /// The state the legislator comes from.
+    ///
+    /// For state legislators, this is the state of their `district`. Members of Congress represent
+    /// districts of the federal [`State`], so this is the state which elected them.
+    #[resource(searchable)]
+    pub home_state: State,
+    /// The name of the chamber of the legislature in which the legislator serves.
+    ///
+    /// This is something like "Senate" or "House of Representatives", depending on the state.
+    #[resource(searchable)]
+    pub chamber: String,
     /// The legislator's political party.
     #[resource(searchable)]
     pub party: Party,
@@ -105,11 +289,52 @@ pub struct Legislator {
     pub sponsored_bills: Many<Bill>,
 }
 
+#[ComplexObject]
+impl Legislator {
+    /// When the legislator was first ingested.
+    async fn created_at(&self, ctx: &Context<'_>) -> async_graphql::Result<DateTime<Utc>> {
+        let (created_at, _) = catalog::legislator_timestamps(ctx.data()?, self.id).await?;
+        Ok(created_at)
+    }
+
+    /// When the legislator last changed in Legiscan.
+    async fn updated_at(&self, ctx: &Context<'_>) -> async_graphql::Result<DateTime<Utc>> {
+        let (_, updated_at) = catalog::legislator_timestamps(ctx.data()?, self.id).await?;
+        Ok(updated_at)
+    }
+
+    /// The number of bills the legislator has sponsored.
+    async fn sponsored_bill_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        Ok(stats::sponsored_bill_count(ctx.data()?, self.id, None).await?)
+    }
+
+    /// The number of bills the legislator has sponsored which have passed.
+    async fn passed_bill_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        Ok(stats::sponsored_bill_count(ctx.data()?, self.id, Some(Status::Passed)).await?)
+    }
+
+    /// The bills the legislator has sponsored, counted in total and by status.
+    async fn bill_counts(&self, ctx: &Context<'_>) -> async_graphql::Result<BillCounts> {
+        let filter = BillFilter {
+            sponsor: Some(vec![self.id]),
+            ..Default::default()
+        };
+        Ok(stats::bill_counts(ctx.data()?, filter).await?)
+    }
+
+    /// The number of users following the legislator.
+    async fn follower_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        Ok(follows::follower_count(ctx.data()?, FollowKind::Legislator, self.id).await?)
+    }
+}
+
 /// A political party.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
 #[resource(plural(Parties))]
 pub struct Party {
     pub id: Id,
+    /// The ID of this party in Legiscan.
+    pub legiscan_id: String,
     /// A shortened form of the party's name.
     ///
     /// This is frequently a single letter as in "R" (for Republican) or "D" (for Democrat). For
@@ -125,7 +350,12 @@ pub struct Party {
 }
 
 /// A political issue.
+///
+/// Issues form a hierarchy: broad issues like "Education" have more specific sub-issues like
+/// "Higher Education". The hierarchy is curated, so that issues are consistent across states, even
+/// though each state categorizes its bills differently.
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
+#[graphql(complex)]
 pub struct Issue {
     pub id: Id,
     /// A short name for the issue.
@@ -133,7 +363,84 @@ pub struct Issue {
     #[resource(searchable)]
     pub name: String,
     /// Bills pertaining to this issue.
+    ///
+    /// This only includes bills which are categorized under this specific issue. To find bills
+    /// pertaining to this issue or any of its sub-issues, use `descendants`.
+    #[resource(inverse(issues))]
     pub bills: Many<Bill>,
+    /// Bills which may pertain to this issue, as suggested automatically from their text.
+    pub suggested_bills: BelongsTo<IssueSuggestion>,
+    /// Broader issues which this issue falls under.
+    #[resource(inverse(children))]
+    pub parents: Many<Issue>,
+    /// More specific issues which fall under this issue.
+    #[resource(inverse(parents))]
+    pub children: Many<Issue>,
+    /// This issue, its parents, their parents, and so on.
+    #[resource(inverse(descendants))]
+    pub ancestors: Many<Issue>,
+    /// This issue, its children, their children, and so on.
+    #[resource(inverse(ancestors))]
+    pub descendants: Many<Issue>,
+    /// Legiscan subjects which are categorized under this issue.
+    pub subjects: BelongsTo<Subject>,
+}
+
+#[ComplexObject]
+impl Issue {
+    /// The bills pertaining to this issue or any of its sub-issues, counted in total and by status.
+    ///
+    /// If `session` is given, only bills introduced in one of the given sessions are counted.
+    async fn bill_count(
+        &self,
+        ctx: &Context<'_>,
+        session: Option<Vec<String>>,
+    ) -> async_graphql::Result<BillCounts> {
+        let filter = BillFilter {
+            within_issue: Some(vec![self.name.clone()]),
+            session,
+            ..Default::default()
+        };
+        Ok(stats::bill_counts(ctx.data()?, filter).await?)
+    }
+
+    /// The number of users following the issue.
+    async fn follower_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        Ok(follows::follower_count(ctx.data()?, FollowKind::Issue, self.id).await?)
+    }
+}
+
+/// A subject, or topic, used by Legiscan to categorize bills.
+///
+/// Legiscan subjects are not consistent from state to state. Each subject is mapped to a canonical
+/// [`Issue`], and bills with that subject are categorized under that issue.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
+pub struct Subject {
+    pub id: Id,
+    /// The name of the subject in Legiscan.
+    #[resource(primary)]
+    #[resource(searchable)]
+    pub name: String,
+    /// The issue which this subject is categorized under.
+    #[resource(searchable)]
+    pub issue: Issue,
+}
+
+/// An issue which a bill may pertain to, as suggested automatically from the text of the bill.
+///
+/// Unlike [`Bill::issues`], which come from Legiscan, these suggestions are generated by a
+/// [`Classifier`](crate::classifier::Classifier), and may be inaccurate.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Resource)]
+pub struct IssueSuggestion {
+    pub id: Id,
+    /// The bill which may pertain to `issue`.
+    #[resource(searchable)]
+    pub bill: Bill,
+    /// The issue suggested for `bill`.
+    #[resource(searchable)]
+    pub issue: Issue,
+    /// How confident the classifier is in this suggestion, as a percentage.
+    pub confidence: i32,
 }
 
 /// Entrypoint for read-only GraphQL queries.
@@ -144,20 +451,105 @@ pub struct Issue {
 #[query(resource(districts: District))]
 #[query(resource(parties: Party))]
 #[query(resource(issues: Issue))]
+#[query(resource(subjects: Subject))]
+#[query(resource(issue_suggestions: IssueSuggestion))]
 pub struct Query;
 
+/// Entrypoint for all GraphQL queries.
+///
+/// This combines the queries for resources in [`Query`] with queries which are not expressible in
+/// terms of resources, like full-text search, recent bills, facet counts, bill counts, trending
+/// bills, users' feeds and histories, the actions, votes and hearings on bills, stances and
+/// comments on bills, and webhooks.
+#[derive(MergedObject)]
+#[graphql(name = "Query")]
+pub struct QueryRoot(
+    Query,
+    SearchQuery,
+    CatalogQuery,
+    FacetQuery,
+    StatsQuery,
+    AccountQuery,
+    FollowQuery,
+    FeedQuery,
+    TrendingQuery,
+    HistoryQuery,
+    ActionQuery,
+    VoteQuery,
+    HearingQuery,
+    StanceQuery,
+    CommentQuery,
+    WebhookQuery,
+);
+
+/// Entrypoint for all GraphQL mutations.
+///
+/// The resources in [`Query`] are read-only; mutations act on the data owned by users, like their
+/// accounts, the entities they follow, their notification preferences, their browsing history,
+/// their stances and comments on bills, and their webhooks.
+#[derive(Default, MergedObject)]
+#[graphql(name = "Mutation")]
+pub struct MutationRoot(
+    AccountMutation,
+    FollowMutation,
+    HistoryMutation,
+    StanceMutation,
+    CommentMutation,
+    NotificationMutation,
+    WebhookMutation,
+);
+
+/// The GraphQL API.
+pub type Api = Schema<QueryRoot, MutationRoot, Subscription>;
+
 /// Create the schema for the GraphQL API.
-pub fn generate() -> Schema<Query, EmptyMutation, EmptySubscription> {
+pub fn generate() -> Api {
     build().finish()
 }
 
 /// Create an executor for the GraphQL API backed by a Postgres database.
-pub async fn executor(
-    opt: &db::Options,
-) -> Result<Schema<Query, EmptyMutation, EmptySubscription>, Error> {
-    Ok(build().data(opt.connect().await?).finish())
+pub async fn executor(opt: &db::Options) -> Result<Api, Error> {
+    Ok(build()
+        .data(opt.connect().await?)
+        .data(opt.connect_sql().await?)
+        .data(Changes::listen(opt).await?)
+        .finish())
+}
+
+/// The read-only API for the resources in [`Query`], without the supplementary queries.
+///
+/// This resolves relations between resources, like the sponsors of bills, straight from a
+/// [`Connection`](db::Connection), for bulk loads like [exports](crate::export).
+pub type ResourceApi = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Create an executor for the resource API backed by a Postgres database.
+pub async fn resource_executor(opt: &db::Options) -> Result<ResourceApi, Error> {
+    Ok(Schema::build(Query, EmptyMutation, EmptySubscription)
+        .data(opt.connect().await?)
+        .finish())
 }
 
-fn build() -> SchemaBuilder<Query, EmptyMutation, EmptySubscription> {
-    Schema::build(Query, EmptyMutation, EmptySubscription)
+fn build() -> SchemaBuilder<QueryRoot, MutationRoot, Subscription> {
+    Schema::build(
+        QueryRoot(
+            Query,
+            SearchQuery,
+            CatalogQuery,
+            FacetQuery,
+            StatsQuery,
+            AccountQuery,
+            FollowQuery,
+            FeedQuery,
+            TrendingQuery,
+            HistoryQuery,
+            ActionQuery,
+            VoteQuery,
+            HearingQuery,
+            StanceQuery,
+            CommentQuery,
+            WebhookQuery,
+        ),
+        MutationRoot::default(),
+        Subscription,
+    )
 }
@@ -0,0 +1,410 @@
+//! Full-text search over bills, legislators and issues.
+//!
+//! Searchable text is kept in a supplementary table, `search_index`, with one row per entity. Each
+//! row has a title (like the title of a bill or the name of a legislator) and a body (like the
+//! summary of a bill), from which Postgres maintains a weighted `tsvector` with a GIN index. Matches
+//! in the title count for more than matches in the body.
+//!
+//! Queries use Postgres' web search syntax, so "student president" matches "Student Body
+//! President.", and quoted phrases and `-excluded` words work as users expect. Results are ranked
+//! by relevance and come with a snippet of the matching text, with matches highlighted.
+//!
+//! The titles are also indexed by trigrams, for typo-tolerant autocompletion as the user types.
+//!
+//! [`db::update`] indexes bills and legislators as they are inserted, and indexes them again when
+//! they change. If the index ever does get out of date, [`reindex`] (or `db reindex`) rebuilds it
+//! from scratch.
+
+use crate::{
+    db::{self, SqlClient},
+    follows::{Entity, FollowKind},
+    notifications::escape_html,
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{ComplexObject, Context, Enum, Object, SimpleObject};
+use futures::future::{try_join3, try_join_all};
+use relational_graphql::graphql::type_system::Id;
+use strum::{Display, EnumString};
+
+/// The text search configuration used for stemming and stop words.
+const LANGUAGE: &str = "english";
+
+/// The maximum number of results returned by a single search.
+const MAX_RESULTS: i64 = 100;
+
+/// The maximum number of suggestions returned by a single autocomplete query.
+const MAX_SUGGESTIONS: i64 = 50;
+
+/// The markers Postgres puts around matches in snippets.
+///
+/// These are private-use characters, which are removed from the text before it is highlighted, so
+/// that they can be told apart from the text when it is escaped. See [`highlight`].
+const START_MATCH: char = '\u{e000}';
+const STOP_MATCH: char = '\u{e001}';
+
+/// The kinds of entities which can be searched for.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString, Enum)]
+#[strum(serialize_all = "lowercase")]
+pub enum SearchKind {
+    Bill,
+    Legislator,
+    Issue,
+}
+
+impl From<SearchKind> for FollowKind {
+    fn from(kind: SearchKind) -> Self {
+        match kind {
+            SearchKind::Bill => Self::Bill,
+            SearchKind::Legislator => Self::Legislator,
+            SearchKind::Issue => Self::Issue,
+        }
+    }
+}
+
+/// A single search result.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct SearchResult {
+    /// The kind of entity which matched the search.
+    pub kind: SearchKind,
+    /// How relevant this result is to the search; higher is more relevant.
+    pub rank: f32,
+    /// An excerpt of the matching text as HTML, with matches enclosed in `<mark>` tags.
+    ///
+    /// Any markup in the text itself is escaped.
+    pub snippet: String,
+    #[graphql(skip)]
+    pub id: Id,
+}
+
+#[ComplexObject]
+impl SearchResult {
+    /// The matching bill, if `kind` is `BILL`.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        self.entity().bill(ctx).await
+    }
+
+    /// The matching legislator, if `kind` is `LEGISLATOR`.
+    async fn legislator(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::Legislator>> {
+        self.entity().legislator(ctx).await
+    }
+
+    /// The matching issue, if `kind` is `ISSUE`.
+    async fn issue(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Issue>> {
+        self.entity().issue(ctx).await
+    }
+}
+
+impl SearchResult {
+    fn entity(&self) -> Entity {
+        Entity {
+            kind: self.kind.into(),
+            id: self.id,
+        }
+    }
+}
+
+/// An autocompletion suggestion.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Suggestion {
+    /// The kind of entity being suggested.
+    pub kind: SearchKind,
+    /// The name of the suggested entity, suitable for display in a list of suggestions.
+    pub text: String,
+    /// How closely the suggestion matches what the user has typed, between 0 and 1.
+    pub score: f32,
+    #[graphql(skip)]
+    pub id: Id,
+}
+
+#[ComplexObject]
+impl Suggestion {
+    /// The suggested bill, if `kind` is `BILL`.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        self.entity().bill(ctx).await
+    }
+
+    /// The suggested legislator, if `kind` is `LEGISLATOR`.
+    async fn legislator(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Option<schema::Legislator>> {
+        self.entity().legislator(ctx).await
+    }
+
+    /// The suggested issue, if `kind` is `ISSUE`.
+    async fn issue(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Issue>> {
+        self.entity().issue(ctx).await
+    }
+}
+
+impl Suggestion {
+    fn entity(&self) -> Entity {
+        Entity {
+            kind: self.kind.into(),
+            id: self.id,
+        }
+    }
+}
+
+/// Full-text search queries.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SearchQuery;
+
+#[Object]
+impl SearchQuery {
+    /// Search for bills, legislators and issues matching free-form text.
+    ///
+    /// Results are ordered by relevance, most relevant first. If `kinds` is given, only entities of
+    /// those kinds are returned.
+    async fn search(
+        &self,
+        ctx: &Context<'_>,
+        text: String,
+        kinds: Option<Vec<SearchKind>>,
+        #[graphql(default = 20)] first: i32,
+    ) -> async_graphql::Result<Vec<SearchResult>> {
+        Ok(search(ctx.data()?, &text, kinds.as_deref(), first.into()).await?)
+    }
+
+    /// Suggest bills, legislators and issues whose names match a partially typed `prefix`.
+    ///
+    /// This is fast enough to call on every keystroke, and tolerates typos. Names which start with
+    /// `prefix` are suggested first, followed by close matches.
+    async fn suggest(
+        &self,
+        ctx: &Context<'_>,
+        prefix: String,
+        #[graphql(default = 10)] limit: i32,
+    ) -> async_graphql::Result<Vec<Suggestion>> {
+        Ok(suggest(ctx.data()?, &prefix, limit.into()).await?)
+    }
+}
+
+/// Create the search index.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(&format!(
+        "CREATE TABLE IF NOT EXISTS search_index (
+            kind TEXT NOT NULL,
+            id INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            document tsvector GENERATED ALWAYS AS (
+                setweight(to_tsvector('{LANGUAGE}', title), 'A') ||
+                setweight(to_tsvector('{LANGUAGE}', body), 'B')
+            ) STORED,
+            PRIMARY KEY (kind, id)
+        );
+        CREATE INDEX IF NOT EXISTS search_index_document ON search_index USING GIN (document);
+        CREATE EXTENSION IF NOT EXISTS pg_trgm;
+        CREATE INDEX IF NOT EXISTS search_index_title
+            ON search_index USING GIN (title gin_trgm_ops);"
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Add a bill to the search index, or update it if it is already indexed.
+pub async fn index_bill(sql: &SqlClient, bill: &schema::Bill) -> Result<(), Error> {
+    index(
+        sql,
+        SearchKind::Bill,
+        bill.id,
+        &format!("{} {}", bill.name, bill.title),
+        &bill.summary,
+    )
+    .await
+}
+
+/// Add a legislator to the search index, or update it if it is already indexed.
+pub async fn index_legislator(
+    sql: &SqlClient,
+    legislator: &schema::Legislator,
+) -> Result<(), Error> {
+    let name = [
+        &legislator.first_name,
+        &legislator.middle_name,
+        &legislator.last_name,
+    ]
+    .into_iter()
+    .filter(|name| !name.is_empty())
+    .cloned()
+    .collect::<Vec<_>>()
+    .join(" ");
+    index(sql, SearchKind::Legislator, legislator.id, &name, "").await
+}
+
+/// Add an issue to the search index, or update it if it is already indexed.
+pub async fn index_issue(sql: &SqlClient, id: Id, name: &str) -> Result<(), Error> {
+    index(sql, SearchKind::Issue, id, name, "").await
+}
+
+async fn index(
+    sql: &SqlClient,
+    kind: SearchKind,
+    id: Id,
+    title: &str,
+    body: &str,
+) -> Result<(), Error> {
+    sql.execute(
+        "INSERT INTO search_index (kind, id, title, body) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (kind, id) DO UPDATE SET title = EXCLUDED.title, body = EXCLUDED.body",
+        &[&kind.to_string(), &id, &title, &body],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Rebuild the search index from the bills, legislators and issues in the database.
+///
+/// The index is replaced in a single transaction, so concurrent searches see either the old index
+/// or the new one.
+pub async fn reindex(conn: &db::Connection, sql: &SqlClient) -> Result<(), Error> {
+    let (bills, legislators, issues) = try_join3(
+        db::find_all::<schema::Bill>(conn),
+        db::find_all::<schema::Legislator>(conn),
+        db::find_all::<schema::Issue>(conn),
+    )
+    .await?;
+    tracing::info!(
+        "indexing {} bills, {} legislators and {} issues",
+        bills.len(),
+        legislators.len(),
+        issues.len()
+    );
+
+    sql.batch_execute("BEGIN").await?;
+    let res = async {
+        sql.execute("DELETE FROM search_index", &[]).await?;
+        try_join3(
+            try_join_all(bills.iter().map(|bill| index_bill(sql, bill))),
+            try_join_all(
+                legislators
+                    .iter()
+                    .map(|legislator| index_legislator(sql, legislator)),
+            ),
+            try_join_all(
+                issues
+                    .iter()
+                    .map(|issue| index_issue(sql, issue.id, &issue.name)),
+            ),
+        )
+        .await?;
+        Ok::<_, Error>(())
+    }
+    .await;
+    match res {
+        Ok(()) => sql.batch_execute("COMMIT").await?,
+        Err(err) => {
+            sql.batch_execute("ROLLBACK").await?;
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Search for entities matching `text`, most relevant first.
+pub async fn search(
+    sql: &SqlClient,
+    text: &str,
+    kinds: Option<&[SearchKind]>,
+    limit: i64,
+) -> Result<Vec<SearchResult>, Error> {
+    let kinds = kinds.map(|kinds| kinds.iter().map(SearchKind::to_string).collect::<Vec<_>>());
+    let rows = sql
+        .query(
+            format!(
+                "SELECT kind, id, ts_rank_cd(document, query) AS rank,
+                    ts_headline(
+                        '{LANGUAGE}',
+                        translate(
+                            concat_ws(' ', title, NULLIF(body, '')),
+                            '{START_MATCH}{STOP_MATCH}',
+                            ''
+                        ),
+                        query,
+                        'StartSel="{START_MATCH}", StopSel="{STOP_MATCH}", MaxFragments=2'
+                    ) AS snippet
+                FROM search_index, websearch_to_tsquery('{LANGUAGE}', $1) query
+                WHERE document @@ query AND ($2::TEXT[] IS NULL OR kind = ANY($2))
+                ORDER BY rank DESC, kind, id
+                LIMIT $3"
+            )
+            .as_str(),
+            &[&text, &kinds, &limit.clamp(0, MAX_RESULTS)],
+        )
+        .await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(SearchResult {
+                kind: row.try_get::<_, String>("kind")?.parse()?,
+                id: row.try_get("id")?,
+                rank: row.try_get("rank")?,
+                snippet: highlight(row.try_get("snippet")?),
+            })
+        })
+        .collect()
+}
+
+/// Turn a snippet from Postgres into HTML, escaping the text and marking the matches.
+fn highlight(snippet: &str) -> String {
+    escape_html(snippet)
+        .replace(START_MATCH, "<mark>")
+        .replace(STOP_MATCH, "</mark>")
+}
+
+/// Suggest entities whose titles match `prefix`, best matches first.
+pub async fn suggest(sql: &SqlClient, prefix: &str, limit: i64) -> Result<Vec<Suggestion>, Error> {
+    let prefix = prefix.trim();
+    if prefix.is_empty() {
+        return Ok(vec![]);
+    }
+
+    // Titles which start with `prefix` are the best matches, followed by titles containing a word
+    // similar to `prefix`. Both conditions can use the trigram index.
+    let pattern = format!(
+        "{}%",
+        prefix
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    let rows = sql
+        .query(
+            "SELECT kind, id, title, word_similarity($1, title) AS score
+                FROM search_index
+                WHERE title ILIKE $2 OR $1 <% title
+                ORDER BY title ILIKE $2 DESC, score DESC, length(title), title
+                LIMIT $3",
+            &[&prefix, &pattern, &limit.clamp(0, MAX_SUGGESTIONS)],
+        )
+        .await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(Suggestion {
+                kind: row.try_get::<_, String>("kind")?.parse()?,
+                id: row.try_get("id")?,
+                text: row.try_get("title")?,
+                score: row.try_get("score")?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_highlight() {
+        assert_eq!(
+            highlight("Ban <script> tags in \u{e000}student\u{e001} & staff newsletters"),
+            "Ban &lt;script&gt; tags in <mark>student</mark> &amp; staff newsletters"
+        );
+    }
+}
@@ -0,0 +1,334 @@
+//! Users' public stances on bills.
+//!
+//! A stance records whether a user supports, opposes or is neutral on a bill, with an optional
+//! reason. Stances are public: anyone can see who took which stance. They are kept in a
+//! supplementary table, `stances`, alongside the [accounts](crate::accounts) of their users.
+//!
+//! Counts of stances on each [bill](schema::Bill) can be narrowed to users living in particular
+//! states or districts, so that a legislator's constituents can be told apart from everyone else.
+
+use crate::{
+    accounts::{self, User, Viewer},
+    catalog::Params,
+    db::{self, SqlClient},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{
+    connection::{Connection, CursorType, Edge},
+    ComplexObject, Context, Enum, InputObject, Object, SimpleObject,
+};
+use chrono::{DateTime, Utc};
+use relational_graphql::graphql::type_system::Id;
+use strum::{Display, EnumString};
+
+/// The maximum number of stances returned in a single page.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// The maximum length of the reason for a stance, in characters.
+const MAX_REASON_LEN: usize = 1000;
+
+/// A position on a bill.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString, Enum)]
+#[strum(serialize_all = "lowercase")]
+pub enum Position {
+    Support,
+    Oppose,
+    Neutral,
+}
+
+/// A user's stance on a bill.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Stance {
+    /// Whether the user supports or opposes the bill.
+    pub position: Position,
+    /// The user's reason for their stance, if they gave one.
+    pub reason: Option<String>,
+    /// When the user first took a stance on the bill.
+    pub created_at: DateTime<Utc>,
+    /// When the user last changed their stance.
+    pub updated_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub user: i32,
+    #[graphql(skip)]
+    pub bill: Id,
+}
+
+#[ComplexObject]
+impl Stance {
+    /// The user who took this stance.
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        Ok(accounts::user_by_id(ctx.data()?, self.user).await?)
+    }
+
+    /// The bill this stance is on.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        Ok(db::bill_by_id(ctx.data()?, self.bill).await?)
+    }
+}
+
+/// Criteria for selecting the users whose stances are counted.
+///
+/// Each criterion which is given narrows the selection to users matching any of the given values.
+/// Users who have not set their district never match a criterion.
+#[derive(Clone, Debug, Default, InputObject)]
+pub struct SupporterFilter {
+    /// Abbreviations of states the users live in.
+    ///
+    /// Users living in a congressional district count as living in the state it belongs to, like
+    /// CO for "HD-CO-2", rather than the federal state.
+    pub state: Option<Vec<String>>,
+    /// IDs of districts the users live in.
+    pub district: Option<Vec<Id>>,
+}
+
+impl SupporterFilter {
+    /// Build a condition on `users u` selecting users who match this filter.
+    fn sql(&self, params: &mut Params) -> String {
+        let mut conditions = vec!["TRUE".to_string()];
+        if let Some(states) = &self.state {
+            conditions.push(format!("u.state = ANY({})", params.bind(states.clone())));
+        }
+        if let Some(districts) = &self.district {
+            conditions.push(format!(
+                "u.district = ANY({})",
+                params.bind(districts.clone())
+            ));
+        }
+        conditions.join(" AND ")
+    }
+}
+
+/// Counts of the stances on a bill.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StanceCounts {
+    /// The number of users supporting the bill.
+    pub support_count: i64,
+    /// The number of users opposing the bill.
+    pub oppose_count: i64,
+    /// The number of users who are neutral on the bill.
+    pub neutral_count: i64,
+}
+
+/// Queries about stances.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StanceQuery;
+
+#[Object]
+impl StanceQuery {
+    /// The stances on a bill, most recently changed first.
+    ///
+    /// If `position` is given, only stances with that position are listed.
+    async fn stances(
+        &self,
+        ctx: &Context<'_>,
+        bill: Id,
+        position: Option<Position>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<usize, Stance>> {
+        let offset = match after {
+            Some(cursor) => usize::decode_cursor(&cursor)? + 1,
+            None => 0,
+        };
+        let limit = first.map(i64::from).unwrap_or(MAX_PAGE_SIZE);
+        Ok(stances(ctx.data()?, bill, position, offset, limit).await?)
+    }
+
+    /// The signed-in user's stance on a bill, or `null` if they have not taken one.
+    async fn my_stance(
+        &self,
+        ctx: &Context<'_>,
+        bill: Id,
+    ) -> async_graphql::Result<Option<Stance>> {
+        match Viewer::of(ctx).user {
+            Some(user) => Ok(stance(ctx.data()?, user, bill).await?),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Mutations for taking stances.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StanceMutation;
+
+#[Object]
+impl StanceMutation {
+    /// Take a stance on a bill as the signed-in user, replacing any previous stance on it.
+    async fn set_stance(
+        &self,
+        ctx: &Context<'_>,
+        bill: Id,
+        position: Position,
+        reason: Option<String>,
+    ) -> async_graphql::Result<Stance> {
+        let user = Viewer::of(ctx).require()?;
+        if db::bill_by_id(ctx.data()?, bill).await?.is_none() {
+            return Err(format!("no such bill {bill:?}").into());
+        }
+        Ok(set_stance(ctx.data()?, user, bill, position, reason).await?)
+    }
+
+    /// Withdraw the signed-in user's stance on a bill.
+    ///
+    /// Returns whether the user had taken a stance.
+    async fn clear_stance(&self, ctx: &Context<'_>, bill: Id) -> async_graphql::Result<bool> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(clear_stance(ctx.data()?, user, bill).await?)
+    }
+}
+
+/// Create the table of stances.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS stances (
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            bill INTEGER NOT NULL,
+            position TEXT NOT NULL,
+            reason TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            PRIMARY KEY (user_id, bill)
+        );
+        CREATE INDEX IF NOT EXISTS stances_bill ON stances (bill, position);",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Set `user`'s stance on `bill`.
+pub async fn set_stance(
+    sql: &SqlClient,
+    user: i32,
+    bill: Id,
+    position: Position,
+    reason: Option<String>,
+) -> Result<Stance, Error> {
+    let reason = reason
+        .map(|reason| reason.trim().to_string())
+        .filter(|reason| !reason.is_empty());
+    if let Some(reason) = &reason {
+        if reason.chars().count() > MAX_REASON_LEN {
+            return Err(Error::msg(format!(
+                "reason must be at most {MAX_REASON_LEN} characters"
+            )));
+        }
+    }
+    let row = sql
+        .query_one(
+            "INSERT INTO stances (user_id, bill, position, reason) VALUES ($1, $2, $3, $4)
+                ON CONFLICT (user_id, bill) DO UPDATE SET
+                    position = EXCLUDED.position,
+                    reason = EXCLUDED.reason,
+                    updated_at = now()
+                RETURNING user_id, bill, position, reason, created_at, updated_at",
+            &[&user, &bill, &position.to_string(), &reason],
+        )
+        .await?;
+    stance_from_row(&row)
+}
+
+/// Withdraw `user`'s stance on `bill`.
+///
+/// Returns whether the user had taken a stance.
+pub async fn clear_stance(sql: &SqlClient, user: i32, bill: Id) -> Result<bool, Error> {
+    let deleted = sql
+        .execute(
+            "DELETE FROM stances WHERE user_id = $1 AND bill = $2",
+            &[&user, &bill],
+        )
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// Load `user`'s stance on `bill`, if they have taken one.
+pub async fn stance(sql: &SqlClient, user: i32, bill: Id) -> Result<Option<Stance>, Error> {
+    sql.query_opt(
+        "SELECT user_id, bill, position, reason, created_at, updated_at FROM stances
+            WHERE user_id = $1 AND bill = $2",
+        &[&user, &bill],
+    )
+    .await?
+    .map(|row| stance_from_row(&row))
+    .transpose()
+}
+
+/// Count the stances on `bill` by users matching `filter`.
+pub async fn stance_counts(
+    sql: &SqlClient,
+    bill: Id,
+    filter: SupporterFilter,
+) -> Result<StanceCounts, Error> {
+    let mut params = Params::default();
+    let bill = params.bind(bill);
+    let support = params.bind(Position::Support.to_string());
+    let oppose = params.bind(Position::Oppose.to_string());
+    let neutral = params.bind(Position::Neutral.to_string());
+    let row = sql
+        .query_one(
+            format!(
+                "SELECT
+                    count(*) FILTER (WHERE s.position = {support}) AS support,
+                    count(*) FILTER (WHERE s.position = {oppose}) AS oppose,
+                    count(*) FILTER (WHERE s.position = {neutral}) AS neutral
+                FROM stances s
+                JOIN users u ON u.id = s.user_id
+                WHERE s.bill = {bill} AND {}",
+                filter.sql(&mut params)
+            )
+            .as_str(),
+            &params.as_refs(),
+        )
+        .await?;
+    Ok(StanceCounts {
+        support_count: row.try_get("support")?,
+        oppose_count: row.try_get("oppose")?,
+        neutral_count: row.try_get("neutral")?,
+    })
+}
+
+/// Load a page of the stances on `bill`, most recently changed first.
+pub async fn stances(
+    sql: &SqlClient,
+    bill: Id,
+    position: Option<Position>,
+    offset: usize,
+    limit: i64,
+) -> Result<Connection<usize, Stance>, Error> {
+    let limit = limit.clamp(0, MAX_PAGE_SIZE);
+    // Fetch one more stance than requested, to find out if there is a next page.
+    let rows = sql
+        .query(
+            "SELECT user_id, bill, position, reason, created_at, updated_at FROM stances
+                WHERE bill = $1 AND ($2::TEXT IS NULL OR position = $2)
+                ORDER BY updated_at DESC, user_id
+                LIMIT $3 OFFSET $4",
+            &[
+                &bill,
+                &position.map(|position| position.to_string()),
+                &(limit + 1),
+                &(offset as i64),
+            ],
+        )
+        .await?;
+    let has_next = rows.len() as i64 > limit;
+    let mut connection = Connection::new(offset > 0, has_next);
+    for (i, row) in rows.iter().take(limit as usize).enumerate() {
+        connection
+            .edges
+            .push(Edge::new(offset + i, stance_from_row(row)?));
+    }
+    Ok(connection)
+}
+
+fn stance_from_row(row: &tokio_postgres::Row) -> Result<Stance, Error> {
+    Ok(Stance {
+        user: row.try_get("user_id")?,
+        bill: row.try_get("bill")?,
+        position: row.try_get::<_, String>("position")?.parse()?,
+        reason: row.try_get("reason")?,
+        created_at: row.try_get("created_at")?,
+        updated_at: row.try_get("updated_at")?,
+    })
+}
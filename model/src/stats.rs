@@ -0,0 +1,104 @@
+//! Aggregate statistics about bills and legislators.
+//!
+//! Like [facets](crate::facets), these are computed in SQL from the [catalog](crate::catalog)
+//! tables, so that answering "how many bills has this legislator sponsored?" doesn't require paging
+//! through every bill.
+//!
+//! The counts are exposed as fields of the resources, like `Legislator.sponsoredBillCount` and
+//! `State.billCount`, whose resolvers in [`schema`](crate::schema) call into this module. For
+//! other selections, [`bill_counts`] takes the same filter as the [bill facets](crate::facets),
+//! and counts the matching bills in total and by status.
+
+use crate::{
+    catalog::{BillFilter, Params},
+    db::SqlClient,
+    legiscan::Status,
+};
+use anyhow::Error;
+use async_graphql::{Context, Object, SimpleObject};
+use relational_graphql::graphql::type_system::Id;
+
+/// The number of bills with a particular status.
+#[derive(Clone, Debug, SimpleObject)]
+pub struct StatusCount {
+    /// The status, like "Introduced" or "Passed".
+    pub status: String,
+    /// The number of bills with this status.
+    pub count: i64,
+}
+
+/// Counts of a selection of bills.
+#[derive(Clone, Debug, Default, SimpleObject)]
+pub struct BillCounts {
+    /// The total number of bills.
+    pub total_count: i64,
+    /// The number of bills with each status, in alphabetical order of status.
+    pub by_status: Vec<StatusCount>,
+}
+
+/// Queries for aggregate statistics.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StatsQuery;
+
+#[Object]
+impl StatsQuery {
+    /// Count the bills matching `filter`, in total and by status.
+    ///
+    /// For example, filtering by `issue` gives the number of bills pertaining to an issue, and
+    /// filtering by `state` and `session` gives the number of bills introduced in a state's
+    /// legislature during a session.
+    async fn bill_counts(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<BillFilter>,
+    ) -> async_graphql::Result<BillCounts> {
+        Ok(bill_counts(ctx.data()?, filter.unwrap_or_default()).await?)
+    }
+}
+
+/// Count the bills matching `filter`, in total and by status.
+pub async fn bill_counts(sql: &SqlClient, filter: BillFilter) -> Result<BillCounts, Error> {
+    let mut params = Params::default();
+    let rows = sql
+        .query(
+            format!(
+                "SELECT b.status, count(*) AS count FROM catalog_bills b WHERE {}
+                    GROUP BY b.status
+                    ORDER BY b.status",
+                filter.sql(&mut params)
+            )
+            .as_str(),
+            &params.as_refs(),
+        )
+        .await?;
+    let by_status = rows
+        .into_iter()
+        .map(|row| {
+            Ok(StatusCount {
+                status: row.try_get("status")?,
+                count: row.try_get("count")?,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(BillCounts {
+        total_count: by_status.iter().map(|status| status.count).sum(),
+        by_status,
+    })
+}
+
+/// Count the bills `legislator` has sponsored, or only those with `status` if it is given.
+pub async fn sponsored_bill_count(
+    sql: &SqlClient,
+    legislator: Id,
+    status: Option<Status>,
+) -> Result<i64, Error> {
+    let row = sql
+        .query_one(
+            "SELECT count(*) AS count FROM catalog_sponsors s
+                JOIN catalog_bills b ON b.id = s.bill
+                WHERE s.legislator = $1 AND ($2::TEXT IS NULL OR b.status = $2)",
+            &[&legislator, &status.map(|status| status.to_string())],
+        )
+        .await?;
+    Ok(row.try_get("count")?)
+}
@@ -0,0 +1,482 @@
+//! Atom and RSS feeds of changes to bills, for feed readers.
+//!
+//! There is a feed for each bill, legislator, issue and state, listing the latest
+//! [actions](crate::actions) taken on the bills concerning it, like being introduced, referred to a
+//! committee or passed. The feed for an issue includes the bills about its sub-issues. Entries are
+//! dated by when the actions happened in the legislature, not when they were pulled from Legiscan,
+//! so feeds stay accurate after a bulk import. The feeds themselves are dated by when they last
+//! changed, so that readers polling them notice actions which are pulled late. Unlike the
+//! personalized feeds in [`feed`](crate::feed), these are public, and are served over plain HTTP
+//! rather than GraphQL, so that any feed reader can subscribe to them.
+
+use crate::{catalog::Params, db::SqlClient, legiscan::State, notifications::escape_html};
+use anyhow::Error;
+use chrono::{DateTime, SecondsFormat, Utc};
+use std::fmt::Write;
+use std::time::SystemTime;
+use strum::{Display, EnumString};
+use surf::Url;
+
+/// The maximum number of entries in a feed.
+const MAX_ENTRIES: i64 = 50;
+
+/// The entity a feed is about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Subject {
+    /// The bill with the given ID.
+    Bill(i32),
+    /// The legislator with the given ID.
+    Legislator(i32),
+    /// The issue with the given name.
+    Issue(String),
+    /// The state with the given abbreviation.
+    State(String),
+}
+
+impl Subject {
+    /// Interpret the kind and key of a subject, as they appear in the path of a feed.
+    ///
+    /// Returns [`None`] if `kind` is not a kind of subject, or `key` can't identify one.
+    pub fn parse(kind: &str, key: &str) -> Option<Self> {
+        match kind {
+            "bill" => key.parse().ok().map(Self::Bill),
+            "legislator" => key.parse().ok().map(Self::Legislator),
+            "issue" => Some(Self::Issue(key.into())),
+            "state" => Some(Self::State(key.to_uppercase())),
+            _ => None,
+        }
+    }
+}
+
+/// A syndication format.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString)]
+#[strum(serialize_all = "lowercase")]
+pub enum Format {
+    Atom,
+    Rss,
+}
+
+impl Format {
+    /// The MIME type of feeds in this format.
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Self::Atom => "application/atom+xml; charset=utf-8",
+            Self::Rss => "application/rss+xml; charset=utf-8",
+        }
+    }
+}
+
+/// A feed, ready to be rendered.
+#[derive(Clone, Debug)]
+pub struct Syndication {
+    /// The title of the feed.
+    pub title: String,
+    /// When the feed last changed.
+    ///
+    /// This is when the newest entry was pulled from Legiscan, or when the subject last changed if
+    /// that is later. Actions are often pulled long after the day they happened, so the dates of
+    /// the entries don't tell when the feed changed. An empty feed about a subject which doesn't
+    /// keep track of its changes has never changed, and is dated at the Unix epoch.
+    pub updated: DateTime<Utc>,
+    /// A permanent, unique identifier for the feed.
+    id: String,
+    /// The path of the subject in the app, if the app has a page for it.
+    path: Option<String>,
+    /// The entries in the feed, newest first.
+    entries: Vec<Entry>,
+}
+
+/// An action in a feed.
+#[derive(Clone, Debug)]
+struct Entry {
+    bill: i32,
+    /// The position of the action in the bill's history.
+    sequence: i32,
+    description: String,
+    name: String,
+    title: String,
+    state: String,
+    /// The date of the action, at midnight UTC.
+    occurred_at: DateTime<Utc>,
+    /// When the action was pulled from Legiscan.
+    recorded_at: DateTime<Utc>,
+}
+
+impl Entry {
+    fn summary(&self) -> String {
+        format!("{} {}: {}", self.state, self.name, self.description)
+    }
+
+    /// A permanent, unique identifier for the entry.
+    fn id(&self) -> String {
+        format!("urn:bill-tracker:action:{}:{}", self.bill, self.sequence)
+    }
+}
+
+/// Load the feed about `subject`.
+///
+/// Returns [`None`] if there is no such subject.
+pub async fn load(sql: &SqlClient, subject: &Subject) -> Result<Option<Syndication>, Error> {
+    let mut params = Params::default();
+    let (title, id, path, condition, changed_at) = match subject {
+        Subject::Bill(bill) => {
+            let Some(row) = sql
+                .query_opt(
+                    "SELECT name, title, state, updated_at FROM catalog_bills WHERE id = $1",
+                    &[bill],
+                )
+                .await?
+            else {
+                return Ok(None);
+            };
+            let state: String = row.try_get("state")?;
+            let name: String = row.try_get("name")?;
+            let title: String = row.try_get("title")?;
+            (
+                format!("{state} {name}: {title}"),
+                format!("bill:{bill}"),
+                Some(format!("bills/{bill}")),
+                format!("a.bill = {}", params.bind(*bill)),
+                Some(row.try_get("updated_at")?),
+            )
+        }
+        Subject::Legislator(legislator) => {
+            let Some(row) = sql
+                .query_opt(
+                    "SELECT first_name, last_name, state, updated_at
+                        FROM catalog_legislators WHERE id = $1",
+                    &[legislator],
+                )
+                .await?
+            else {
+                return Ok(None);
+            };
+            let first_name: String = row.try_get("first_name")?;
+            let last_name: String = row.try_get("last_name")?;
+            let state: String = row.try_get("state")?;
+            (
+                format!("Bills sponsored by {first_name} {last_name} ({state})"),
+                format!("legislator:{legislator}"),
+                Some(format!("legislators/{legislator}")),
+                format!(
+                    "EXISTS (
+                        SELECT 1 FROM catalog_sponsors s
+                        WHERE s.bill = a.bill AND s.legislator = {}
+                    )",
+                    params.bind(*legislator)
+                ),
+                Some(row.try_get("updated_at")?),
+            )
+        }
+        Subject::Issue(name) => {
+            let Some(row) = sql
+                .query_opt(
+                    "SELECT id, name FROM catalog_issues WHERE lower(name) = lower($1)",
+                    &[name],
+                )
+                .await?
+            else {
+                return Ok(None);
+            };
+            let issue: i32 = row.try_get("id")?;
+            let name: String = row.try_get("name")?;
+            (
+                format!("Bills about {name}"),
+                format!("issue:{issue}"),
+                Some(format!("issues/{issue}")),
+                format!(
+                    "EXISTS (
+                        SELECT 1 FROM catalog_bill_issues i
+                            JOIN catalog_issue_ancestors anc ON anc.issue = i.issue
+                        WHERE i.bill = a.bill AND anc.ancestor = {}
+                    )",
+                    params.bind(issue)
+                ),
+                None,
+            )
+        }
+        Subject::State(abbreviation) => {
+            let Ok(state) = abbreviation.parse::<State>() else {
+                return Ok(None);
+            };
+            (
+                format!("Bills in {}", state.metadata().name),
+                format!("state:{state}"),
+                None,
+                format!("b.state = {}", params.bind(state.to_string())),
+                None,
+            )
+        }
+    };
+
+    let rows = sql
+        .query(
+            format!(
+                "SELECT a.bill, a.sequence, a.description, a.created_at,
+                        a.date::DATE::TIMESTAMP AT TIME ZONE 'UTC' AS occurred_at,
+                        b.name, b.title, b.state
+                    FROM bill_actions a
+                    JOIN catalog_bills b ON b.id = a.bill
+                    WHERE {condition}
+                    ORDER BY a.date DESC, a.bill DESC, a.sequence DESC
+                    LIMIT {}",
+                params.bind(MAX_ENTRIES)
+            )
+            .as_str(),
+            &params.as_refs(),
+        )
+        .await?;
+    let entries = rows
+        .iter()
+        .map(|row| {
+            Ok(Entry {
+                bill: row.try_get("bill")?,
+                sequence: row.try_get("sequence")?,
+                description: row.try_get("description")?,
+                name: row.try_get("name")?,
+                title: row.try_get("title")?,
+                state: row.try_get("state")?,
+                occurred_at: row.try_get("occurred_at")?,
+                recorded_at: row.try_get("created_at")?,
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(Some(Syndication {
+        title,
+        updated: entries
+            .iter()
+            .map(|entry| entry.recorded_at)
+            .chain(changed_at)
+            .max()
+            .unwrap_or_else(|| SystemTime::UNIX_EPOCH.into()),
+        id: format!("urn:bill-tracker:feed:{id}"),
+        path,
+        entries,
+    }))
+}
+
+impl Syndication {
+    /// A validator which changes whenever the contents of the feed do, for use as an `ETag`.
+    ///
+    /// This is based on [`updated`](Self::updated), like the `Last-Modified` header, but with the
+    /// full precision of the timestamp.
+    pub fn etag(&self) -> String {
+        format!(
+            "W/\"{}-{}\"",
+            self.updated.timestamp_micros(),
+            self.entries.len()
+        )
+    }
+
+    /// Render the feed in `format`.
+    ///
+    /// `self_url` is the URL the feed is served from. Entries link to bills in the app if `app_url`
+    /// is given.
+    pub fn render(&self, format: Format, self_url: &Url, app_url: Option<&Url>) -> String {
+        match format {
+            Format::Atom => self.render_atom(self_url, app_url),
+            Format::Rss => self.render_rss(self_url, app_url),
+        }
+    }
+
+    fn render_atom(&self, self_url: &Url, app_url: Option<&Url>) -> String {
+        // Writing to a string can't fail.
+        let mut xml = String::new();
+        writeln!(xml, "<?xml version=\"1.0\" encoding=\"utf-8\"?>").unwrap();
+        writeln!(xml, "<feed xmlns=\"http://www.w3.org/2005/Atom\">").unwrap();
+        writeln!(xml, "  <title>{}</title>", escape_html(&self.title)).unwrap();
+        writeln!(xml, "  <id>{}</id>", self.id).unwrap();
+        writeln!(
+            xml,
+            "  <link rel=\"self\" href=\"{}\"/>",
+            escape_html(self_url.as_str())
+        )
+        .unwrap();
+        if let Some(link) = self.link(app_url) {
+            writeln!(
+                xml,
+                "  <link rel=\"alternate\" href=\"{}\"/>",
+                escape_html(link.as_str())
+            )
+            .unwrap();
+        }
+        writeln!(xml, "  <updated>{}</updated>", atom_date(self.updated)).unwrap();
+        writeln!(xml, "  <author><name>Bill Tracker</name></author>").unwrap();
+        for entry in &self.entries {
+            writeln!(xml, "  <entry>").unwrap();
+            writeln!(xml, "    <title>{}</title>", escape_html(&entry.summary())).unwrap();
+            writeln!(xml, "    <id>{}</id>", entry.id()).unwrap();
+            if let Some(link) = bill_link(entry.bill, app_url) {
+                writeln!(
+                    xml,
+                    "    <link rel=\"alternate\" href=\"{}\"/>",
+                    escape_html(link.as_str())
+                )
+                .unwrap();
+            }
+            writeln!(
+                xml,
+                "    <updated>{}</updated>",
+                atom_date(entry.occurred_at)
+            )
+            .unwrap();
+            writeln!(xml, "    <summary>{}</summary>", escape_html(&entry.title)).unwrap();
+            writeln!(xml, "  </entry>").unwrap();
+        }
+        writeln!(xml, "</feed>").unwrap();
+        xml
+    }
+
+    fn render_rss(&self, self_url: &Url, app_url: Option<&Url>) -> String {
+        // RSS requires a link for the channel, so fall back to the feed itself.
+        let link = self.link(app_url).unwrap_or_else(|| self_url.clone());
+
+        // Writing to a string can't fail.
+        let mut xml = String::new();
+        writeln!(xml, "<?xml version=\"1.0\" encoding=\"utf-8\"?>").unwrap();
+        writeln!(
+            xml,
+            "<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">"
+        )
+        .unwrap();
+        writeln!(xml, "  <channel>").unwrap();
+        writeln!(xml, "    <title>{}</title>", escape_html(&self.title)).unwrap();
+        writeln!(xml, "    <link>{}</link>", escape_html(link.as_str())).unwrap();
+        writeln!(
+            xml,
+            "    <description>{}</description>",
+            escape_html(&self.title)
+        )
+        .unwrap();
+        writeln!(
+            xml,
+            "    <atom:link rel=\"self\" type=\"application/rss+xml\" href=\"{}\"/>",
+            escape_html(self_url.as_str())
+        )
+        .unwrap();
+        writeln!(
+            xml,
+            "    <lastBuildDate>{}</lastBuildDate>",
+            self.updated.to_rfc2822()
+        )
+        .unwrap();
+        for entry in &self.entries {
+            writeln!(xml, "    <item>").unwrap();
+            writeln!(
+                xml,
+                "      <title>{}</title>",
+                escape_html(&entry.summary())
+            )
+            .unwrap();
+            if let Some(link) = bill_link(entry.bill, app_url) {
+                writeln!(xml, "      <link>{}</link>", escape_html(link.as_str())).unwrap();
+            }
+            writeln!(
+                xml,
+                "      <description>{}</description>",
+                escape_html(&entry.title)
+            )
+            .unwrap();
+            writeln!(
+                xml,
+                "      <guid isPermaLink=\"false\">{}</guid>",
+                entry.id()
+            )
+            .unwrap();
+            writeln!(
+                xml,
+                "      <pubDate>{}</pubDate>",
+                entry.occurred_at.to_rfc2822()
+            )
+            .unwrap();
+            writeln!(xml, "    </item>").unwrap();
+        }
+        writeln!(xml, "  </channel>").unwrap();
+        writeln!(xml, "</rss>").unwrap();
+        xml
+    }
+
+    /// A link to the subject in the app, if the app's URL is known.
+    fn link(&self, app_url: Option<&Url>) -> Option<Url> {
+        let app_url = app_url?;
+        match &self.path {
+            Some(path) => app_url.join(path).ok(),
+            None => Some(app_url.clone()),
+        }
+    }
+}
+
+/// A link to `bill` in the app, if the app's URL is known.
+fn bill_link(bill: i32, app_url: Option<&Url>) -> Option<Url> {
+    app_url?.join(&format!("bills/{bill}")).ok()
+}
+
+fn atom_date(date: DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn syndication() -> Syndication {
+        let occurred_at = Utc.with_ymd_and_hms(2023, 3, 7, 12, 0, 0).unwrap();
+        let recorded_at = Utc.with_ymd_and_hms(2024, 1, 2, 3, 4, 5).unwrap();
+        Syndication {
+            title: "Bills in Colorado".into(),
+            updated: recorded_at,
+            id: "urn:bill-tracker:feed:state:CO".into(),
+            path: None,
+            entries: vec![Entry {
+                bill: 1,
+                sequence: 3,
+                description: "Governor Signed".into(),
+                name: "HB1".into(),
+                title: "Students <& Teachers>".into(),
+                state: "CO".into(),
+                occurred_at,
+                recorded_at,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_parse_subject() {
+        assert_eq!(Subject::parse("bill", "42"), Some(Subject::Bill(42)));
+        assert_eq!(Subject::parse("bill", "HB1"), None);
+        assert_eq!(
+            Subject::parse("state", "co"),
+            Some(Subject::State("CO".into()))
+        );
+        assert_eq!(Subject::parse("committee", "1"), None);
+    }
+
+    #[test]
+    fn test_render_atom() {
+        let self_url: Url = "https://api.example.com/feeds/state/CO.atom"
+            .parse()
+            .unwrap();
+        let app_url: Url = "https://example.com/".parse().unwrap();
+        let xml = syndication().render(Format::Atom, &self_url, Some(&app_url));
+        // The feed is dated by when it changed, and the entry by when the action happened.
+        assert!(xml.contains("  <updated>2024-01-02T03:04:05Z</updated>"));
+        assert!(xml.contains("    <updated>2023-03-07T12:00:00Z</updated>"));
+        assert!(xml.contains("<title>CO HB1: Governor Signed</title>"));
+        assert!(xml.contains("<id>urn:bill-tracker:action:1:3</id>"));
+        assert!(xml.contains("<summary>Students &lt;&amp; Teachers&gt;</summary>"));
+        assert!(xml.contains("<link rel=\"alternate\" href=\"https://example.com/bills/1\"/>"));
+    }
+
+    #[test]
+    fn test_render_rss() {
+        let self_url: Url = "https://api.example.com/feeds/state/CO.rss"
+            .parse()
+            .unwrap();
+        let xml = syndication().render(Format::Rss, &self_url, None);
+        assert!(xml.contains("<link>https://api.example.com/feeds/state/CO.rss</link>"));
+        assert!(xml.contains("Mar 2023 12:00:00 +0000</pubDate>"));
+        // Without the app's URL, there is nothing to link entries to.
+        assert!(!xml.contains("/bills/"));
+    }
+}
@@ -0,0 +1,304 @@
+//! A curated, cross-state hierarchy of political issues.
+//!
+//! Legiscan categorizes bills by subject, but each state has its own vocabulary of subjects, and
+//! subjects are not related to each other, so "Education", "Education -- Higher" and "Schools"
+//! look like unrelated issues. A [`Taxonomy`] normalizes these subjects into a hierarchy of
+//! canonical issues, which is loaded from a configuration file of the form
+//! ```json
+//! {
+//!     "issues": [
+//!         {
+//!             "name": "Education",
+//!             "keywords": ["school", "student", "teacher"],
+//!             "children": [
+//!                 { "name": "Higher Education", "keywords": ["college", "university"] },
+//!                 { "name": "K-12 Education" }
+//!             ]
+//!         }
+//!     ],
+//!     "subjects": {
+//!         "Education -- Higher": "Higher Education",
+//!         "Schools": "K-12 Education"
+//!     }
+//! }
+//! ```
+//!
+//! An issue may appear in more than one place in the hierarchy, in which case it has more than one
+//! parent. Its children need only be listed in one of those places: they are children of the issue
+//! wherever it appears. Subjects which are not mentioned in the taxonomy are treated as top-level issues of their
+//! own.
+//!
+//! The optional `keywords` for each issue are words and short phrases which suggest that a bill
+//! pertains to that issue. They are used by the [`Classifier`](crate::classifier::Classifier) to
+//! suggest issues for bills which Legiscan has not categorized.
+
+use anyhow::Error;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::path::Path;
+
+/// A hierarchy of canonical issues, with a mapping from Legiscan subjects to issues.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Taxonomy {
+    /// The top-level issues in the hierarchy.
+    #[serde(default)]
+    issues: Vec<IssueNode>,
+    /// A map from Legiscan subject names to the names of canonical issues.
+    #[serde(default)]
+    subjects: BTreeMap<String, String>,
+}
+
+/// An issue in the hierarchy, along with its sub-issues.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct IssueNode {
+    name: String,
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(default)]
+    children: Vec<IssueNode>,
+}
+
+impl Taxonomy {
+    /// Load a taxonomy from a JSON configuration file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .map_err(|err| Error::msg(format!("unable to open {}: {err}", path.display())))?;
+        let taxonomy: Self = serde_json::from_reader(file)
+            .map_err(|err| Error::msg(format!("malformed taxonomy {}: {err}", path.display())))?;
+        taxonomy.validate()?;
+        Ok(taxonomy)
+    }
+
+    /// The names of all canonical issues in the hierarchy.
+    pub fn issues(&self) -> BTreeSet<&str> {
+        let mut issues = BTreeSet::new();
+        for node in &self.issues {
+            node.visit(&mut |node, _| {
+                issues.insert(node.name.as_str());
+            });
+        }
+        issues
+    }
+
+    /// All parent-child relationships in the hierarchy, as `(parent, child)` pairs.
+    pub fn edges(&self) -> BTreeSet<(&str, &str)> {
+        let mut edges = BTreeSet::new();
+        for node in &self.issues {
+            node.visit(&mut |node, _| {
+                for child in &node.children {
+                    edges.insert((node.name.as_str(), child.name.as_str()));
+                }
+            });
+        }
+        edges
+    }
+
+    /// The ancestors of each issue in the hierarchy.
+    ///
+    /// The ancestors of an issue include the issue itself, its parents, their parents, and so on.
+    /// Since an issue may appear in several places in the hierarchy, these are found by following
+    /// [`edges`](Self::edges) rather than the path to any one place.
+    pub fn ancestors(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut parents: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (parent, child) in self.edges() {
+            parents.entry(child).or_default().insert(parent);
+        }
+        self.issues()
+            .into_iter()
+            .map(|issue| {
+                let mut ancestors = BTreeSet::new();
+                let mut queue = vec![issue];
+                while let Some(next) = queue.pop() {
+                    if ancestors.insert(next) {
+                        queue.extend(parents.get(next).into_iter().flatten().copied());
+                    }
+                }
+                (issue, ancestors)
+            })
+            .collect()
+    }
+
+    /// The keywords associated with each issue in the hierarchy.
+    pub fn keywords(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut keywords: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for node in &self.issues {
+            node.visit(&mut |node, _| {
+                keywords
+                    .entry(node.name.as_str())
+                    .or_default()
+                    .extend(node.keywords.iter().map(String::as_str));
+            });
+        }
+        keywords
+    }
+
+    /// The Legiscan subjects which are explicitly mapped to canonical issues.
+    ///
+    /// Yields `(subject, issue)` pairs.
+    pub fn subjects(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.subjects
+            .iter()
+            .map(|(subject, issue)| (subject.as_str(), issue.as_str()))
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        // Every subject must map to an issue in the hierarchy.
+        let issues = self.issues();
+        for (subject, issue) in self.subjects() {
+            if !issues.contains(issue) {
+                return Err(Error::msg(format!(
+                    "subject {subject} maps to unknown issue {issue}"
+                )));
+            }
+        }
+
+        // An issue cannot be its own descendant. Because an issue's children are its children
+        // wherever it appears, a cycle may span several places in the hierarchy, so look for one in
+        // the graph of all edges: a cycle exists if some issue is an ancestor of its own parent.
+        let ancestors = self.ancestors();
+        for (parent, child) in self.edges() {
+            if ancestors[parent].contains(child) {
+                return Err(Error::msg(format!("issue {child} is its own descendant")));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl IssueNode {
+    /// Visit this node and all of its descendants in depth-first order.
+    ///
+    /// The visitor is called with each node and the names of the ancestors of that node, from the
+    /// root down to the node's parent.
+    fn visit<'a>(&'a self, f: &mut impl FnMut(&'a Self, &[&'a str])) {
+        self.visit_with_path(f, &mut vec![]);
+    }
+
+    fn visit_with_path<'a>(
+        &'a self,
+        f: &mut impl FnMut(&'a Self, &[&'a str]),
+        path: &mut Vec<&'a str>,
+    ) {
+        f(self, path);
+        if path.contains(&self.name.as_str()) {
+            // Don't recurse infinitely if there is a cycle; `validate` will report it.
+            return;
+        }
+        path.push(&self.name);
+        for child in &self.children {
+            child.visit_with_path(f, path);
+        }
+        path.pop();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn taxonomy(json: serde_json::Value) -> Taxonomy {
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn test_load() {
+        let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+        let taxonomy = Taxonomy::load(workspace.join("db/taxonomy.json")).unwrap();
+        let issues = taxonomy.issues();
+        assert!(issues.contains("Education"));
+        for (subject, issue) in taxonomy.subjects() {
+            assert!(issues.contains(issue), "{subject} maps to unknown {issue}");
+        }
+
+        // Every ancestor of an issue is an issue, and every issue is its own ancestor.
+        let ancestors = taxonomy.ancestors();
+        assert_eq!(ancestors.keys().copied().collect::<BTreeSet<_>>(), issues);
+        for (issue, ancestors) in &ancestors {
+            assert!(ancestors.contains(issue));
+            assert!(ancestors.is_subset(&issues));
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        taxonomy(serde_json::json!({
+            "issues": [{ "name": "Education", "children": [{ "name": "Schools" }] }],
+            "subjects": { "Schools": "Schools" }
+        }))
+        .validate()
+        .unwrap();
+
+        let err = taxonomy(serde_json::json!({
+            "issues": [{ "name": "Education" }],
+            "subjects": { "Schools": "Schools" }
+        }))
+        .validate()
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "subject Schools maps to unknown issue Schools"
+        );
+
+        // A cycle along a single path.
+        let err = taxonomy(serde_json::json!({
+            "issues": [{ "name": "A", "children": [{ "name": "B", "children": [{ "name": "A" }] }] }]
+        }))
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().ends_with("is its own descendant"), "{err}");
+
+        // A cycle which only appears by combining the children listed in different places.
+        let err = taxonomy(serde_json::json!({
+            "issues": [
+                { "name": "A", "children": [{ "name": "B" }] },
+                { "name": "B", "children": [{ "name": "A" }] }
+            ]
+        }))
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().ends_with("is its own descendant"), "{err}");
+    }
+
+    #[test]
+    fn test_ancestors() {
+        // "Voting Rights" appears under both "Civil Rights" and "Elections", but its child is only
+        // listed under "Civil Rights".
+        let taxonomy = taxonomy(serde_json::json!({
+            "issues": [
+                {
+                    "name": "Civil Rights",
+                    "children": [
+                        { "name": "Voting Rights", "children": [{ "name": "Voter ID" }] }
+                    ]
+                },
+                {
+                    "name": "Government",
+                    "children": [
+                        { "name": "Elections", "children": [{ "name": "Voting Rights" }] }
+                    ]
+                }
+            ]
+        }));
+        taxonomy.validate().unwrap();
+
+        let ancestors = taxonomy.ancestors();
+        assert_eq!(
+            ancestors["Voter ID"],
+            BTreeSet::from([
+                "Civil Rights",
+                "Elections",
+                "Government",
+                "Voter ID",
+                "Voting Rights"
+            ])
+        );
+        assert_eq!(
+            ancestors["Voting Rights"],
+            BTreeSet::from(["Civil Rights", "Elections", "Government", "Voting Rights"])
+        );
+        assert_eq!(ancestors["Government"], BTreeSet::from(["Government"]));
+    }
+}
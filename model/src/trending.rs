@@ -0,0 +1,160 @@
+//! Trending bills, scored by recent activity.
+//!
+//! Each bill's trending score is a sum over recent activity signals concerning it: legislative
+//! [actions](crate::actions), like the bill being introduced or passing a chamber, recorded
+//! [votes](crate::votes), new cosponsors, and users following or viewing the bill. Each signal is
+//! weighted by its kind and decays exponentially with age, so that a burst of activity today
+//! outranks a larger burst last month.
+//!
+//! Actions and votes are dated by when they happened in the legislature, not when they were pulled
+//! from Legiscan, so that pulling a backlog of old actions doesn't make their bills trend. Bills
+//! with no recorded history fall back to the date of their latest status change. Legiscan doesn't
+//! date sponsorships, so a cosponsor counts from when it first appeared in a pull after the bill
+//! itself.
+//!
+//! Scoring every bill is too expensive to do on each request, so scores are materialized in a
+//! supplementary table, `trending_bills`, by [`refresh`]. This runs at the end of each
+//! [`update`](db::update), and should also be run periodically (with `db trending`) so that scores
+//! decay even when there is no new data.
+
+use crate::{
+    catalog::{BillFilter, Page, Params, SortedConnection},
+    db::{self, SqlClient},
+    follows::FollowKind,
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{Context, Object};
+
+/// The time it takes for the weight of a signal to halve, in hours.
+const HALF_LIFE_HOURS: f64 = 72.0;
+
+/// Signals older than this many days are ignored.
+const WINDOW_DAYS: i32 = 30;
+
+/// The weight of a legislative action, like a bill being introduced or passing a chamber.
+const ACTION_WEIGHT: f64 = 3.0;
+
+/// The weight of a recorded vote.
+///
+/// A roll call is usually also listed among the bill's actions, but floor and committee votes are
+/// what make a bill news, so they count for more than other actions.
+const VOTE_WEIGHT: f64 = 4.0;
+
+/// The weight of a new cosponsor.
+const COSPONSOR_WEIGHT: f64 = 2.0;
+
+/// The weight of a user following a bill.
+const FOLLOW_WEIGHT: f64 = 1.0;
+
+/// The weight of a user viewing a bill.
+///
+/// Only a user's most recent view of each bill is [recorded](crate::history), so repeatedly viewing
+/// a bill doesn't inflate its score.
+const VIEW_WEIGHT: f64 = 0.5;
+
+/// Queries for trending bills.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TrendingQuery;
+
+#[Object]
+impl TrendingQuery {
+    /// Bills with the most recent activity, most active first.
+    ///
+    /// If `state` or `issue` is given, only bills introduced in one of the given states, or
+    /// pertaining to one of the named issues, are listed. Bills with no recent activity are not
+    /// listed at all.
+    async fn trending_bills(
+        &self,
+        ctx: &Context<'_>,
+        state: Option<Vec<String>>,
+        issue: Option<Vec<String>>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<SortedConnection<schema::Bill>> {
+        let filter = BillFilter {
+            state,
+            issue,
+            ..Default::default()
+        };
+        let page = Page::new(first, after)?;
+        let mut params = Params::default();
+        let query = format!(
+            "SELECT b.id, row_number() OVER (ORDER BY t.score DESC, b.id) AS position
+                FROM catalog_bills b
+                JOIN trending_bills t ON t.bill = b.id
+                WHERE {}",
+            filter.sql(&mut params),
+        );
+        let conn = ctx.data::<db::Connection>()?;
+        Ok(page
+            .load(ctx.data()?, query, params, |id| async move {
+                db::bill_by_id(conn, id).await
+            })
+            .await?)
+    }
+}
+
+/// Create the table of trending scores.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS trending_bills (
+            bill INTEGER PRIMARY KEY,
+            score DOUBLE PRECISION NOT NULL,
+            computed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Recompute the trending score of every bill.
+///
+/// Only bills with activity in the last [`WINDOW_DAYS`] days are scored. The scores are replaced
+/// in a single statement, so concurrent queries see either the old scores or the new ones.
+pub async fn refresh(sql: &SqlClient) -> Result<(), Error> {
+    // New cosponsors are sponsorships cataloged after the bill itself; the original sponsors are
+    // cataloged just before the bill, and are part of the bill being introduced.
+    sql.execute(
+        format!(
+            "WITH signals AS (
+                SELECT bill, NULLIF(date, '')::DATE::TIMESTAMPTZ AS at,
+                        {ACTION_WEIGHT}::DOUBLE PRECISION AS weight
+                    FROM bill_actions
+                UNION ALL
+                SELECT b.id, b.status_date::TIMESTAMPTZ, {ACTION_WEIGHT}
+                    FROM catalog_bills b
+                    WHERE NOT EXISTS (SELECT 1 FROM bill_actions a WHERE a.bill = b.id)
+                UNION ALL
+                SELECT bill, NULLIF(date, '')::DATE::TIMESTAMPTZ, {VOTE_WEIGHT} FROM votes
+                UNION ALL
+                SELECT s.bill, s.created_at, {COSPONSOR_WEIGHT}
+                    FROM catalog_sponsors s
+                    JOIN catalog_bills b ON b.id = s.bill
+                    WHERE s.created_at > b.created_at
+                UNION ALL
+                SELECT id, created_at, {FOLLOW_WEIGHT} FROM follows WHERE kind = $1
+                UNION ALL
+                SELECT id, viewed_at, {VIEW_WEIGHT} FROM views WHERE kind = $1
+            ), scores AS (
+                SELECT bill, sum(weight * power(
+                    0.5,
+                    extract(epoch FROM now() - at)::DOUBLE PRECISION / 3600 / {HALF_LIFE_HOURS}
+                )) AS score
+                FROM signals
+                WHERE at > now() - make_interval(days => {WINDOW_DAYS})
+                GROUP BY bill
+            ), stale AS (
+                DELETE FROM trending_bills WHERE bill NOT IN (SELECT bill FROM scores)
+            )
+            INSERT INTO trending_bills (bill, score) SELECT bill, score FROM scores
+                ON CONFLICT (bill) DO UPDATE SET
+                    score = EXCLUDED.score,
+                    computed_at = now()"
+        )
+        .as_str(),
+        &[&FollowKind::Bill.to_string()],
+    )
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,178 @@
+//! Roll-call votes on bills.
+//!
+//! Legiscan reports the outcome of each roll call held on a bill: what was being voted on, the
+//! tally, and whether the motion passed. [`db::update`] records the votes on each new or changed
+//! bill in a supplementary table, `votes`, with one row per roll call, and records each vote it
+//! hasn't seen before in [feeds](crate::feed).
+
+use crate::{
+    db::{self, SqlClient},
+    legiscan::{self, State},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{ComplexObject, Context, Object, SimpleObject};
+use chrono::{DateTime, Utc};
+use futures::future::try_join_all;
+use relational_graphql::graphql::type_system::Id;
+
+/// A roll-call vote on a bill.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Vote {
+    pub id: i32,
+    /// The date of the vote, in the form YYYY-MM-DD.
+    pub date: String,
+    /// What was being voted on, like "Third Reading".
+    pub description: String,
+    /// The name of the chamber in which the vote was held, or `null` for a joint session.
+    pub chamber: Option<String>,
+    /// The number of votes in favor.
+    pub yea: i32,
+    /// The number of votes against.
+    pub nay: i32,
+    /// The number of legislators who were present but did not vote.
+    pub not_voting: i32,
+    /// The number of legislators who were absent.
+    pub absent: i32,
+    /// Whether the motion passed.
+    pub passed: bool,
+    /// When the vote was first pulled from Legiscan.
+    pub recorded_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub bill: Id,
+}
+
+#[ComplexObject]
+impl Vote {
+    /// The bill which was voted on.
+    async fn bill(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<schema::Bill>> {
+        Ok(db::bill_by_id(ctx.data()?, self.bill).await?)
+    }
+}
+
+impl Vote {
+    /// A one-line summary of the vote and its outcome, like "House vote on Third Reading passed
+    /// 40-22".
+    pub fn summary(&self) -> String {
+        format!(
+            "{} vote on {} {} {}-{}",
+            self.chamber.as_deref().unwrap_or("Joint"),
+            self.description,
+            if self.passed { "passed" } else { "failed" },
+            self.yea,
+            self.nay
+        )
+    }
+}
+
+/// Queries for votes.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VoteQuery;
+
+#[Object]
+impl VoteQuery {
+    /// The roll-call votes held on a bill, oldest first.
+    async fn votes(&self, ctx: &Context<'_>, bill: Id) -> async_graphql::Result<Vec<Vote>> {
+        Ok(votes(ctx.data()?, bill).await?)
+    }
+}
+
+/// Create the table of votes.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS votes (
+            id SERIAL PRIMARY KEY,
+            legiscan_id TEXT NOT NULL UNIQUE,
+            bill INTEGER NOT NULL,
+            date TEXT NOT NULL,
+            description TEXT NOT NULL,
+            chamber TEXT,
+            yea INTEGER NOT NULL,
+            nay INTEGER NOT NULL,
+            not_voting INTEGER NOT NULL,
+            absent INTEGER NOT NULL,
+            passed BOOLEAN NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );
+        CREATE INDEX IF NOT EXISTS votes_bill ON votes (bill, date);",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Record the votes held on `bill`.
+///
+/// Returns the votes which had not been recorded before.
+pub async fn record(
+    sql: &SqlClient,
+    bill: &schema::Bill,
+    votes: &[legiscan::Vote],
+) -> Result<Vec<Vote>, Error> {
+    let state: State = bill.state.abbreviation.parse()?;
+    let recorded = try_join_all(votes.iter().map(|vote| async move {
+        let chamber = vote.chamber.map(|chamber| state.chamber_name(chamber));
+        sql.query_opt(
+            "INSERT INTO votes (
+                    legiscan_id, bill, date, description, chamber,
+                    yea, nay, not_voting, absent, passed
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                ON CONFLICT (legiscan_id) DO NOTHING
+                RETURNING *",
+            &[
+                &vote.id,
+                &bill.id,
+                &vote.date,
+                &vote.description,
+                &chamber,
+                &(vote.yea as i32),
+                &(vote.nay as i32),
+                &(vote.not_voting as i32),
+                &(vote.absent as i32),
+                &vote.passed,
+            ],
+        )
+        .await?
+        .map(|row| vote_from_row(&row))
+        .transpose()
+    }))
+    .await?;
+    Ok(recorded.into_iter().flatten().collect())
+}
+
+/// Load the votes held on `bill`, oldest first.
+pub async fn votes(sql: &SqlClient, bill: Id) -> Result<Vec<Vote>, Error> {
+    sql.query(
+        "SELECT * FROM votes WHERE bill = $1 ORDER BY date, id",
+        &[&bill],
+    )
+    .await?
+    .iter()
+    .map(vote_from_row)
+    .collect()
+}
+
+/// Load the vote `id`.
+pub async fn vote(sql: &SqlClient, id: i32) -> Result<Option<Vote>, Error> {
+    sql.query_opt("SELECT * FROM votes WHERE id = $1", &[&id])
+        .await?
+        .map(|row| vote_from_row(&row))
+        .transpose()
+}
+
+fn vote_from_row(row: &tokio_postgres::Row) -> Result<Vote, Error> {
+    Ok(Vote {
+        id: row.try_get("id")?,
+        bill: row.try_get("bill")?,
+        date: row.try_get("date")?,
+        description: row.try_get("description")?,
+        chamber: row.try_get("chamber")?,
+        yea: row.try_get("yea")?,
+        nay: row.try_get("nay")?,
+        not_voting: row.try_get("not_voting")?,
+        absent: row.try_get("absent")?,
+        passed: row.try_get("passed")?,
+        recorded_at: row.try_get("created_at")?,
+    })
+}
@@ -0,0 +1,891 @@
+//! Outgoing webhooks, for piping changes to bills into other systems.
+//!
+//! A user can subscribe a URL to changes to bills, optionally narrowed by state, issue or bill.
+//! Subscriptions are kept in a supplementary table, `webhooks`. Deliveries are built from
+//! [feed](crate::feed) events, in two steps like [notifications](crate::notifications):
+//! 1. [`enqueue`] adds a row to `webhook_deliveries` for each new event matching each active
+//!    webhook.
+//! 2. [`deliver`] POSTs the pending deliveries which are due, recording the outcome of each attempt
+//!    in the delivery log. Failed deliveries are retried with exponential backoff, up to
+//!    [`MAX_ATTEMPTS`] times.
+//!
+//! Each delivery is a JSON object describing the event and the bill it concerns. The body is signed
+//! with HMAC-SHA256, keyed by the webhook's secret, and the signature is sent in the
+//! `X-Bill-Tracker-Signature` header as `sha256=<hex digest>`, so receivers can check that the
+//! delivery came from us. The `X-Bill-Tracker-Delivery` header holds the ID of the delivery, which
+//! is the same on every attempt, so that receivers can ignore duplicates.
+//!
+//! Each webhook's deliveries are made in order, but deliveries to different webhooks are made
+//! concurrently, so that a slow receiver doesn't hold up the others.
+//!
+//! Since webhook URLs come from users, they must not be used to reach the servers around ours.
+//! Deliveries are only made to public addresses: a URL whose host resolves to a loopback,
+//! link-local or private address is rejected when the webhook is created, and again before each
+//! round of deliveries, in case its DNS records have changed since. The deliveries connect to the
+//! address which was checked, rather than looking the host up again, so that a record changed in
+//! between can't redirect them. Redirects from receivers aren't followed either.
+
+use crate::{
+    accounts::Viewer,
+    db::{self, SqlClient},
+    feed::{self, FeedEvent},
+    live::{Change, Changes, Topic},
+    schema,
+};
+use anyhow::Error;
+use async_graphql::{
+    connection::{Connection, CursorType, Edge},
+    ComplexObject, Context, Enum, InputObject, Object, SimpleObject,
+};
+use async_std::{future::timeout, net::ToSocketAddrs};
+use chrono::{DateTime, Utc};
+use futures::{future::join_all, StreamExt};
+use hmac::{Hmac, Mac};
+use http_client::isahc::IsahcClient;
+use isahc::config::{Configurable, RedirectPolicy, ResolveMap};
+use relational_graphql::graphql::type_system::Id;
+use serde_json::json;
+use sha2::Sha256;
+use std::{
+    fmt::Write,
+    net::{IpAddr, SocketAddr},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+use strum::{Display, EnumString};
+use surf::Url;
+
+/// The maximum number of deliveries returned in a single page.
+const MAX_PAGE_SIZE: i64 = 100;
+
+/// The maximum number of webhooks a user can have.
+const MAX_WEBHOOKS: i64 = 10;
+
+/// The minimum length of a webhook secret, in characters.
+const MIN_SECRET_LEN: usize = 16;
+
+/// Only events from this many days ago or later are delivered.
+const WINDOW_DAYS: i32 = 7;
+
+/// The number of times delivery is attempted before giving up.
+pub const MAX_ATTEMPTS: i32 = 8;
+
+/// The delay before the first retry of a failed delivery, in seconds.
+///
+/// The delay doubles with each subsequent attempt, so all attempts are spread over about two hours.
+const RETRY_DELAY_SECS: f64 = 60.;
+
+/// How long to wait for a receiver to respond.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often [`watch`] checks for deliveries which are due to be retried.
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Whether deliveries may be made to non-public addresses; see [`allow_private_addresses`].
+static ALLOW_PRIVATE_ADDRESSES: AtomicBool = AtomicBool::new(false);
+
+/// A subscription of a URL to changes to bills.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct Webhook {
+    pub id: i32,
+    /// The URL deliveries are POSTed to.
+    pub url: String,
+    /// Abbreviations of the states whose bills are delivered, or `null` for all states.
+    pub states: Option<Vec<String>>,
+    /// Whether deliveries are currently being made.
+    pub active: bool,
+    /// When the webhook was created.
+    pub created_at: DateTime<Utc>,
+    #[graphql(skip)]
+    pub issues: Option<Vec<Id>>,
+    #[graphql(skip)]
+    pub bills: Option<Vec<Id>>,
+}
+
+#[ComplexObject]
+impl Webhook {
+    /// The issues whose bills are delivered, or `null` for all issues.
+    async fn issues(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Vec<schema::Issue>>> {
+        let Some(ids) = &self.issues else {
+            return Ok(None);
+        };
+        let mut issues = vec![];
+        for id in ids {
+            issues.extend(db::issue_by_id(ctx.data()?, *id).await?);
+        }
+        Ok(Some(issues))
+    }
+
+    /// The bills which are delivered, or `null` for all bills.
+    async fn bills(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<Vec<schema::Bill>>> {
+        let Some(ids) = &self.bills else {
+            return Ok(None);
+        };
+        let mut bills = vec![];
+        for id in ids {
+            bills.extend(db::bill_by_id(ctx.data()?, *id).await?);
+        }
+        Ok(Some(bills))
+    }
+}
+
+/// Criteria for selecting the changes delivered to a webhook.
+///
+/// Each criterion which is given narrows the selection to changes to bills matching any of the
+/// given values.
+#[derive(Clone, Debug, Default, InputObject)]
+pub struct WebhookFilter {
+    /// Abbreviations of states in which the bills were introduced.
+    pub state: Option<Vec<String>>,
+    /// IDs of issues the bills address, either directly or through one of their sub-issues.
+    pub issue: Option<Vec<Id>>,
+    /// IDs of the bills.
+    pub bill: Option<Vec<Id>>,
+}
+
+/// The state of a delivery.
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, EnumString, Enum)]
+#[strum(serialize_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// The delivery has not succeeded yet, but will be attempted again.
+    Pending,
+    /// The receiver accepted the delivery.
+    Delivered,
+    /// Every attempt failed, and the delivery was abandoned.
+    Failed,
+}
+
+/// An entry in the delivery log of a webhook.
+#[derive(Clone, Debug, SimpleObject)]
+#[graphql(complex)]
+pub struct WebhookDelivery {
+    /// The ID of the delivery, which is sent in the `X-Bill-Tracker-Delivery` header.
+    pub id: i32,
+    pub status: DeliveryStatus,
+    /// The number of attempts made so far.
+    pub attempts: i32,
+    /// The HTTP status of the response to the last attempt, if there was a response.
+    pub response_status: Option<i32>,
+    /// Why the last attempt failed, if it did.
+    pub error: Option<String>,
+    /// When the delivery was queued.
+    pub created_at: DateTime<Utc>,
+    /// When the last attempt was made.
+    pub attempted_at: Option<DateTime<Utc>>,
+    /// When the next attempt is due, if the delivery is pending.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    #[graphql(skip)]
+    pub event: i32,
+}
+
+#[ComplexObject]
+impl WebhookDelivery {
+    /// The change which was delivered.
+    async fn event(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<FeedEvent>> {
+        Ok(feed::event(ctx.data()?, self.event).await?)
+    }
+}
+
+/// Queries about webhooks.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WebhookQuery;
+
+#[Object]
+impl WebhookQuery {
+    /// The signed-in user's webhooks, oldest first.
+    async fn webhooks(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Webhook>> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(webhooks(ctx.data()?, user).await?)
+    }
+
+    /// The delivery log of one of the signed-in user's webhooks, newest first.
+    ///
+    /// If `status` is given, only deliveries in that state are listed.
+    async fn webhook_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        webhook: i32,
+        status: Option<DeliveryStatus>,
+        first: Option<i32>,
+        after: Option<String>,
+    ) -> async_graphql::Result<Connection<usize, WebhookDelivery>> {
+        let user = Viewer::of(ctx).require()?;
+        let offset = match after {
+            Some(cursor) => usize::decode_cursor(&cursor)? + 1,
+            None => 0,
+        };
+        let limit = first.map(i64::from).unwrap_or(MAX_PAGE_SIZE);
+        Ok(deliveries(ctx.data()?, user, webhook, status, offset, limit).await?)
+    }
+}
+
+/// Mutations for managing webhooks.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WebhookMutation;
+
+#[Object]
+impl WebhookMutation {
+    /// Subscribe a URL to changes to bills matching `filter`, as the signed-in user.
+    ///
+    /// Deliveries are signed with `secret`, which must be at least 16 characters long.
+    async fn create_webhook(
+        &self,
+        ctx: &Context<'_>,
+        url: String,
+        secret: String,
+        filter: Option<WebhookFilter>,
+    ) -> async_graphql::Result<Webhook> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(create_webhook(ctx.data()?, user, &url, &secret, filter.unwrap_or_default()).await?)
+    }
+
+    /// Change one of the signed-in user's webhooks.
+    ///
+    /// Only the arguments which are given are changed. Deactivating a webhook stops deliveries of
+    /// new changes; changes which occur while it is inactive are never delivered.
+    async fn update_webhook(
+        &self,
+        ctx: &Context<'_>,
+        id: i32,
+        url: Option<String>,
+        secret: Option<String>,
+        filter: Option<WebhookFilter>,
+        active: Option<bool>,
+    ) -> async_graphql::Result<Webhook> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(update_webhook(
+            ctx.data()?,
+            user,
+            id,
+            url.as_deref(),
+            secret.as_deref(),
+            filter,
+            active,
+        )
+        .await?)
+    }
+
+    /// Delete one of the signed-in user's webhooks, along with its delivery log.
+    ///
+    /// Returns whether the webhook existed.
+    async fn delete_webhook(&self, ctx: &Context<'_>, id: i32) -> async_graphql::Result<bool> {
+        let user = Viewer::of(ctx).require()?;
+        Ok(delete_webhook(ctx.data()?, user, id).await?)
+    }
+}
+
+/// Create the tables for webhooks and their deliveries.
+pub async fn setup(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "CREATE TABLE IF NOT EXISTS webhooks (
+            id SERIAL PRIMARY KEY,
+            user_id INTEGER NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+            url TEXT NOT NULL,
+            secret TEXT NOT NULL,
+            -- Filters, where NULL matches everything.
+            states TEXT[],
+            issues INTEGER[],
+            bills INTEGER[],
+            active BOOLEAN NOT NULL DEFAULT TRUE,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            -- When the webhook was last activated; only later events are delivered.
+            activated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+        );
+        CREATE INDEX IF NOT EXISTS webhooks_user ON webhooks (user_id);
+        CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id SERIAL PRIMARY KEY,
+            webhook INTEGER NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+            event INTEGER NOT NULL REFERENCES feed_events (id) ON DELETE CASCADE,
+            status TEXT NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            response_status INTEGER,
+            error TEXT,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+            attempted_at TIMESTAMPTZ,
+            next_attempt_at TIMESTAMPTZ DEFAULT now(),
+            UNIQUE (webhook, event)
+        );
+        CREATE INDEX IF NOT EXISTS webhook_deliveries_due ON webhook_deliveries (next_attempt_at)
+            WHERE next_attempt_at IS NOT NULL;",
+    )
+    .await?;
+    Ok(())
+}
+
+/// Subscribe `url` to changes to bills matching `filter`, on behalf of `user`.
+pub async fn create_webhook(
+    sql: &SqlClient,
+    user: i32,
+    url: &str,
+    secret: &str,
+    filter: WebhookFilter,
+) -> Result<Webhook, Error> {
+    let url = validate_url(url)?;
+    check_destination(&url).await?;
+    validate_secret(secret)?;
+    let count: i64 = sql
+        .query_one("SELECT count(*) FROM webhooks WHERE user_id = $1", &[&user])
+        .await?
+        .try_get(0)?;
+    if count >= MAX_WEBHOOKS {
+        return Err(Error::msg(format!(
+            "you can have at most {MAX_WEBHOOKS} webhooks"
+        )));
+    }
+    let row = sql
+        .query_one(
+            "INSERT INTO webhooks (user_id, url, secret, states, issues, bills)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                RETURNING id, url, states, issues, bills, active, created_at",
+            &[
+                &user,
+                &url.as_str(),
+                &secret,
+                &filter.state,
+                &filter.issue,
+                &filter.bill,
+            ],
+        )
+        .await?;
+    webhook_from_row(&row)
+}
+
+/// Change `user`'s webhook `id`.
+pub async fn update_webhook(
+    sql: &SqlClient,
+    user: i32,
+    id: i32,
+    url: Option<&str>,
+    secret: Option<&str>,
+    filter: Option<WebhookFilter>,
+    active: Option<bool>,
+) -> Result<Webhook, Error> {
+    let url = url.map(validate_url).transpose()?;
+    if let Some(url) = &url {
+        check_destination(url).await?;
+    }
+    if let Some(secret) = secret {
+        validate_secret(secret)?;
+    }
+    let replace_filter = filter.is_some();
+    let filter = filter.unwrap_or_default();
+    let row = sql
+        .query_opt(
+            "UPDATE webhooks SET
+                    url = coalesce($3, url),
+                    secret = coalesce($4, secret),
+                    states = CASE WHEN $5 THEN $6 ELSE states END,
+                    issues = CASE WHEN $5 THEN $7 ELSE issues END,
+                    bills = CASE WHEN $5 THEN $8 ELSE bills END,
+                    activated_at = CASE WHEN $9 AND NOT active THEN now() ELSE activated_at END,
+                    active = coalesce($9, active)
+                WHERE id = $1 AND user_id = $2
+                RETURNING id, url, states, issues, bills, active, created_at",
+            &[
+                &id,
+                &user,
+                &url.as_ref().map(Url::as_str),
+                &secret,
+                &replace_filter,
+                &filter.state,
+                &filter.issue,
+                &filter.bill,
+                &active,
+            ],
+        )
+        .await?
+        .ok_or_else(|| Error::msg(format!("you have no webhook {id}")))?;
+    webhook_from_row(&row)
+}
+
+/// Delete `user`'s webhook `id`.
+///
+/// Returns whether the webhook existed.
+pub async fn delete_webhook(sql: &SqlClient, user: i32, id: i32) -> Result<bool, Error> {
+    let deleted = sql
+        .execute(
+            "DELETE FROM webhooks WHERE id = $1 AND user_id = $2",
+            &[&id, &user],
+        )
+        .await?;
+    Ok(deleted > 0)
+}
+
+/// Load `user`'s webhooks, oldest first.
+pub async fn webhooks(sql: &SqlClient, user: i32) -> Result<Vec<Webhook>, Error> {
+    sql.query(
+        "SELECT id, url, states, issues, bills, active, created_at FROM webhooks
+            WHERE user_id = $1
+            ORDER BY id",
+        &[&user],
+    )
+    .await?
+    .iter()
+    .map(webhook_from_row)
+    .collect()
+}
+
+/// Load a page of the delivery log of `user`'s webhook `webhook`, newest first.
+pub async fn deliveries(
+    sql: &SqlClient,
+    user: i32,
+    webhook: i32,
+    status: Option<DeliveryStatus>,
+    offset: usize,
+    limit: i64,
+) -> Result<Connection<usize, WebhookDelivery>, Error> {
+    let limit = limit.clamp(0, MAX_PAGE_SIZE);
+    // Fetch one more delivery than requested, to find out if there is a next page.
+    let rows = sql
+        .query(
+            "SELECT d.* FROM webhook_deliveries d
+                JOIN webhooks w ON w.id = d.webhook
+                WHERE d.webhook = $1 AND w.user_id = $2 AND ($3::TEXT IS NULL OR d.status = $3)
+                ORDER BY d.id DESC
+                LIMIT $4 OFFSET $5",
+            &[
+                &webhook,
+                &user,
+                &status.map(|status| status.to_string()),
+                &(limit + 1),
+                &(offset as i64),
+            ],
+        )
+        .await?;
+    let has_next = rows.len() as i64 > limit;
+    let mut connection = Connection::new(offset > 0, has_next);
+    for (i, row) in rows.iter().take(limit as usize).enumerate() {
+        connection
+            .edges
+            .push(Edge::new(offset + i, delivery_from_row(row)?));
+    }
+    Ok(connection)
+}
+
+/// Queue deliveries of new feed events to the webhooks they match.
+///
+/// Only events recorded since a webhook was last activated are delivered to it.
+///
+/// Returns the number of deliveries queued.
+pub async fn enqueue(sql: &SqlClient) -> Result<u64, Error> {
+    Ok(sql
+        .execute(
+            "INSERT INTO webhook_deliveries (webhook, event, status)
+                SELECT w.id, e.id, $1
+                FROM webhooks w
+                JOIN feed_events e ON e.created_at >= w.activated_at
+                JOIN catalog_bills b ON b.id = e.bill
+                WHERE w.active
+                    AND e.created_at > now() - make_interval(days => $2)
+                    AND (w.states IS NULL OR b.state = ANY(w.states))
+                    AND (w.bills IS NULL OR e.bill = ANY(w.bills))
+                    AND (w.issues IS NULL OR EXISTS (
+                        SELECT 1 FROM catalog_bill_issues i
+                            JOIN catalog_issue_ancestors a ON a.issue = i.issue
+                        WHERE i.bill = e.bill AND a.ancestor = ANY(w.issues)
+                    ))
+                ON CONFLICT DO NOTHING",
+            &[&DeliveryStatus::Pending.to_string(), &WINDOW_DAYS],
+        )
+        .await?)
+}
+
+/// Attempt the pending deliveries which are due.
+///
+/// Returns the number of deliveries which succeeded.
+pub async fn deliver(sql: &SqlClient) -> Result<usize, Error> {
+    let rows = sql
+        .query(
+            "SELECT d.id, d.webhook, d.attempts, w.url, w.secret,
+                e.id AS event, e.kind, e.status, e.detail, e.created_at,
+                b.id AS bill, b.name, b.title, b.state
+            FROM webhook_deliveries d
+            JOIN webhooks w ON w.id = d.webhook
+            JOIN feed_events e ON e.id = d.event
+            LEFT JOIN catalog_bills b ON b.id = e.bill
+            WHERE d.next_attempt_at <= now() AND w.active
+            ORDER BY d.webhook, d.id",
+            &[],
+        )
+        .await?;
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    // Group the deliveries by webhook.
+    let mut batches: Vec<Vec<tokio_postgres::Row>> = vec![];
+    for row in rows {
+        let webhook: i32 = row.try_get("webhook")?;
+        match batches.last_mut() {
+            Some(batch) if batch[0].try_get::<_, i32>("webhook")? == webhook => batch.push(row),
+            _ => batches.push(vec![row]),
+        }
+    }
+
+    // Each webhook's batch is delivered on its own, so that an error in one batch doesn't stop the
+    // others.
+    let results = join_all(batches.iter().map(|batch| async move {
+        // Check where the webhook's URL leads once per batch, since it can change at any time, and
+        // deliver the whole batch to the address which was checked.
+        let url: String = batch[0].try_get("url")?;
+        let client = match url.parse() {
+            Ok(url) => match check_destination(&url).await {
+                Ok(addr) => pinned_client(&url, addr),
+                Err(err) => Err(err),
+            },
+            Err(err) => Err(Error::from(err)),
+        }
+        .map_err(|err| err.to_string());
+
+        let mut delivered = 0;
+        for row in batch {
+            if attempt(sql, &client, row).await? {
+                delivered += 1;
+            }
+        }
+        Ok::<_, Error>(delivered)
+    }))
+    .await;
+
+    let mut delivered = 0;
+    for (batch, res) in batches.iter().zip(results) {
+        match res {
+            Ok(count) => delivered += count,
+            Err(err) => {
+                let webhook: i32 = batch[0].try_get("webhook")?;
+                tracing::error!("failed to deliver to webhook {webhook}: {err}");
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+/// An HTTP client which connects to `addr` for the host of `url`, instead of looking it up.
+fn pinned_client(url: &Url, addr: SocketAddr) -> Result<surf::Client, Error> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::msg("webhook URL must have a host"))?;
+    let client = isahc::HttpClient::builder()
+        .dns_resolve(ResolveMap::new().add(host, addr.port(), addr.ip()))
+        .redirect_policy(RedirectPolicy::None)
+        .build()?;
+    surf::Config::new()
+        .set_http_client(IsahcClient::from_client(client))
+        .set_timeout(Some(REQUEST_TIMEOUT))
+        .try_into()
+        .map_err(Error::msg)
+}
+
+/// Attempt the delivery described by `row`, and record the outcome.
+///
+/// `client` connects to the checked address of the webhook's URL. If it is an error, the URL can't
+/// be delivered to, so the attempt fails without sending anything. Returns whether the delivery
+/// succeeded.
+async fn attempt(
+    sql: &SqlClient,
+    client: &Result<surf::Client, String>,
+    row: &tokio_postgres::Row,
+) -> Result<bool, Error> {
+    let id: i32 = row.try_get("id")?;
+    let attempts: i32 = row.try_get::<_, i32>("attempts")? + 1;
+    let url: String = row.try_get("url")?;
+    let secret: String = row.try_get("secret")?;
+    let body = json!({
+        "delivery": id,
+        "event": {
+            "id": row.try_get::<_, i32>("event")?,
+            "kind": row.try_get::<_, String>("kind")?,
+            "status": row.try_get::<_, String>("status")?,
+            "detail": row.try_get::<_, String>("detail")?,
+            "occurredAt": row.try_get::<_, DateTime<Utc>>("created_at")?,
+        },
+        "bill": {
+            "id": row.try_get::<_, Option<i32>>("bill")?,
+            "name": row.try_get::<_, Option<String>>("name")?,
+            "title": row.try_get::<_, Option<String>>("title")?,
+            "state": row.try_get::<_, Option<String>>("state")?,
+        },
+    })
+    .to_string();
+
+    let (response_status, error) = match client {
+        Ok(client) => match post(client, &url, &secret, id, body).await {
+            Ok(status) if (200..300).contains(&status) => (Some(status), None),
+            Ok(status) => (
+                Some(status),
+                Some(format!("receiver responded with status {status}")),
+            ),
+            Err(err) => (None, Some(err.to_string())),
+        },
+        Err(err) => (None, Some(err.clone())),
+    };
+    let status = if error.is_none() {
+        DeliveryStatus::Delivered
+    } else if attempts >= MAX_ATTEMPTS {
+        tracing::warn!("giving up on delivery {id} to {url}: {error:?}");
+        DeliveryStatus::Failed
+    } else {
+        DeliveryStatus::Pending
+    };
+    let retry_delay = (status == DeliveryStatus::Pending).then(|| retry_delay_secs(attempts));
+    sql.execute(
+        "UPDATE webhook_deliveries SET
+                status = $2,
+                attempts = $3,
+                response_status = $4,
+                error = $5,
+                attempted_at = now(),
+                next_attempt_at = now() + make_interval(secs => $6)
+            WHERE id = $1",
+        &[
+            &id,
+            &status.to_string(),
+            &attempts,
+            &response_status,
+            &error,
+            &retry_delay,
+        ],
+    )
+    .await?;
+    Ok(status == DeliveryStatus::Delivered)
+}
+
+/// Queue and attempt deliveries as soon as feed events are recorded, retrying failed deliveries
+/// when they are due.
+///
+/// This runs until the connection to the database is lost.
+pub async fn watch(opt: &db::Options, sql: &SqlClient) -> Result<(), Error> {
+    let mut changes = Changes::listen(opt).await?.subscribe(Topic::FeedEvents);
+    loop {
+        let queued = enqueue(sql).await?;
+        let delivered = deliver(sql).await?;
+        tracing::info!("queued {queued} deliveries, delivered {delivered}");
+
+        // Wait for the next feed event, or until it is time to check for retries.
+        let next_event = async {
+            loop {
+                match changes.next().await {
+                    Some(Change::FeedEvent(_)) => return Ok(()),
+                    Some(_) => continue,
+                    None => return Err(Error::msg("stopped receiving changes")),
+                }
+            }
+        };
+        if let Ok(res) = timeout(POLL_INTERVAL, next_event).await {
+            res?;
+        }
+    }
+}
+
+/// POST a signed delivery to `url`.
+///
+/// Returns the HTTP status of the response.
+async fn post(
+    client: &surf::Client,
+    url: &str,
+    secret: &str,
+    delivery: i32,
+    body: String,
+) -> Result<i32, Error> {
+    let res = client
+        .post(url)
+        .header("X-Bill-Tracker-Delivery", delivery.to_string())
+        .header("X-Bill-Tracker-Signature", sign(secret, &body))
+        .content_type(surf::http::mime::JSON)
+        .body_string(body)
+        .await
+        .map_err(Error::msg)?;
+    Ok(u16::from(res.status()).into())
+}
+
+/// Sign `body` with `secret`, in the format of the `X-Bill-Tracker-Signature` header.
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    let mut signature = "sha256=".to_string();
+    for byte in mac.finalize().into_bytes() {
+        write!(signature, "{byte:02x}").unwrap();
+    }
+    signature
+}
+
+/// The delay before the attempt following attempt number `attempts`, in seconds.
+fn retry_delay_secs(attempts: i32) -> f64 {
+    RETRY_DELAY_SECS * 2f64.powi(attempts - 1)
+}
+
+fn validate_url(url: &str) -> Result<Url, Error> {
+    let url: Url = url.parse()?;
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Error::msg("webhook URL must use http or https"));
+    }
+    Ok(url)
+}
+
+/// Check that `url` leads to a public address, and return the address to connect to.
+///
+/// Every address the host resolves to must be public: a host with any private address is likely
+/// aimed at our own network.
+async fn check_destination(url: &Url) -> Result<SocketAddr, Error> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| Error::msg("webhook URL must have a host"))?;
+    // IPv6 literals are bracketed in URLs, but not in socket addresses.
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs = (host, port).to_socket_addrs().await?.collect::<Vec<_>>();
+    let Some(first) = addrs.first() else {
+        return Err(Error::msg(format!("webhook host {host} has no addresses")));
+    };
+    if ALLOW_PRIVATE_ADDRESSES.load(Ordering::Relaxed) {
+        return Ok(*first);
+    }
+    for addr in &addrs {
+        if !is_public(addr.ip()) {
+            return Err(Error::msg(format!(
+                "webhook host {host} resolves to non-public address {}",
+                addr.ip()
+            )));
+        }
+    }
+    Ok(*first)
+}
+
+/// Whether `ip` is an address on the public internet, as opposed to a loopback, link-local,
+/// private or otherwise reserved address.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // 0.0.0.0/8, "this network".
+                || a == 0
+                // 100.64.0.0/10, shared address space for carrier-grade NAT.
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(ip.into()),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    // fc00::/7, unique local addresses.
+                    || (first & 0xfe00) == 0xfc00
+                    // fe80::/10, link-local addresses.
+                    || (first & 0xffc0) == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Allow webhooks to deliver to loopback, link-local and private addresses.
+///
+/// This lets tests run receivers on the loopback interface, and affects the whole process.
+#[cfg(any(test, feature = "testing"))]
+pub fn allow_private_addresses() {
+    ALLOW_PRIVATE_ADDRESSES.store(true, Ordering::Relaxed);
+}
+
+fn validate_secret(secret: &str) -> Result<(), Error> {
+    if secret.chars().count() < MIN_SECRET_LEN {
+        return Err(Error::msg(format!(
+            "webhook secret must be at least {MIN_SECRET_LEN} characters"
+        )));
+    }
+    Ok(())
+}
+
+fn webhook_from_row(row: &tokio_postgres::Row) -> Result<Webhook, Error> {
+    Ok(Webhook {
+        id: row.try_get("id")?,
+        url: row.try_get("url")?,
+        states: row.try_get("states")?,
+        issues: row.try_get("issues")?,
+        bills: row.try_get("bills")?,
+        active: row.try_get("active")?,
+        created_at: row.try_get("created_at")?,
+    })
+}
+
+fn delivery_from_row(row: &tokio_postgres::Row) -> Result<WebhookDelivery, Error> {
+    Ok(WebhookDelivery {
+        id: row.try_get("id")?,
+        status: row.try_get::<_, String>("status")?.parse()?,
+        attempts: row.try_get("attempts")?,
+        response_status: row.try_get("response_status")?,
+        error: row.try_get("error")?,
+        created_at: row.try_get("created_at")?,
+        attempted_at: row.try_get("attempted_at")?,
+        next_attempt_at: row.try_get("next_attempt_at")?,
+        event: row.try_get("event")?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use async_std::task::block_on;
+
+    #[test]
+    fn test_sign() {
+        // Test case 2 from RFC 4231.
+        assert_eq!(
+            sign("Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["93.184.216.34", "2606:2800:220:1:248:1893:25c8:1946"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn test_check_destination() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://localhost/hook",
+        ] {
+            let url = url.parse().unwrap();
+            assert!(block_on(check_destination(&url)).is_err(), "{url}");
+        }
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay_secs(1), RETRY_DELAY_SECS);
+        assert_eq!(retry_delay_secs(3), 4. * RETRY_DELAY_SECS);
+        // All retries are made within a few hours.
+        let total: f64 = (1..MAX_ATTEMPTS).map(retry_delay_secs).sum();
+        assert!(total < 3. * 60. * 60.);
+    }
+}
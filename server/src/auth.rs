@@ -0,0 +1,84 @@
+//! Authentication of incoming requests.
+
+use async_graphql::Data;
+use model::{accounts, db::SqlClient};
+use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tide::{http::StatusCode, Middleware, Next, Request, Response};
+
+/// Middleware which validates the session token of each request.
+///
+/// Requests may carry a token in an `Authorization: Bearer <token>` header. If the token identifies
+/// a current session, the signed-in [`Viewer`](accounts::Viewer) is attached to the request as an
+/// extension. Requests without a token proceed anonymously, but requests with an invalid or expired
+/// token are rejected, so that clients find out they need to sign in again.
+#[derive(Clone)]
+pub struct Authenticate {
+    sql: Arc<SqlClient>,
+}
+
+impl Authenticate {
+    pub fn new(sql: Arc<SqlClient>) -> Self {
+        Self { sql }
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for Authenticate {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let token = req
+            .header("Authorization")
+            .and_then(|values| values.last().as_str().strip_prefix("Bearer "))
+            .map(|token| token.trim().to_string());
+        if let Some(token) = token {
+            match accounts::authenticate(&self.sql, &token).await? {
+                Some(viewer) => req.set_ext(viewer),
+                None => {
+                    let mut res = Response::new(StatusCode::Unauthorized);
+                    res.set_body("invalid or expired session token");
+                    return Ok(res);
+                }
+            };
+        }
+        Ok(next.run(req).await)
+    }
+}
+
+/// Authenticate a WebSocket connection for GraphQL subscriptions.
+///
+/// Browsers can't set headers on WebSocket requests, so clients send their token in the payload of
+/// the `connection_init` message instead, as `{"authorization": "Bearer <token>"}`. As with HTTP
+/// requests, connections without a token proceed anonymously, and connections with an invalid
+/// token are rejected.
+pub async fn connection_init(sql: &SqlClient, payload: Value) -> async_graphql::Result<Data> {
+    let mut data = Data::default();
+    let token = payload
+        .get("authorization")
+        .and_then(Value::as_str)
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim);
+    if let Some(token) = token {
+        match accounts::authenticate(sql, token).await? {
+            Some(viewer) => data.insert(viewer),
+            None => return Err("invalid or expired session token".into()),
+        }
+    }
+    Ok(data)
+}
+
+/// The address of the client which made `req`.
+///
+/// This is the address of the peer, unless `trust_proxy` is set, in which case it is the address
+/// the reverse proxy in front of the server says the request came from.
+pub fn client_addr<State>(req: &Request<State>, trust_proxy: bool) -> Option<IpAddr> {
+    let addr = if trust_proxy {
+        req.remote()
+    } else {
+        req.peer_addr()
+    }?;
+    addr.parse::<SocketAddr>()
+        .map(|addr| addr.ip())
+        .or_else(|_| addr.parse())
+        .ok()
+}
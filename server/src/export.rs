@@ -0,0 +1,99 @@
+//! Downloads of [bulk exports](model::export) for administrators.
+
+use async_std::{
+    channel::{bounded, Sender},
+    io,
+    task::spawn,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use model::{
+    accounts::Viewer,
+    db::{self, SqlClient},
+    export::{Export, ExportFilter, Format, Table},
+};
+use std::str::FromStr;
+use tide::{
+    http::{mime::Mime, StatusCode},
+    Body, Request, Response,
+};
+
+/// The number of chunks of an export to read ahead of the client.
+const EXPORT_BUFFER: usize = 4;
+
+/// Respond to a request for `/export/:file`.
+///
+/// `file` is the name of a table followed by `.csv`, `.jsonl` or `.parquet` to select the format,
+/// as in `bills.csv`. The export can be narrowed with the `state` and `session` query parameters.
+/// Only administrators can download exports, since each one reads a whole table.
+///
+/// The file is streamed to the client as it is read from the database, through a connection of its
+/// own, so that neither the table nor the other queries of the server wait on a slow download.
+pub async fn respond(db: &db::Options, sql: &SqlClient, req: Request<()>) -> tide::Result {
+    let viewer = req.ext::<Viewer>().cloned().unwrap_or_default();
+    if let Err(err) = viewer.require_admin(sql).await {
+        let status = if viewer.user.is_none() {
+            StatusCode::Unauthorized
+        } else {
+            StatusCode::Forbidden
+        };
+        let mut res = Response::new(status);
+        res.set_body(err.message);
+        return Ok(res);
+    }
+    let Some((table, format)) = req.param("file")?.rsplit_once('.') else {
+        return Ok(Response::new(StatusCode::NotFound));
+    };
+    let (Ok(table), Ok(format)) = (table.parse::<Table>(), format.parse::<Format>()) else {
+        return Ok(Response::new(StatusCode::NotFound));
+    };
+    let filter: ExportFilter = req.query()?;
+
+    let export_sql = db.connect_sql().await?;
+    let (sender, receiver) = bounded(EXPORT_BUFFER);
+    spawn(export(export_sql, table, format, filter, sender));
+    // Wait for the first chunk, so that an export which can't be started fails with an error
+    // status, rather than a truncated file.
+    let first = match receiver.recv().await {
+        Ok(chunk) => chunk?,
+        Err(_) => vec![],
+    };
+
+    let chunks = stream::iter([Ok(first)]).chain(receiver);
+    let mut res = Response::new(StatusCode::Ok);
+    res.set_body(Body::from_reader(chunks.into_async_read(), None));
+    res.set_content_type(Mime::from_str(format.content_type())?);
+    res.insert_header(
+        "Content-Disposition",
+        format!("attachment; filename=\"{}\"", format.file_name(table)),
+    );
+    Ok(res)
+}
+
+/// Send the chunks of an export to `chunks`, until it is complete or the receiver goes away.
+///
+/// If the export fails, the error is sent in place of the next chunk.
+async fn export(
+    mut sql: SqlClient,
+    table: Table,
+    format: Format,
+    filter: ExportFilter,
+    chunks: Sender<io::Result<Vec<u8>>>,
+) {
+    let res = async {
+        let mut export = Export::start(&mut sql, table, &filter).await?;
+        while let Some(chunk) = export.next_chunk(format).await? {
+            if chunks.send(Ok(chunk)).await.is_err() {
+                // The client went away.
+                break;
+            }
+        }
+        Ok::<_, tide::Error>(())
+    }
+    .await;
+    if let Err(err) = res {
+        chunks
+            .send(Err(io::Error::new(io::ErrorKind::Other, err.to_string())))
+            .await
+            .ok();
+    }
+}
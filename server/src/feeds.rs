@@ -0,0 +1,70 @@
+//! Atom and RSS feeds, served over plain HTTP for feed readers.
+
+use chrono::{DateTime, Utc};
+use model::{
+    db::SqlClient,
+    syndication::{self, Format, Subject},
+};
+use percent_encoding::percent_decode_str;
+use std::str::FromStr;
+use tide::{
+    http::{mime::Mime, StatusCode, Url},
+    Request, Response,
+};
+
+/// How long clients and proxies may cache a feed, in seconds.
+const MAX_AGE: u32 = 300;
+
+/// Respond to a request for `/feeds/:kind/:file`.
+///
+/// `file` is the key of the subject, like the ID of a bill or the abbreviation of a state, followed
+/// by `.atom` or `.rss` to select the format. Responses carry `ETag` and `Last-Modified` headers,
+/// and conditional requests for feeds which have not changed get `304 Not Modified`.
+pub async fn respond(sql: &SqlClient, app_url: Option<&Url>, req: Request<()>) -> tide::Result {
+    let kind = req.param("kind")?;
+    let Some((key, format)) = req.param("file")?.rsplit_once('.') else {
+        return Ok(Response::new(StatusCode::NotFound));
+    };
+    let (Ok(format), Ok(key)) = (
+        format.parse::<Format>(),
+        percent_decode_str(key).decode_utf8(),
+    ) else {
+        return Ok(Response::new(StatusCode::NotFound));
+    };
+    let Some(subject) = Subject::parse(kind, &key) else {
+        return Ok(Response::new(StatusCode::NotFound));
+    };
+    let Some(feed) = syndication::load(sql, &subject).await? else {
+        return Ok(Response::new(StatusCode::NotFound));
+    };
+
+    let etag = feed.etag();
+    let not_modified = match req.header("If-None-Match") {
+        Some(tags) => tags
+            .as_str()
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag == etag),
+        None => req
+            .header("If-Modified-Since")
+            .and_then(|since| DateTime::parse_from_rfc2822(since.as_str()).ok())
+            // HTTP dates only have a resolution of seconds.
+            .is_some_and(|since| feed.updated.timestamp() <= since.timestamp()),
+    };
+    let mut res = if not_modified {
+        Response::new(StatusCode::NotModified)
+    } else {
+        let mut res = Response::new(StatusCode::Ok);
+        res.set_body(feed.render(format, req.url(), app_url));
+        res.set_content_type(Mime::from_str(format.content_type())?);
+        res
+    };
+    res.insert_header("ETag", etag);
+    res.insert_header("Last-Modified", http_date(feed.updated));
+    res.insert_header("Cache-Control", format!("public, max-age={MAX_AGE}"));
+    Ok(res)
+}
+
+fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
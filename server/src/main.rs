@@ -1,9 +1,12 @@
-use async_graphql_tide::graphql;
+use async_graphql_tide::GraphQLSubscription;
+use auth::Authenticate;
 use clap::Parser;
-use model::{db, schema};
+use model::{accounts::Viewer, db, migrations, schema};
+use std::sync::Arc;
 use tide::{
-    http::headers::HeaderValue,
+    http::{headers::HeaderValue, Url},
     security::{CorsMiddleware, Origin},
+    Request,
 };
 
 /// Start the bill tracker server.
@@ -13,6 +16,17 @@ struct Options {
     #[clap(short, long, env = "BILL_TRACKER_PORT", default_value = "80")]
     port: u16,
 
+    /// Base URL of the app, for linking to bills from feeds.
+    #[clap(long, env = "BILL_TRACKER_APP_URL")]
+    app_url: Option<Url>,
+
+    /// Take the address of each client from the `Forwarded` or `X-Forwarded-For` header.
+    ///
+    /// Only use this behind a reverse proxy which sets these headers, since otherwise clients can
+    /// claim any address they like.
+    #[clap(long, env = "BILL_TRACKER_TRUST_PROXY")]
+    trust_proxy: bool,
+
     #[clap(flatten)]
     db: db::Options,
 }
@@ -21,12 +35,62 @@ impl Options {
     async fn serve(&self) -> tide::Result<()> {
         let cors = CorsMiddleware::new()
             .allow_methods("GET, POST".parse::<HeaderValue>().unwrap())
+            .allow_headers(
+                "Authorization, Content-Type"
+                    .parse::<HeaderValue>()
+                    .unwrap(),
+            )
             .allow_origin(Origin::from("*"));
+        let sql = Arc::new(self.db.connect_sql().await?);
+        // Refuse to serve against a schema which doesn't match the one this code expects.
+        migrations::check(&sql).await?;
+        let api = schema::executor(&self.db).await?;
 
         let mut app = tide::new();
-        app.with(cors)
-            .at("/graphql")
-            .all(graphql(schema::executor(&self.db).await?));
+        app.with(cors).with(Authenticate::new(sql.clone()));
+        app.at("/graphql").all({
+            let api = api.clone();
+            let trust_proxy = self.trust_proxy;
+            move |req: Request<()>| {
+                let api = api.clone();
+                async move {
+                    // Pass the viewer authenticated by the middleware on to the GraphQL API.
+                    let mut viewer = req.ext::<Viewer>().cloned().unwrap_or_default();
+                    viewer.client_addr = auth::client_addr(&req, trust_proxy);
+                    let req = async_graphql_tide::receive_request(req).await?.data(viewer);
+                    async_graphql_tide::respond(api.execute(req).await)
+                }
+            }
+        });
+        // Atom and RSS feeds are served over plain HTTP, for feed readers.
+        app.at("/feeds/:kind/:file").get({
+            let sql = sql.clone();
+            let app_url = self.app_url.clone();
+            move |req: Request<()>| {
+                let sql = sql.clone();
+                let app_url = app_url.clone();
+                async move { feeds::respond(&sql, app_url.as_ref(), req).await }
+            }
+        });
+        // Bulk exports are downloaded over plain HTTP, for use in other tools.
+        app.at("/export/:file").get({
+            let db = self.db.clone();
+            let sql = sql.clone();
+            move |req: Request<()>| {
+                let db = db.clone();
+                let sql = sql.clone();
+                async move { export::respond(&db, &sql, req).await }
+            }
+        });
+        // Subscriptions are served over WebSockets, authenticated when the connection starts.
+        app.at("/graphql/ws").get(
+            GraphQLSubscription::new(api)
+                .on_connection_init(move |payload| {
+                    let sql = sql.clone();
+                    async move { auth::connection_init(&sql, payload).await }
+                })
+                .build(),
+        );
         app.listen(format!("0.0.0.0:{}", self.port)).await?;
         Ok(())
     }
@@ -38,4 +102,14 @@ async fn main() -> tide::Result<()> {
     Options::parse().serve().await
 }
 
+mod auth;
+mod export;
+mod feeds;
+mod test_accounts;
+mod test_ingest;
+mod test_live;
+mod test_migrations;
 mod test_runner;
+mod test_snapshots;
+mod test_stances;
+mod test_webhooks;
@@ -0,0 +1,56 @@
+#![cfg(test)]
+
+//! This module checks that failed sign-in attempts are throttled by username and client address.
+//!
+//! Accounts are created in a scratch database on the test server, which is recreated on each run,
+//! so this only requires the test database server to be up:
+//! ```ignore
+//! bin/start-test-db
+//! ```
+
+use anyhow::Error;
+use model::{accounts, db};
+use std::net::IpAddr;
+
+#[async_std::test]
+async fn throttle_failed_logins() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let opt = db::Options::test_scratch("bill_tracker_accounts").await?;
+    let sql = opt.connect_sql().await?;
+    accounts::setup(&sql).await?;
+    accounts::register(&sql, "dean", "dean@example.com", "password").await?;
+    let guesser: IpAddr = "192.0.2.1".parse()?;
+    let dean: IpAddr = "198.51.100.7".parse()?;
+
+    // Guessing from one address is cut off after a few attempts...
+    for _ in 0..5 {
+        assert!(accounts::login(&sql, "dean", "guess", Some(guesser))
+            .await?
+            .is_none());
+    }
+    assert!(accounts::login(&sql, "dean", "password", Some(guesser))
+        .await
+        .is_err());
+
+    // ...but the user can still sign in from elsewhere.
+    let user = accounts::login(&sql, "dean", "password", Some(dean)).await?;
+    assert_eq!(user.unwrap().username, "dean");
+
+    // Failures for usernames which don't exist are recorded too, but are forgotten once their
+    // window has passed.
+    assert!(accounts::login(&sql, "nobody", "guess", Some(guesser))
+        .await?
+        .is_none());
+    sql.batch_execute("UPDATE login_failures SET window_start = now() - interval '1 day'")
+        .await?;
+    assert!(accounts::login(&sql, "dean", "password", Some(guesser))
+        .await?
+        .is_some());
+    let row = sql
+        .query_one("SELECT count(*) AS count FROM login_failures", &[])
+        .await?;
+    assert_eq!(row.try_get::<_, i64>("count")?, 0);
+
+    Ok(())
+}
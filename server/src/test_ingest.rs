@@ -0,0 +1,469 @@
+#![cfg(test)]
+
+//! This module checks that pulling changed data from Legiscan updates the database, records the
+//! changes in feeds and emails them to followers, and that pulling the same data again doesn't.
+//!
+//! The fixtures in `db/test/data` are pulled into a scratch database on the test server, which is
+//! recreated on each run, and emails are delivered to an SMTP sink run by the test itself, so this
+//! only requires the test database server to be up:
+//! ```ignore
+//! bin/start-test-db
+//! ```
+
+use anyhow::Error;
+use async_std::{
+    io::{prelude::BufReadExt, BufReader, WriteExt},
+    net::{TcpListener, TcpStream},
+    task::spawn,
+};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use model::{
+    accounts, actions,
+    db::{self, SqlClient},
+    feed::{self, FeedEventKind},
+    follows::{self, FollowKind},
+    hearings,
+    legiscan::{Legiscan, LocalClient},
+    notifications::{self, NotificationMode, SmtpOptions},
+    schema,
+    syndication::{self, Format, Subject},
+    taxonomy::Taxonomy,
+    trending, votes,
+};
+use relational_graphql::graphql::type_system::Id;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+#[async_std::test]
+async fn pull_changes() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let taxonomy = Taxonomy::load(workspace.join("db/taxonomy.json"))?;
+    let opt = db::Options::test_scratch("bill_tracker_ingest").await?;
+    let mut conn = opt.connect().await?;
+    let sql = opt.connect_sql().await?;
+    db::setup(&mut conn, &sql, &taxonomy).await?;
+
+    // Pull the fixtures as they are.
+    pull(&mut conn, &sql, workspace.join("db/test/data")).await?;
+    let (_, introduced_at) = hb1(&sql).await?;
+    let id: Id = sql
+        .query_one(
+            "SELECT id FROM catalog_bills WHERE state = 'CO' AND name = 'HB1'",
+            &[],
+        )
+        .await?
+        .try_get("id")?;
+
+    // HB1 has no history yet, so its syndication feed is empty, and dated by when the bill last
+    // changed. Feeds with nothing to date them by have never changed.
+    let hb1_feed = Subject::Bill(id.into());
+    let feed = syndication::load(&sql, &hb1_feed).await?.unwrap();
+    assert_eq!(feed.updated, introduced_at);
+    let dc_feed = Subject::State("DC".into());
+    let feed = syndication::load(&sql, &dc_feed).await?.unwrap();
+    assert_eq!(feed.updated.timestamp(), 0);
+    assert_eq!(
+        syndication::load(&sql, &dc_feed).await?.unwrap().etag(),
+        feed.etag()
+    );
+
+    // Follow HB1, asking to be emailed about changes to it as they happen.
+    let user = accounts::register(&sql, "follower", "follower@example.com", "password").await?;
+    follows::follow(&sql, user.id, FollowKind::Bill, id).await?;
+    notifications::set_preference(&sql, user.id, FollowKind::Bill, NotificationMode::Instant)
+        .await?;
+
+    // Follow Education, which covers HB1 through its sub-issue Campus Life.
+    let education: Id = sql
+        .query_one(
+            "SELECT id FROM catalog_issues WHERE name = 'Education'",
+            &[],
+        )
+        .await?
+        .try_get("id")?;
+    let student = accounts::register(&sql, "student", "student@example.com", "password").await?;
+    follows::follow(&sql, student.id, FollowKind::Issue, education).await?;
+
+    // Pull a copy of the fixtures in which HB1 has passed, after gaining a cosponsor, a hearing and
+    // a vote.
+    let changed = std::env::temp_dir().join("bill_tracker_ingest");
+    if changed.exists() {
+        fs::remove_dir_all(&changed)?;
+    }
+    copy(&workspace.join("db/test/data"), &changed)?;
+    let hb1_path = changed.join("CO/2023-2024_Regular_Session/bill/HB1.json");
+    let mut hb1_json: Value = serde_json::from_slice(&fs::read(&hb1_path)?)?;
+    hb1_json["bill"]["change_hash"] = json!("0cc175b9c0f1b6a831c399e269772661");
+    hb1_json["bill"]["title"] = json!("Student Body President, Again.");
+    hb1_json["bill"]["status"] = json!(4);
+    hb1_json["bill"]["status_date"] = json!("2023-06-01");
+    let hb2_json: Value = serde_json::from_slice(&fs::read(
+        changed.join("CO/2023-2024_Regular_Session/bill/HB2.json"),
+    )?)?;
+    let edison = hb2_json["bill"]["sponsors"][0].clone();
+    assert_eq!(edison["last_name"], "Edison");
+    hb1_json["bill"]["sponsors"]
+        .as_array_mut()
+        .unwrap()
+        .push(edison);
+    hb1_json["bill"]["history"] = json!([
+        {
+            "date": "2023-01-09",
+            "action": "Introduced In House - Assigned to Education",
+            "chamber": "H",
+            "chamber_id": 1,
+            "importance": 1,
+        },
+        {
+            "date": "2023-06-01",
+            "action": "Governor Signed",
+            "chamber": "",
+            "chamber_id": 0,
+            "importance": 1,
+        },
+    ]);
+    hb1_json["bill"]["votes"] = json!([{
+        "roll_call_id": 1001,
+        "date": "2023-05-15",
+        "desc": "Third Reading",
+        "yea": 40,
+        "nay": 22,
+        "nv": 1,
+        "absent": 2,
+        "total": 65,
+        "passed": 1,
+        "chamber": "H",
+        "chamber_id": 1,
+    }]);
+    hb1_json["bill"]["calendar"] = json!([{
+        "type_id": 1,
+        "type": "Hearing",
+        "date": "2023-02-10",
+        "time": "13:30",
+        "location": "Room 0112",
+        "description": "House Education Hearing",
+    }]);
+    fs::write(&hb1_path, serde_json::to_vec(&hb1_json)?)?;
+    pull(&mut conn, &sql, &changed).await?;
+    let (status, updated_at) = hb1(&sql).await?;
+    assert_eq!(status, "Passed");
+    assert!(updated_at > introduced_at);
+
+    // The bill itself is updated, including its hash and when it last changed.
+    let api = schema::executor(&opt).await?;
+    let res = api
+        .execute(
+            "{
+                bills(where: {has: {name: {is: {lit: \"HB1\"}}}}) {
+                    edges { node { legiscanHash title status statusDate createdAt updatedAt } }
+                }
+            }",
+        )
+        .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    let mut node = res.data.into_json()?["bills"]["edges"][0]["node"].take();
+    let node_fields = node.as_object_mut().unwrap();
+    let created_at: DateTime<Utc> = sql
+        .query_one("SELECT created_at FROM catalog_bills WHERE id = $1", &[&id])
+        .await?
+        .try_get("created_at")?;
+    for (field, expected) in [("createdAt", created_at), ("updatedAt", updated_at)] {
+        let timestamp = node_fields.remove(field).unwrap();
+        assert_eq!(
+            timestamp.as_str().unwrap().parse::<DateTime<Utc>>()?,
+            expected
+        );
+    }
+    assert_eq!(
+        node,
+        json!({
+            "legiscanHash": "0cc175b9c0f1b6a831c399e269772661",
+            "title": "Student Body President, Again.",
+            "status": "Passed",
+            "statusDate": "2023-06-01",
+        })
+    );
+
+    // The history, the vote and the hearing are recorded, and the changes appear in feeds.
+    let history = actions::actions(&sql, id).await?;
+    assert_eq!(
+        history
+            .iter()
+            .map(|action| (action.date.as_str(), action.chamber.as_deref()))
+            .collect::<Vec<_>>(),
+        [
+            ("2023-01-09", Some("House of Representatives")),
+            ("2023-06-01", None)
+        ]
+    );
+
+    // The syndication feed lists the history, newest first, dated by when each action happened.
+    // The actions happened long before they were pulled, but the feed itself is dated by the pull,
+    // so that readers who fetched it before then see that it changed.
+    let feed = syndication::load(&sql, &hb1_feed).await?.unwrap();
+    assert!(feed.updated > introduced_at);
+    assert!(feed.updated >= updated_at);
+    let self_url = "https://api.example.com/feeds/bill/1.atom".parse()?;
+    let atom = feed.render(Format::Atom, &self_url, None);
+    let signed = atom.find("<title>CO HB1: Governor Signed</title>").unwrap();
+    let introduced = atom
+        .find("<title>CO HB1: Introduced In House - Assigned to Education</title>")
+        .unwrap();
+    assert!(signed < introduced);
+    assert!(atom.contains("<updated>2023-01-09T00:00:00Z</updated>"));
+
+    let recorded = votes::votes(&sql, id).await?;
+    assert_eq!(recorded.len(), 1);
+    assert_eq!(
+        recorded[0].summary(),
+        "House of Representatives vote on Third Reading passed 40-22"
+    );
+    let scheduled = hearings::hearings(&sql, id).await?;
+    assert_eq!(scheduled.len(), 1);
+    assert_eq!(
+        scheduled[0].summary(),
+        "House Education Hearing scheduled for 2023-02-10 at 13:30 in Room 0112"
+    );
+    let summaries = feed_summaries(&sql).await?;
+    assert_eq!(
+        summaries,
+        [
+            "CO HB1 is now Passed",
+            "Annie Edison is now sponsoring CO HB1",
+            "CO HB1: House of Representatives vote on Third Reading passed 40-22",
+            "CO HB1: House Education Hearing scheduled for 2023-02-10 at 13:30 in Room 0112",
+        ]
+    );
+
+    // Following the parent issue is enough to see the changes, along with the new bill.
+    let student_feed = feed::feed(&sql, student.id, None, 100).await?;
+    assert_eq!(
+        student_feed
+            .edges
+            .iter()
+            .filter(|edge| edge.node.bill == id)
+            .count(),
+        summaries.len() + 1
+    );
+
+    // The follower is emailed about each change, including the vote and the hearing.
+    let sink = SmtpSink::start().await?;
+    let mailer = sink.options().connect()?;
+    let sent = notifications::notify(&sql, &mailer, NotificationMode::Instant).await?;
+    assert_eq!(sent, summaries.len());
+    let emails = sink.messages();
+    for summary in &summaries {
+        assert!(
+            emails
+                .iter()
+                .any(|email| email.contains(&format!("Subject: {summary}\r\n"))),
+            "no email about {summary:?} in {emails:#?}"
+        );
+    }
+    assert!(emails
+        .iter()
+        .all(|email| email.contains("To: follower@example.com\r\n")));
+
+    // The actions in the fixtures are too old to count towards trending scores, so the new
+    // cosponsor is the only recent activity.
+    let trending = sql
+        .query(
+            "SELECT b.name, t.score FROM trending_bills t JOIN catalog_bills b ON b.id = t.bill",
+            &[],
+        )
+        .await?;
+    assert_eq!(trending.len(), 1);
+    assert_eq!(trending[0].try_get::<_, String>("name")?, "HB1");
+    // The weight of a cosponsor is 2, which has barely decayed.
+    let score: f64 = trending[0].try_get("score")?;
+    assert!(score > 1.99 && score <= 2.0, "{score}");
+
+    // A vote held today outweighs the new cosponsor, moving HB2 to the top.
+    sql.execute(
+        "INSERT INTO votes (
+                legiscan_id, bill, date, description, chamber, yea, nay, not_voting, absent, passed
+            )
+            SELECT 'recent', id, CURRENT_DATE::TEXT, 'Second Reading', 'House', 33, 30, 0, 2, true
+            FROM catalog_bills WHERE state = 'CO' AND name = 'HB2'",
+        &[],
+    )
+    .await?;
+    trending::refresh(&sql).await?;
+    let trending = sql
+        .query(
+            "SELECT b.name FROM trending_bills t JOIN catalog_bills b ON b.id = t.bill
+            ORDER BY t.score DESC",
+            &[],
+        )
+        .await?
+        .into_iter()
+        .map(|row| row.try_get("name"))
+        .collect::<Result<Vec<String>, _>>()?;
+    assert_eq!(trending, ["HB2", "HB1"]);
+
+    // Pulling the same data again changes nothing.
+    pull(&mut conn, &sql, &changed).await?;
+    assert_eq!(hb1(&sql).await?, (status, updated_at));
+    assert_eq!(actions::actions(&sql, id).await?.len(), 2);
+    assert_eq!(votes::votes(&sql, id).await?.len(), 1);
+    assert_eq!(hearings::hearings(&sql, id).await?.len(), 1);
+    assert_eq!(feed_summaries(&sql).await?, summaries);
+    assert_eq!(
+        notifications::notify(&sql, &mailer, NotificationMode::Instant).await?,
+        0
+    );
+    assert_eq!(sink.messages().len(), summaries.len());
+
+    Ok(())
+}
+
+pub(super) async fn pull(
+    conn: &mut db::Connection,
+    sql: &SqlClient,
+    dir: impl Into<PathBuf>,
+) -> Result<(), Error> {
+    let client = LocalClient::open(dir.into());
+    let datasets = client.list_datasets(None, None).await?;
+    db::update::<_, PathBuf>(conn, sql, &client, datasets, None).await
+}
+
+/// The status of CO HB1, and when it last changed, according to the catalog.
+async fn hb1(sql: &SqlClient) -> Result<(String, DateTime<Utc>), Error> {
+    let row = sql
+        .query_one(
+            "SELECT status, updated_at FROM catalog_bills WHERE state = 'CO' AND name = 'HB1'",
+            &[],
+        )
+        .await?;
+    Ok((row.try_get("status")?, row.try_get("updated_at")?))
+}
+
+/// Summaries of the feed events about existing bills, oldest first.
+async fn feed_summaries(sql: &SqlClient) -> Result<Vec<String>, Error> {
+    sql.query(
+        "SELECT e.kind, e.status, e.detail, b.state, b.name FROM feed_events e
+            JOIN catalog_bills b ON b.id = e.bill
+            WHERE e.kind <> 'new_bill'
+            ORDER BY e.id",
+        &[],
+    )
+    .await?
+    .into_iter()
+    .map(|row| {
+        let kind: FeedEventKind = row.try_get::<_, String>("kind")?.parse()?;
+        Ok(kind.summary(
+            row.try_get("state")?,
+            row.try_get("name")?,
+            row.try_get("status")?,
+            row.try_get("detail")?,
+        ))
+    })
+    .collect()
+}
+
+/// Copy the directory `from` to `to`, recursively.
+fn copy(from: &Path, to: &Path) -> Result<(), Error> {
+    fs::create_dir_all(to)?;
+    for entry in from.read_dir()? {
+        let path = entry?.path();
+        let dest = to.join(path.file_name().unwrap());
+        if path.is_dir() {
+            copy(&path, &dest)?;
+        } else {
+            fs::copy(&path, &dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// A minimal SMTP server on a local port, which accepts every message and keeps it.
+struct SmtpSink {
+    port: u16,
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl SmtpSink {
+    async fn start() -> Result<Self, Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+        let messages: Arc<Mutex<Vec<String>>> = Default::default();
+        let received = messages.clone();
+        spawn(async move {
+            let mut incoming = listener.incoming();
+            while let Some(Ok(stream)) = incoming.next().await {
+                let received = received.clone();
+                spawn(async move {
+                    if let Err(err) = Self::serve(stream, received).await {
+                        tracing::warn!("SMTP sink connection failed: {err}");
+                    }
+                });
+            }
+        });
+        Ok(Self { port, messages })
+    }
+
+    /// Options for delivering email to this sink.
+    fn options(&self) -> SmtpOptions {
+        SmtpOptions {
+            smtp_host: "127.0.0.1".into(),
+            smtp_port: self.port,
+            smtp_user: None,
+            smtp_password: None,
+            smtp_tls: false,
+            email_from: "Bill Tracker <notifications@localhost>".into(),
+            app_url: None,
+        }
+    }
+
+    /// The messages received so far, with folded header lines unfolded.
+    fn messages(&self) -> Vec<String> {
+        self.messages
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|message| message.replace("\r\n ", " ").replace("\r\n\t", " "))
+            .collect()
+    }
+
+    async fn serve(stream: TcpStream, messages: Arc<Mutex<Vec<String>>>) -> Result<(), Error> {
+        let mut reader = BufReader::new(stream.clone());
+        let mut writer = stream;
+        writer.write_all(b"220 localhost SMTP sink\r\n").await?;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if reader.read_line(&mut line).await? == 0 {
+                return Ok(());
+            }
+            let command = line.to_ascii_uppercase();
+            if command.starts_with("DATA") {
+                writer
+                    .write_all(b"354 end data with <CR><LF>.<CR><LF>\r\n")
+                    .await?;
+                let mut message = String::new();
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line).await? == 0 {
+                        return Ok(());
+                    }
+                    if line == ".\r\n" {
+                        break;
+                    }
+                    message.push_str(&line);
+                }
+                messages.lock().unwrap().push(message);
+                writer.write_all(b"250 OK\r\n").await?;
+            } else if command.starts_with("QUIT") {
+                writer.write_all(b"221 bye\r\n").await?;
+                return Ok(());
+            } else {
+                writer.write_all(b"250 OK\r\n").await?;
+            }
+        }
+    }
+}
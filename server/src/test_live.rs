@@ -0,0 +1,97 @@
+#![cfg(test)]
+
+//! This module checks that changes announced with `NOTIFY` reach [live](model::live) subscribers to
+//! their topics, that slow subscribers only fall behind so far, and that changes keep arriving
+//! after the listening connection is lost.
+//!
+//! The notifications are sent through a scratch database on the test server, which is recreated on
+//! each run, so this only requires the test database server to be up:
+//! ```ignore
+//! bin/start-test-db
+//! ```
+
+use anyhow::Error;
+use async_std::future::timeout;
+use futures::{channel::mpsc::Receiver, StreamExt};
+use model::{
+    db::{self, SqlClient},
+    live::{self, Change, Changes, Topic, SUBSCRIPTION_BUFFER},
+};
+use std::time::Duration;
+
+/// How long to wait for a notification before giving up on it.
+const NOTIFICATION_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[async_std::test]
+async fn notifications() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let opt = db::Options::test_scratch("bill_tracker_live").await?;
+    let sql = opt.connect_sql().await?;
+    let changes = Changes::listen(&opt).await?;
+    let mut bill = changes.subscribe(Topic::Bill(1));
+    let mut subscription = changes.subscribe(Topic::FeedEvents);
+
+    // Each subscriber only receives the changes on its topic, in the order they were announced. The
+    // updates to bills are announced by hand, in the form `live::notify_bills_updated` uses, each
+    // in its own transaction so that Postgres doesn't fold the repeated ones together.
+    for id in ["1", "2", "1"] {
+        sql.batch_execute(&format!("NOTIFY bill_updated, '{id}'"))
+            .await?;
+    }
+    live::notify_feed_event(&sql, 3).await?;
+    assert_eq!(next(&mut subscription).await?, Change::FeedEvent(3));
+    // The hub forwards changes in order, so by now it has forwarded the updates to bills too.
+    assert_eq!(next(&mut bill).await?, Change::BillUpdated(1));
+    assert_eq!(next(&mut bill).await?, Change::BillUpdated(1));
+    assert!(bill.try_next().is_err());
+
+    // A subscriber which doesn't keep up misses changes, rather than buffering all of them.
+    let announced = SUBSCRIPTION_BUFFER + 10;
+    for _ in 0..announced {
+        sql.batch_execute("NOTIFY bill_updated, '1'").await?;
+    }
+    live::notify_feed_event(&sql, 4).await?;
+    assert_eq!(next(&mut subscription).await?, Change::FeedEvent(4));
+    let mut buffered = 0;
+    while let Ok(Some(change)) = bill.try_next() {
+        assert_eq!(change, Change::BillUpdated(1));
+        buffered += 1;
+    }
+    assert!(buffered > 0 && buffered < announced, "{buffered}");
+    // Once it has caught up, it receives changes again.
+    sql.batch_execute("NOTIFY bill_updated, '1'").await?;
+    assert_eq!(next(&mut bill).await?, Change::BillUpdated(1));
+
+    // Drop the listening connection, and check that the hub reconnects. Changes announced before
+    // it has reconnected are missed, so keep announcing until one gets through.
+    drop_other_connections(&sql).await?;
+    let mut reconnected = false;
+    for _ in 0..10 {
+        live::notify_feed_event(&sql, 5).await?;
+        if let Ok(change) = timeout(Duration::from_secs(1), subscription.next()).await {
+            assert_eq!(change, Some(Change::FeedEvent(5)));
+            reconnected = true;
+            break;
+        }
+    }
+    assert!(reconnected, "hub did not reconnect");
+    Ok(())
+}
+
+async fn next(subscription: &mut Receiver<Change>) -> Result<Change, Error> {
+    timeout(NOTIFICATION_TIMEOUT, subscription.next())
+        .await?
+        .ok_or_else(|| Error::msg("subscription ended"))
+}
+
+/// Terminate every connection to the database other than `sql`.
+async fn drop_other_connections(sql: &SqlClient) -> Result<(), Error> {
+    sql.execute(
+        "SELECT pg_terminate_backend(pid) FROM pg_stat_activity
+            WHERE datname = current_database() AND pid <> pg_backend_pid()",
+        &[],
+    )
+    .await?;
+    Ok(())
+}
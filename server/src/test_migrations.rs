@@ -0,0 +1,207 @@
+#![cfg(test)]
+
+//! This module checks that [migrations](model::migrations) bring a database set up before schema
+//! versions were introduced up to date, including the resource tables.
+//!
+//! The fixtures in `db/test/data` are pulled into a scratch database on the test server, which is
+//! recreated on each run, so this only requires the test database server to be up:
+//! ```ignore
+//! bin/start-test-db
+//! ```
+
+use super::test_ingest::pull;
+use anyhow::Error;
+use model::{
+    db::{self, SqlClient},
+    migrations, schema,
+    taxonomy::Taxonomy,
+};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+#[async_std::test]
+async fn upgrade_unversioned() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let taxonomy = Taxonomy::load(workspace.join("db/taxonomy.json"))?;
+    let opt = db::Options::test_scratch("bill_tracker_migrations").await?;
+    let mut conn = opt.connect().await?;
+    let sql = opt.connect_sql().await?;
+    db::setup(&mut conn, &sql, &taxonomy).await?;
+    pull(&mut conn, &sql, workspace.join("db/test/data")).await?;
+    let bills = bill_statuses(&sql).await?;
+    assert!(!bills.is_empty());
+
+    // Take the database back to the schema from before versions were introduced.
+    downgrade(&sql).await?;
+    assert_eq!(migrations::version(&sql).await?, 0);
+    assert!(migrations::check(&sql).await.is_err());
+
+    // A dry run changes nothing.
+    let applied = migrations::up(&opt, &sql, true).await?;
+    assert_eq!(applied.len(), migrations::MIGRATIONS.len());
+    assert_eq!(migrations::version(&sql).await?, 0);
+    assert!(!has_column(&sql, "bills", "status").await?);
+
+    let applied = migrations::up(&opt, &sql, false).await?;
+    assert_eq!(applied.len(), migrations::MIGRATIONS.len());
+    assert_eq!(migrations::version(&sql).await?, migrations::latest());
+    migrations::check(&sql).await?;
+    for (table, column) in [
+        ("states", "fips_code"),
+        ("parties", "legiscan_id"),
+        ("bills", "status"),
+        ("bills", "status_date"),
+        ("bills", "session"),
+        ("legislators", "chamber"),
+        ("legislators", "home_state"),
+        ("subjects", "name"),
+        ("issue_suggestions", "confidence"),
+    ] {
+        assert!(has_column(&sql, table, column).await?, "{table}.{column}");
+    }
+    assert!(migrations::up(&opt, &sql, false).await?.is_empty());
+
+    // The existing data is still there, and the resources can be queried again.
+    let resources = schema::resource_executor(&opt).await?;
+    let res = resources
+        .execute(
+            "{
+                states { edges { node { abbreviation fipsCode website } } }
+                parties { edges { node { abbreviation legiscanId } } }
+                bills { edges { node { legiscanId status statusDate session } } }
+                legislators {
+                    edges { node { legiscanId chamber homeState { abbreviation } } }
+                }
+                issues {
+                    edges { node { name parents { edges { node { name } } } } }
+                }
+                subjects { edges { node { name } } }
+            }",
+        )
+        .await;
+    assert!(res.errors.is_empty(), "{:?}", res.errors);
+    assert_eq!(bill_statuses(&sql).await?.len(), bills.len());
+
+    // Static data is filled in directly.
+    let row = sql
+        .query_one(
+            "SELECT fips_code, capital FROM states WHERE abbreviation = 'CO'",
+            &[],
+        )
+        .await?;
+    assert_eq!(row.try_get::<_, String>("fips_code")?, "08");
+    assert_eq!(row.try_get::<_, String>("capital")?, "Denver");
+    let row = sql
+        .query_one(
+            "SELECT legiscan_id FROM parties WHERE abbreviation = 'D'",
+            &[],
+        )
+        .await?;
+    assert_eq!(row.try_get::<_, String>("legiscan_id")?, "1");
+    let row = sql
+        .query_one(
+            "SELECT abbreviation, name, fips_code, capital FROM states WHERE id = 48",
+            &[],
+        )
+        .await?;
+    assert_eq!(row.try_get::<_, String>("abbreviation")?, "WV");
+    assert_eq!(row.try_get::<_, String>("name")?, "West Virginia");
+    assert_eq!(row.try_get::<_, String>("fips_code")?, "54");
+    assert_eq!(row.try_get::<_, String>("capital")?, "Charleston");
+    let row = sql
+        .query_one("SELECT abbreviation FROM states WHERE id = 49", &[])
+        .await?;
+    assert_eq!(row.try_get::<_, String>("abbreviation")?, "WI");
+
+    // The rest is filled in by the next pull.
+    assert!(bill_statuses(&sql)
+        .await?
+        .values()
+        .all(|(status, session)| status.is_empty() && session.is_empty()));
+    pull(&mut conn, &sql, workspace.join("db/test/data")).await?;
+    assert_eq!(bill_statuses(&sql).await?, bills);
+    let row = sql
+        .query_one(
+            "SELECT count(*) AS count FROM legislators WHERE chamber = ''",
+            &[],
+        )
+        .await?;
+    assert_eq!(row.try_get::<_, i64>("count")?, 0);
+
+    Ok(())
+}
+
+/// Remove everything added to the schema since before versions were introduced, and swap the
+/// abbreviations of West Virginia and Wisconsin back.
+async fn downgrade(sql: &SqlClient) -> Result<(), Error> {
+    sql.batch_execute(
+        "DROP TABLE schema_migrations, login_failures;
+        DROP INDEX users_email_lower;
+        ALTER TABLE states
+            DROP COLUMN fips_code,
+            DROP COLUMN capital,
+            DROP COLUMN legislature,
+            DROP COLUMN upper_chamber,
+            DROP COLUMN lower_chamber,
+            DROP COLUMN session_calendar,
+            DROP COLUMN website;
+        ALTER TABLE parties DROP COLUMN legiscan_id;
+        ALTER TABLE bills DROP COLUMN status, DROP COLUMN status_date, DROP COLUMN session;
+        ALTER TABLE legislators DROP COLUMN chamber, DROP COLUMN home_state;
+        DROP TABLE subjects, issue_suggestions CASCADE;
+        UPDATE states SET abbreviation = '~' || abbreviation WHERE id IN (48, 49);
+        UPDATE states SET abbreviation = CASE id WHEN 48 THEN 'WI' ELSE 'WV' END
+            WHERE id IN (48, 49);",
+    )
+    .await?;
+
+    // The tables of the issue hierarchy relations.
+    let tables = sql
+        .query(
+            "SELECT table_name FROM information_schema.tables
+            WHERE table_schema = current_schema()
+                AND table_name NOT LIKE 'catalog\\_%'
+                AND (
+                    table_name LIKE '%parent%' OR table_name LIKE '%child%'
+                    OR table_name LIKE '%ancestor%' OR table_name LIKE '%descendant%'
+                )",
+            &[],
+        )
+        .await?;
+    for row in tables {
+        let table: String = row.try_get("table_name")?;
+        sql.batch_execute(&format!("DROP TABLE IF EXISTS \"{table}\" CASCADE"))
+            .await?;
+    }
+    Ok(())
+}
+
+/// Whether `table` has a column called `column`.
+async fn has_column(sql: &SqlClient, table: &str, column: &str) -> Result<bool, Error> {
+    let row = sql
+        .query_one(
+            "SELECT EXISTS (
+                SELECT 1 FROM information_schema.columns
+                WHERE table_schema = current_schema() AND table_name = $1 AND column_name = $2
+            ) AS present",
+            &[&table, &column],
+        )
+        .await?;
+    Ok(row.try_get("present")?)
+}
+
+/// The status and session of each bill, by Legiscan ID.
+async fn bill_statuses(sql: &SqlClient) -> Result<BTreeMap<String, (String, String)>, Error> {
+    sql.query("SELECT legiscan_id, status, session FROM bills", &[])
+        .await?
+        .into_iter()
+        .map(|row| {
+            Ok((
+                row.try_get("legiscan_id")?,
+                (row.try_get("status")?, row.try_get("session")?),
+            ))
+        })
+        .collect()
+}
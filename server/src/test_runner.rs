@@ -9,7 +9,8 @@
 //! This runner will start a server and scan that directory for all such pairs of files, executing
 //! each query and making sure that the response matches the expected response. Before comparing
 //! the expected and actual responses, the test runner will sort any array named "edges", to avoid
-//! dependency on implementation-defined ordering.
+//! dependency on implementation-defined ordering. Test cases for sorted listings should query
+//! `nodes` instead, which is compared in order.
 //!
 //! To run these tests, first make sure the test database is up and running, if you haven't already:
 //! ```ignore
@@ -55,6 +56,7 @@ async fn graphql_api_test_cases() -> Result<(), Error> {
     let port = pick_unused_port().unwrap();
     let opt = Options {
         port,
+        app_url: None,
         db: db::Options::test(),
     };
     spawn(async move {
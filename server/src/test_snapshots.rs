@@ -0,0 +1,257 @@
+#![cfg(test)]
+
+//! This module checks that snapshots of the test database survive a round trip through
+//! [export](model::export) and import unchanged, and that imports which can't be completed are
+//! refused without changing the database.
+//!
+//! Like the GraphQL test cases, this requires the test database to be up and seeded with the
+//! fixtures in `db/test/data`:
+//! ```ignore
+//! bin/start-test-db
+//! cargo run --release --bin create-test-db
+//! ```
+//! The snapshots are imported into scratch databases on the same server, which are recreated on
+//! each run.
+
+use anyhow::Error;
+use model::{
+    db::{self, SqlClient},
+    export::{self, Export, ExportFilter, Format, Rows, Table},
+    taxonomy::Taxonomy,
+};
+use std::path::Path;
+use strum::IntoEnumIterator;
+
+const FORMATS: [Format; 3] = [Format::Csv, Format::Jsonl, Format::Parquet];
+
+#[async_std::test]
+async fn export_import_round_trip() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let taxonomy = Taxonomy::load(workspace.join("db/taxonomy.json"))?;
+
+    // Export the test database in every format.
+    let opt = db::Options::test();
+    let snapshot = export_all(&mut opt.connect_sql().await?).await?;
+
+    for format in FORMATS {
+        tracing::info!("round trip through {format}");
+
+        // Import the snapshot into an empty database.
+        let import_opt =
+            db::Options::test_scratch(&format!("bill_tracker_import_{format}")).await?;
+        let mut import_conn = import_opt.connect().await?;
+        let mut import_sql = import_opt.connect_sql().await?;
+        let tables = snapshot
+            .iter()
+            .filter(|(table_format, _, _)| *table_format == format)
+            .map(|(_, table, data)| Rows::read(*table, format, data.clone()))
+            .collect::<Result<Vec<_>, _>>()?;
+        db::setup(&mut import_conn, &import_sql, &taxonomy).await?;
+        export::import(&import_opt, &import_sql, &tables).await?;
+
+        // Importing it again is refused, since the database is no longer empty.
+        assert!(export::import(&import_opt, &import_sql, &tables)
+            .await
+            .is_err());
+
+        // Export it again, and check that nothing changed.
+        let round_trip = export_all(&mut import_sql).await?;
+        for ((table_format, table, expected), (_, _, actual)) in snapshot.iter().zip(&round_trip) {
+            assert!(
+                expected == actual,
+                "{} changed after a round trip through {format}",
+                table_format.file_name(*table)
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[async_std::test]
+async fn import_rejects_dangling_references() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let taxonomy = Taxonomy::load(workspace.join("db/taxonomy.json"))?;
+    let opt = db::Options::test_scratch("bill_tracker_import_dangling").await?;
+    let mut conn = opt.connect().await?;
+    let sql = opt.connect_sql().await?;
+    db::setup(&mut conn, &sql, &taxonomy).await?;
+
+    // A bill sponsored by a legislator who isn't in the snapshot.
+    let tables = [
+        Rows {
+            table: Table::Bills,
+            rows: vec![[
+                "1",
+                "hash",
+                "CO",
+                "2023",
+                "HB1",
+                "Title",
+                "Summary",
+                "Passed",
+                "2023-06-01",
+            ]
+            .map(String::from)
+            .to_vec()],
+        },
+        Rows {
+            table: Table::Sponsorships,
+            rows: vec![vec!["1".into(), "2".into()]],
+        },
+    ];
+    let err = export::import(&opt, &sql, &tables).await.unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "snapshot refers to unknown legislator \"2\""
+    );
+
+    // Nothing was written.
+    let bills: i64 = sql
+        .query_one("SELECT count(*) FROM bills", &[])
+        .await?
+        .try_get(0)?;
+    assert_eq!(bills, 0);
+    Ok(())
+}
+
+#[async_std::test]
+async fn import_rolls_back_failures() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let taxonomy = Taxonomy::load(workspace.join("db/taxonomy.json"))?;
+    let opt = db::Options::test_scratch("bill_tracker_import_rollback").await?;
+    let mut conn = opt.connect().await?;
+    let sql = opt.connect_sql().await?;
+    db::setup(&mut conn, &sql, &taxonomy).await?;
+
+    // A snapshot whose references all resolve, but which records the same vote twice. The database
+    // refuses the second vote only after the bill and its sponsor have been inserted.
+    let vote = row(&[
+        "10",
+        "1",
+        "2023-03-01",
+        "Third reading",
+        "H",
+        "40",
+        "25",
+        "0",
+        "0",
+        "true",
+    ]);
+    let mut tables = vec![
+        Rows {
+            table: Table::Districts,
+            rows: vec![row(&["CO", "HD-CO-1"])],
+        },
+        Rows {
+            table: Table::Legislators,
+            rows: vec![row(&[
+                "2", "hash", "CO", "HD-CO-1", "H", "Jane", "", "Doe", "1", "CO",
+            ])],
+        },
+        Rows {
+            table: Table::Bills,
+            rows: vec![row(&[
+                "1",
+                "hash",
+                "CO",
+                "2023",
+                "HB1",
+                "Title",
+                "Summary",
+                "Passed",
+                "2023-06-01",
+            ])],
+        },
+        Rows {
+            table: Table::Sponsorships,
+            rows: vec![row(&["1", "2"])],
+        },
+        Rows {
+            table: Table::Votes,
+            rows: vec![vote.clone(), vote],
+        },
+    ];
+    assert!(export::import(&opt, &sql, &tables).await.is_err());
+
+    // Nothing was written, and the scratch schema is gone.
+    for table in [
+        "bills",
+        "legislators",
+        "catalog_bills",
+        "catalog_sponsors",
+        "votes",
+    ] {
+        assert_eq!(count(&sql, table).await?, 0, "{table}");
+    }
+    let staged: bool = sql
+        .query_one(
+            "SELECT EXISTS (SELECT 1 FROM pg_namespace WHERE nspname = 'import_scratch')",
+            &[],
+        )
+        .await?
+        .try_get(0)?;
+    assert!(!staged);
+
+    // So the import can be retried once the snapshot is fixed.
+    tables.last_mut().unwrap().rows.pop();
+    export::import(&opt, &sql, &tables).await?;
+    for table in [
+        "bills",
+        "legislators",
+        "catalog_bills",
+        "catalog_sponsors",
+        "votes",
+    ] {
+        assert_eq!(count(&sql, table).await?, 1, "{table}");
+    }
+    Ok(())
+}
+
+/// A row of a snapshot.
+fn row(fields: &[&str]) -> Vec<String> {
+    fields.iter().map(|field| field.to_string()).collect()
+}
+
+/// The number of rows in `table`.
+async fn count(sql: &SqlClient, table: &str) -> Result<i64, Error> {
+    Ok(sql
+        .query_one(&format!("SELECT count(*) FROM {table}"), &[])
+        .await?
+        .try_get(0)?)
+}
+
+/// Export every table in every format.
+///
+/// Each file is streamed a chunk at a time, as the server does, and checked against the same table
+/// loaded and written all at once.
+async fn export_all(sql: &mut SqlClient) -> Result<Vec<(Format, Table, Vec<u8>)>, Error> {
+    let mut files = vec![];
+    for table in Table::iter() {
+        let rows = export::load(sql, table, &ExportFilter::default()).await?;
+        for format in FORMATS {
+            let mut data = vec![];
+            let mut export = Export::start(sql, table, &ExportFilter::default()).await?;
+            while let Some(chunk) = export.next_chunk(format).await? {
+                data.extend(chunk);
+            }
+            drop(export);
+
+            let mut expected = vec![];
+            rows.write(format, &mut expected)?;
+            assert!(
+                data == expected,
+                "streamed {} differs from loaded table",
+                format.file_name(table)
+            );
+            files.push((format, table, data));
+        }
+    }
+    Ok(files)
+}
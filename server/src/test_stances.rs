@@ -0,0 +1,74 @@
+#![cfg(test)]
+
+//! This module checks that stances are counted by the districts their users live in, including
+//! congressional districts, whose constituents count towards the state the district belongs to.
+//!
+//! The GraphQL test cases can't refer to districts by ID, since the IDs depend on the order in
+//! which districts are inserted, so this looks them up by name instead. Like the test cases, it
+//! requires the test database to be up and seeded with the fixtures in `db/test/data`:
+//! ```ignore
+//! bin/start-test-db
+//! cargo run --release --bin create-test-db
+//! ```
+
+use anyhow::Error;
+use model::{
+    db::{self, Connection},
+    legiscan::State,
+    stances::{self, StanceCounts, SupporterFilter},
+};
+use relational_graphql::graphql::type_system::Id;
+
+#[async_std::test]
+async fn counts_by_district() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let opt = db::Options::test();
+    let conn = opt.connect().await?;
+    let sql = opt.connect_sql().await?;
+    let bill: Id = sql
+        .query_one("SELECT min(id) AS id FROM catalog_bills", &[])
+        .await?
+        .try_get("id")?;
+    let hd1 = district(&conn, State::CO, "HD-001").await?;
+    let hd2 = district(&conn, State::CO, "HD-002").await?;
+    let sd1 = district(&conn, State::DC, "SD-001").await?;
+    let co2 = district(&conn, State::US, "HD-CO-2").await?;
+
+    let counts = |state: Option<&[&str]>, district: Option<Vec<Id>>| {
+        let sql = &sql;
+        let filter = SupporterFilter {
+            state: state.map(|states| states.iter().map(|state| state.to_string()).collect()),
+            district,
+        };
+        async move { stances::stance_counts(sql, bill, filter).await }
+    };
+    assert_counts(counts(None, Some(vec![hd1])).await?, 1, 0, 1);
+    assert_counts(counts(None, Some(vec![hd1, hd2])).await?, 1, 1, 1);
+    assert_counts(counts(None, Some(vec![hd2, sd1])).await?, 1, 1, 0);
+    assert_counts(counts(Some(&["CO"]), Some(vec![sd1])).await?, 0, 0, 0);
+    assert_counts(counts(Some(&["DC"]), Some(vec![sd1])).await?, 1, 0, 0);
+    assert_counts(counts(None, Some(vec![co2])).await?, 1, 0, 0);
+    assert_counts(counts(None, Some(vec![hd2, co2])).await?, 1, 1, 0);
+    assert_counts(counts(Some(&["CO"]), Some(vec![co2])).await?, 1, 0, 0);
+    assert_counts(counts(Some(&["US"]), Some(vec![co2])).await?, 0, 0, 0);
+    Ok(())
+}
+
+async fn district(conn: &Connection, state: State, name: &str) -> Result<Id, Error> {
+    match db::find_district(conn, state, name.into()).await? {
+        Some(district) => Ok(district.id),
+        None => Err(Error::msg(format!("no district {state} {name}"))),
+    }
+}
+
+fn assert_counts(counts: StanceCounts, support: i64, oppose: i64, neutral: i64) {
+    assert_eq!(
+        (
+            counts.support_count,
+            counts.oppose_count,
+            counts.neutral_count
+        ),
+        (support, oppose, neutral)
+    );
+}
@@ -0,0 +1,218 @@
+#![cfg(test)]
+
+//! This module checks that webhooks deliver new feed events to receivers, concurrently across
+//! webhooks and in order within each, and log failed deliveries for retrying.
+//!
+//! The fixtures in `db/test/data` are pulled into a scratch database on the test server, which is
+//! recreated on each run, and deliveries are made to an HTTP receiver run by the test itself, so
+//! this only requires the test database server to be up:
+//! ```ignore
+//! bin/start-test-db
+//! ```
+
+use super::test_ingest::pull;
+use anyhow::Error;
+use async_std::{
+    net::TcpStream,
+    task::{sleep, spawn},
+};
+use model::{
+    accounts,
+    db::{self, SqlClient},
+    taxonomy::Taxonomy,
+    webhooks::{self, DeliveryStatus, WebhookDelivery, WebhookFilter},
+};
+use portpicker::pick_unused_port;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the receiver takes to accept each delivery.
+const RESPONSE_DELAY: Duration = Duration::from_secs(1);
+
+/// A delivery accepted by the receiver.
+#[derive(Clone, Debug)]
+struct Received {
+    /// The name of the receiving endpoint.
+    endpoint: String,
+    delivery: i32,
+    signature: String,
+    body: Value,
+}
+
+type Log = Arc<Mutex<Vec<Received>>>;
+
+#[async_std::test]
+async fn deliveries() -> Result<(), Error> {
+    relational_graphql::init_logging();
+
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR")).parent().unwrap();
+    let taxonomy = Taxonomy::load(workspace.join("db/taxonomy.json"))?;
+    let opt = db::Options::test_scratch("bill_tracker_webhooks").await?;
+    let mut conn = opt.connect().await?;
+    let sql = opt.connect_sql().await?;
+    db::setup(&mut conn, &sql, &taxonomy).await?;
+    let user = accounts::register(&sql, "hooked", "hooked@example.com", "password").await?;
+    let secret = "correct horse battery staple";
+
+    // Webhooks can't be pointed at our own network.
+    for url in ["http://127.0.0.1/hook", "http://localhost:8080/hook"] {
+        assert!(
+            webhooks::create_webhook(&sql, user.id, url, secret, Default::default())
+                .await
+                .is_err(),
+            "{url}"
+        );
+    }
+
+    // Except by tests, which run their receiver locally.
+    webhooks::allow_private_addresses();
+    let port = pick_unused_port().unwrap();
+    let receiver = start_receiver(port).await?;
+    let base = format!("http://127.0.0.1:{port}");
+    let co = webhooks::create_webhook(
+        &sql,
+        user.id,
+        &format!("{base}/ok/co"),
+        secret,
+        WebhookFilter {
+            state: Some(vec!["CO".into()]),
+            ..Default::default()
+        },
+    )
+    .await?;
+    let all = webhooks::create_webhook(
+        &sql,
+        user.id,
+        &format!("{base}/ok/all"),
+        secret,
+        Default::default(),
+    )
+    .await?;
+    let failing = webhooks::create_webhook(
+        &sql,
+        user.id,
+        &format!("{base}/fail"),
+        secret,
+        Default::default(),
+    )
+    .await?;
+
+    // Pulling the fixtures introduces 2 bills in CO and 1 in DC.
+    pull(&mut conn, &sql, workspace.join("db/test/data")).await?;
+    assert_eq!(webhooks::enqueue(&sql).await?, 2 + 3 + 3);
+
+    // The receiver is slow, so delivering to each webhook in turn would take as long as all 5
+    // successful deliveries. Delivering to the webhooks concurrently takes only as long as the 3
+    // deliveries to the busiest one.
+    let start = Instant::now();
+    assert_eq!(webhooks::deliver(&sql).await?, 2 + 3);
+    let elapsed = start.elapsed();
+    assert!(elapsed < 4 * RESPONSE_DELAY, "{elapsed:?}");
+
+    let received = receiver.lock().unwrap().clone();
+    let endpoint = |name: &str| {
+        received
+            .iter()
+            .filter(|received| received.endpoint == name)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+    let co_received = endpoint("co");
+    assert_eq!(co_received.len(), 2);
+    assert!(co_received
+        .iter()
+        .all(|received| received.body["bill"]["state"] == "CO"));
+    let all_received = endpoint("all");
+    assert_eq!(all_received.len(), 3);
+    // Each webhook's deliveries arrive in order.
+    for deliveries in [&co_received, &all_received] {
+        assert!(deliveries
+            .windows(2)
+            .all(|pair| pair[0].delivery < pair[1].delivery));
+    }
+    for received in &received {
+        assert_eq!(received.body["delivery"], received.delivery);
+        assert_eq!(received.body["event"]["kind"], "new_bill");
+        assert!(received.signature.starts_with("sha256="));
+        assert_eq!(received.signature.len(), "sha256=".len() + 64);
+    }
+
+    // The successful deliveries are logged as delivered, and the failed ones are left to retry.
+    for webhook in [co.id, all.id] {
+        assert!(log(&sql, user.id, webhook)
+            .await?
+            .iter()
+            .all(|delivery| delivery.status == DeliveryStatus::Delivered
+                && delivery.response_status == Some(200)
+                && delivery.next_attempt_at.is_none()));
+    }
+    let failed = log(&sql, user.id, failing.id).await?;
+    assert_eq!(failed.len(), 3);
+    for delivery in &failed {
+        assert_eq!(delivery.status, DeliveryStatus::Pending);
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.response_status, Some(500));
+        assert_eq!(
+            delivery.error.as_deref(),
+            Some("receiver responded with status 500")
+        );
+        assert!(delivery.next_attempt_at > delivery.attempted_at);
+    }
+
+    // Nothing else is due yet.
+    assert_eq!(webhooks::enqueue(&sql).await?, 0);
+    assert_eq!(webhooks::deliver(&sql).await?, 0);
+    assert_eq!(receiver.lock().unwrap().len(), 2 + 3);
+
+    Ok(())
+}
+
+/// Start an HTTP receiver on `port`, returning the log of the deliveries it accepts.
+///
+/// Deliveries to `/ok/<name>` are accepted after [`RESPONSE_DELAY`]. Deliveries to `/fail` are
+/// rejected immediately.
+async fn start_receiver(port: u16) -> Result<Log, Error> {
+    let log = Log::default();
+    let mut app = tide::with_state(log.clone());
+    app.at("/ok/:name")
+        .post(|mut req: tide::Request<Log>| async move {
+            let endpoint = req.param("name")?.to_string();
+            let header = |name: &str| {
+                req.header(name)
+                    .map(|values| values.as_str().to_string())
+                    .unwrap_or_default()
+            };
+            let delivery = header("X-Bill-Tracker-Delivery").parse()?;
+            let signature = header("X-Bill-Tracker-Signature");
+            let body = req.body_json().await?;
+            sleep(RESPONSE_DELAY).await;
+            req.state().lock().unwrap().push(Received {
+                endpoint,
+                delivery,
+                signature,
+                body,
+            });
+            Ok(tide::StatusCode::Ok)
+        });
+    app.at("/fail")
+        .post(|_: tide::Request<Log>| async { Ok(tide::StatusCode::InternalServerError) });
+    spawn(app.listen(format!("127.0.0.1:{port}")));
+
+    // Wait for the receiver to come up.
+    while TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+        sleep(Duration::from_millis(100)).await;
+    }
+    Ok(log)
+}
+
+/// The delivery log of `user`'s webhook `webhook`.
+async fn log(sql: &SqlClient, user: i32, webhook: i32) -> Result<Vec<WebhookDelivery>, Error> {
+    Ok(webhooks::deliveries(sql, user, webhook, None, 0, 100)
+        .await?
+        .edges
+        .into_iter()
+        .map(|edge| edge.node)
+        .collect())
+}